@@ -0,0 +1,399 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Serializes read-modify-write cycles on the collection store
+static COLLECTION_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CollectionContent {
+    /// A saved library query, evaluated whenever the collection is opened
+    Smart { query: String },
+    /// Media ids in the order they were arranged
+    Playlist { items: Vec<String> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    pub name: String,
+    #[serde(flatten)]
+    pub content: CollectionContent,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionInfo {
+    pub id: String,
+    #[serde(flatten)]
+    pub collection: Collection,
+}
+
+fn get_collection_store(data_dir: &std::path::Path) -> Option<jfs::Store> {
+    match jfs::Store::new_with_cfg(
+        data_dir.join("collections"),
+        jfs::Config {
+            single: true,
+            indent: 2,
+            pretty: true,
+        },
+    ) {
+        Ok(store) => Some(store),
+        Err(_) => None,
+    }
+}
+
+fn validate(collection: &Collection) -> Result<(), String> {
+    if collection.name.trim().is_empty() {
+        return Err("collection name is empty".to_string());
+    }
+    if let CollectionContent::Smart { query } = &collection.content {
+        crate::query::parse(query).map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+pub fn list_collections(data_dir: &std::path::Path) -> Vec<CollectionInfo> {
+    let mut collections = get_collection_store(data_dir)
+        .and_then(|store| store.all::<Collection>().ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(id, collection)| CollectionInfo { id, collection })
+        .collect::<Vec<_>>();
+    collections.sort_by(|a, b| {
+        a.collection
+            .name
+            .to_lowercase()
+            .cmp(&b.collection.name.to_lowercase())
+    });
+
+    collections
+}
+
+pub fn get_collection(data_dir: &std::path::Path, id: &str) -> Result<Collection, String> {
+    get_collection_store(data_dir)
+        .ok_or("collection store not found".to_string())?
+        .get::<Collection>(id)
+        .map_err(|_| format!("collection {id} not found"))
+}
+
+pub fn create_collection(
+    data_dir: &std::path::Path,
+    collection: Collection,
+) -> Result<CollectionInfo, String> {
+    validate(&collection)?;
+
+    let _lock = COLLECTION_LOCK.lock().map_err(|e| e.to_string())?;
+    let store = get_collection_store(data_dir).ok_or("collection store not found".to_string())?;
+    let id = store.save(&collection).map_err(|e| e.to_string())?;
+
+    Ok(CollectionInfo { id, collection })
+}
+
+fn update_collection(
+    data_dir: &std::path::Path,
+    id: &str,
+    update: impl FnOnce(&mut Collection) -> Result<(), String>,
+) -> Result<CollectionInfo, String> {
+    let _lock = COLLECTION_LOCK.lock().map_err(|e| e.to_string())?;
+    let store = get_collection_store(data_dir).ok_or("collection store not found".to_string())?;
+
+    let mut collection = store
+        .get::<Collection>(id)
+        .map_err(|_| format!("collection {id} not found"))?;
+    update(&mut collection)?;
+    validate(&collection)?;
+    store
+        .save_with_id(&collection, id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(CollectionInfo {
+        id: id.to_string(),
+        collection,
+    })
+}
+
+pub fn rename_collection(
+    data_dir: &std::path::Path,
+    id: &str,
+    name: &str,
+) -> Result<CollectionInfo, String> {
+    update_collection(data_dir, id, |collection| {
+        collection.name = name.trim().to_string();
+        Ok(())
+    })
+}
+
+pub fn set_smart_query(
+    data_dir: &std::path::Path,
+    id: &str,
+    query: &str,
+) -> Result<CollectionInfo, String> {
+    update_collection(data_dir, id, |collection| match &mut collection.content {
+        CollectionContent::Smart { query: current } => {
+            *current = query.to_string();
+            Ok(())
+        }
+        CollectionContent::Playlist { .. } => Err("not a smart collection".to_string()),
+    })
+}
+
+fn update_playlist(
+    data_dir: &std::path::Path,
+    id: &str,
+    update: impl FnOnce(&mut Vec<String>) -> Result<(), String>,
+) -> Result<CollectionInfo, String> {
+    update_collection(data_dir, id, |collection| match &mut collection.content {
+        CollectionContent::Playlist { items } => update(items),
+        CollectionContent::Smart { .. } => Err("not a playlist".to_string()),
+    })
+}
+
+/// Inserts at `position`, or appends. Items already in the playlist move.
+pub fn add_to_playlist(
+    data_dir: &std::path::Path,
+    id: &str,
+    media_ids: &[String],
+    position: Option<usize>,
+) -> Result<CollectionInfo, String> {
+    update_playlist(data_dir, id, |items| {
+        insert_items(items, media_ids, position);
+        Ok(())
+    })
+}
+
+fn insert_items(items: &mut Vec<String>, media_ids: &[String], position: Option<usize>) {
+    let adding = media_ids.iter().collect::<HashSet<_>>();
+    let before = position.unwrap_or(items.len()).min(items.len());
+    // items that move from before the insertion point shift it left
+    let shift = items[..before]
+        .iter()
+        .filter(|item| adding.contains(item))
+        .count();
+    items.retain(|item| !adding.contains(item));
+
+    let mut seen = HashSet::new();
+    let new_items = media_ids
+        .iter()
+        .filter(|media_id| seen.insert(*media_id))
+        .cloned()
+        .collect::<Vec<_>>();
+    let at = before - shift;
+    items.splice(at..at, new_items);
+}
+
+pub fn remove_from_playlist(
+    data_dir: &std::path::Path,
+    id: &str,
+    media_ids: &[String],
+) -> Result<CollectionInfo, String> {
+    update_playlist(data_dir, id, |items| {
+        items.retain(|item| !media_ids.contains(item));
+        Ok(())
+    })
+}
+
+pub fn move_in_playlist(
+    data_dir: &std::path::Path,
+    id: &str,
+    from: usize,
+    to: usize,
+) -> Result<CollectionInfo, String> {
+    update_playlist(data_dir, id, |items| move_item(items, from, to))
+}
+
+fn move_item(items: &mut Vec<String>, from: usize, to: usize) -> Result<(), String> {
+    if from >= items.len() || to >= items.len() {
+        return Err("position out of range".to_string());
+    }
+    let item = items.remove(from);
+    items.insert(to, item);
+    Ok(())
+}
+
+pub fn delete_collection(data_dir: &std::path::Path, id: &str) -> Result<(), String> {
+    let _lock = COLLECTION_LOCK.lock().map_err(|e| e.to_string())?;
+    let store = get_collection_store(data_dir).ok_or("collection store not found".to_string())?;
+    store.delete(id).map_err(|e| e.to_string())
+}
+
+/// Media ids of a collection in its own order, playlist entries whose files
+/// are gone are skipped
+pub fn collection_media_ids(
+    data_dir: &std::path::Path,
+    media_dir: &std::path::PathBuf,
+    id: &str,
+) -> Result<Vec<String>, String> {
+    match get_collection(data_dir, id)?.content {
+        CollectionContent::Smart { query } => {
+            let mut media_ids = crate::local_files::media_ids_in_dir(media_dir);
+            media_ids.sort();
+            crate::query::filter_media_ids(&query, media_ids, media_dir, data_dir)
+                .map_err(|e| e.to_string())
+        }
+        CollectionContent::Playlist { items } => Ok(items
+            .into_iter()
+            .filter(|media_id| crate::local_files::resolve_media_id(media_dir, media_id).is_some())
+            .collect()),
+    }
+}
+
+fn m3u_entry(
+    path: &std::path::Path,
+    media_id: &str,
+    record: Option<&crate::library::MediaRecord>,
+    source: Option<&crate::downloads::SourceMetadata>,
+) -> String {
+    let title = source
+        .and_then(|source| source.title.clone())
+        .unwrap_or_else(|| media_id.to_string());
+    let duration = record
+        .and_then(|record| record.duration)
+        .map(|duration| duration.round() as i64)
+        .unwrap_or(-1);
+    // a line break in the title would end the entry early
+    let title = title.replace(['\r', '\n'], " ");
+
+    format!("#EXTINF:{duration},{title}\n{}\n", path.display())
+}
+
+/// An extended M3U playlist of the collection with absolute paths
+pub fn export_m3u(
+    data_dir: &std::path::Path,
+    media_dir: &std::path::PathBuf,
+    id: &str,
+) -> Result<String, String> {
+    let collection = get_collection(data_dir, id)?;
+    let media_ids = collection_media_ids(data_dir, media_dir, id)?;
+    let records = crate::library::all_records(data_dir);
+    let sources = crate::downloads::all_sources(data_dir);
+
+    let mut m3u = format!(
+        "#EXTM3U\n#PLAYLIST:{}\n",
+        collection.name.replace(['\r', '\n'], " ")
+    );
+    for media_id in media_ids {
+        m3u.push_str(&m3u_entry(
+            &media_dir.join(&media_id),
+            &media_id,
+            records.get(&media_id),
+            sources.get(&media_id),
+        ));
+    }
+
+    Ok(m3u)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn inserted(items: &[&str], adding: &[&str], position: Option<usize>) -> Vec<String> {
+        let mut items = ids(items);
+        insert_items(&mut items, &ids(adding), position);
+        items
+    }
+
+    #[test]
+    fn inserts_new_items_at_the_position() {
+        assert_eq!(
+            inserted(&["a", "b", "c"], &["x", "y"], Some(1)),
+            ["a", "x", "y", "b", "c"]
+        );
+        assert_eq!(inserted(&["a", "b"], &["x"], None), ["a", "b", "x"]);
+        assert_eq!(inserted(&["a", "b"], &["x"], Some(0)), ["x", "a", "b"]);
+        // past the end appends
+        assert_eq!(inserted(&["a", "b"], &["x"], Some(9)), ["a", "b", "x"]);
+        assert_eq!(inserted(&[], &["x", "x", "y"], Some(3)), ["x", "y"]);
+    }
+
+    #[test]
+    fn items_moving_from_before_the_position_shift_it_left() {
+        // "a" and "b" leave the slots before position 3, so the block lands
+        // right before "d" where the caller pointed
+        assert_eq!(
+            inserted(&["a", "b", "c", "d", "e"], &["b", "a"], Some(3)),
+            ["c", "b", "a", "d", "e"]
+        );
+        // items after the position don't move it
+        assert_eq!(
+            inserted(&["a", "b", "c", "d", "e"], &["e", "x"], Some(1)),
+            ["a", "e", "x", "b", "c", "d"]
+        );
+        // a mix of both
+        assert_eq!(
+            inserted(&["a", "b", "c", "d", "e"], &["a", "e"], Some(2)),
+            ["b", "a", "e", "c", "d"]
+        );
+        // re-adding everything just reorders
+        assert_eq!(inserted(&["a", "b"], &["b", "a"], None), ["b", "a"]);
+    }
+
+    #[test]
+    fn moves_items_both_ways() {
+        let mut items = ids(&["a", "b", "c", "d"]);
+        move_item(&mut items, 0, 2).unwrap();
+        assert_eq!(items, ["b", "c", "a", "d"]);
+        move_item(&mut items, 3, 0).unwrap();
+        assert_eq!(items, ["d", "b", "c", "a"]);
+        move_item(&mut items, 1, 1).unwrap();
+        assert_eq!(items, ["d", "b", "c", "a"]);
+
+        assert!(move_item(&mut items, 4, 0).is_err());
+        assert!(move_item(&mut items, 0, 4).is_err());
+        assert_eq!(items, ["d", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn exports_playlists_as_extended_m3u() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let media_tmp = tempfile::tempdir().unwrap();
+        let media_dir = media_tmp.path().to_path_buf();
+        for name in ["a.mp4", "b.jpg"] {
+            std::fs::write(media_dir.join(name), name).unwrap();
+        }
+        crate::library::update_record(data_dir.path(), "a.mp4", |record| {
+            record.duration = Some(61.6)
+        })
+        .unwrap();
+        crate::downloads::get_source_store(data_dir.path())
+            .unwrap()
+            .save_with_id(
+                &crate::downloads::SourceMetadata {
+                    source_url: "https://example.com/post".to_string(),
+                    media_url: "https://example.com/b.jpg".to_string(),
+                    subreddit: None,
+                    title: Some("Two\nlines".to_string()),
+                    tags: vec![],
+                    downloaded_at: 0,
+                },
+                "b.jpg",
+            )
+            .unwrap();
+
+        let collection = create_collection(
+            data_dir.path(),
+            Collection {
+                name: "Trip\nphotos".to_string(),
+                content: CollectionContent::Playlist {
+                    items: ids(&["b.jpg", "gone.mp4", "a.mp4"]),
+                },
+            },
+        )
+        .unwrap();
+
+        let m3u = export_m3u(data_dir.path(), &media_dir, &collection.id).unwrap();
+        let expected = format!(
+            "#EXTM3U\n#PLAYLIST:Trip photos\n\
+             #EXTINF:-1,Two lines\n{}\n\
+             #EXTINF:62,a.mp4\n{}\n",
+            media_dir.join("b.jpg").display(),
+            media_dir.join("a.mp4").display()
+        );
+        assert_eq!(m3u, expected);
+    }
+}
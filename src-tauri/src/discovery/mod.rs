@@ -0,0 +1,272 @@
+use mdns_sd::{IfKind, ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::Mutex;
+
+pub const SERVICE_TYPE: &str = "_scrollocal._tcp.local.";
+
+const REACHABILITY_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Peer {
+    pub name: String,
+    pub host: String,
+    pub addresses: Vec<String>,
+    pub port: u16,
+    pub library_size: Option<usize>,
+    /// Base URL of the first address that accepted a connection
+    pub url: String,
+}
+
+#[derive(Default)]
+pub struct DiscoveryState {
+    daemon: Mutex<Option<ServiceDaemon>>,
+    /// Full service name of our own advertisement, so we can skip ourselves
+    advertised: Mutex<Option<String>>,
+}
+
+impl DiscoveryState {
+    fn with_daemon<T>(
+        &self,
+        f: impl FnOnce(&ServiceDaemon) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut daemon = self.daemon.lock().map_err(|e| e.to_string())?;
+        if daemon.is_none() {
+            let new_daemon = ServiceDaemon::new().map_err(|e| e.to_string())?;
+            // lets two instances on one machine find each other
+            let _ = new_daemon.enable_interface(IfKind::LoopbackV4);
+            *daemon = Some(new_daemon);
+        }
+
+        f(daemon.as_ref().unwrap())
+    }
+}
+
+pub fn default_instance_name() -> String {
+    let host = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or("scrollocal".to_string());
+    format!("{host}-{:04x}", rand::random::<u16>())
+}
+
+/// The record [`advertise`] registers. `interface` is the address the
+/// server is bound to, the wildcard address advertises every interface.
+fn service_info(
+    instance_name: &str,
+    interface: &str,
+    port: u16,
+    library_size: usize,
+) -> Result<ServiceInfo, String> {
+    let host_name = format!("{}.local.", instance_name.replace([' ', '.'], "-"));
+    let properties = HashMap::from([
+        ("name".to_string(), instance_name.to_string()),
+        ("library_size".to_string(), library_size.to_string()),
+        ("version".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+    ]);
+
+    let is_wildcard = interface
+        .parse::<IpAddr>()
+        .map(|ip| ip.is_unspecified())
+        .unwrap_or(true);
+    let info = ServiceInfo::new(
+        SERVICE_TYPE,
+        instance_name,
+        &host_name,
+        if is_wildcard { "" } else { interface },
+        port,
+        properties,
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(if is_wildcard {
+        info.enable_addr_auto()
+    } else {
+        info
+    })
+}
+
+/// Announces the LAN media server, see [`service_info`]
+pub fn advertise(
+    state: &DiscoveryState,
+    instance_name: &str,
+    interface: &str,
+    port: u16,
+    library_size: usize,
+) -> Result<(), String> {
+    stop_advertising(state)?;
+
+    let info = service_info(instance_name, interface, port, library_size)?;
+    let full_name = info.get_fullname().to_string();
+    state.with_daemon(|daemon| daemon.register(info).map_err(|e| e.to_string()))?;
+
+    println!("advertising {full_name} on port {port}");
+    *state.advertised.lock().map_err(|e| e.to_string())? = Some(full_name);
+
+    Ok(())
+}
+
+pub fn stop_advertising(state: &DiscoveryState) -> Result<(), String> {
+    let advertised = state.advertised.lock().map_err(|e| e.to_string())?.take();
+
+    if let Some(full_name) = advertised {
+        state.with_daemon(|daemon| {
+            daemon
+                .unregister(&full_name)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// The peer a resolved record describes, or `None` when none of its
+/// addresses accepts a connection
+fn peer_from_info(info: &ServiceInfo) -> Option<Peer> {
+    let port = info.get_port();
+    let mut addresses = info.get_addresses().iter().copied().collect::<Vec<_>>();
+    // prefer routable addresses, loopback only helps on the same machine
+    addresses.sort_by_key(|ip| (ip.is_loopback(), ip.is_ipv6()));
+
+    let url = addresses
+        .iter()
+        .find(|ip| {
+            TcpStream::connect_timeout(&SocketAddr::new(**ip, port), REACHABILITY_TIMEOUT).is_ok()
+        })
+        .map(|ip| match ip {
+            IpAddr::V6(ip) => format!("http://[{ip}]:{port}/"),
+            IpAddr::V4(ip) => format!("http://{ip}:{port}/"),
+        })?;
+
+    Some(Peer {
+        name: info
+            .get_property_val_str("name")
+            .unwrap_or(info.get_fullname())
+            .to_string(),
+        host: info.get_hostname().to_string(),
+        addresses: addresses.iter().map(|ip| ip.to_string()).collect(),
+        port,
+        library_size: info
+            .get_property_val_str("library_size")
+            .and_then(|size| size.parse().ok()),
+        url,
+    })
+}
+
+/// Browses for `timeout` and returns every other instance that resolved and
+/// accepted a connection on one of its addresses. Instances that can't be
+/// reached are left out.
+pub fn discover_peers(
+    state: &DiscoveryState,
+    timeout: std::time::Duration,
+) -> Result<Vec<Peer>, String> {
+    let own_name = state.advertised.lock().map_err(|e| e.to_string())?.clone();
+    let receiver =
+        state.with_daemon(|daemon| daemon.browse(SERVICE_TYPE).map_err(|e| e.to_string()))?;
+
+    let mut peers = HashMap::<String, ServiceInfo>::new();
+    let deadline = std::time::Instant::now() + timeout;
+    while let Some(remaining) = deadline.checked_duration_since(std::time::Instant::now()) {
+        match receiver.recv_timeout(remaining) {
+            Ok(ServiceEvent::ServiceResolved(info)) => {
+                if Some(info.get_fullname()) != own_name.as_deref() {
+                    peers.insert(info.get_fullname().to_string(), info);
+                }
+            }
+            Ok(ServiceEvent::ServiceRemoved(_, full_name)) => {
+                peers.remove(&full_name);
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+
+    let _ = state.with_daemon(|daemon| daemon.stop_browse(SERVICE_TYPE).map_err(|e| e.to_string()));
+
+    let mut peers = peers
+        .into_values()
+        .filter_map(|info| peer_from_info(&info))
+        .collect::<Vec<_>>();
+    peers.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(peers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What one instance advertises is what the other resolves, minus the
+    /// multicast in between, see [`finds_an_instance_on_loopback`]
+    #[test]
+    fn advertised_records_resolve_to_a_reachable_peer() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let info = service_info("loopback test", "127.0.0.1", port, 3).unwrap();
+        assert_eq!(info.get_fullname(), format!("loopback test.{SERVICE_TYPE}"));
+
+        let peer = peer_from_info(&info).expect("advertised instance was not reachable");
+        assert_eq!(peer.name, "loopback test");
+        assert_eq!(peer.host, "loopback-test.local.");
+        assert_eq!(peer.addresses, ["127.0.0.1"]);
+        assert_eq!(peer.port, port);
+        assert_eq!(peer.library_size, Some(3));
+        assert_eq!(peer.url, format!("http://127.0.0.1:{port}/"));
+    }
+
+    #[test]
+    fn skips_unreachable_instances() {
+        // bind and drop, so nothing listens on the advertised port
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let info = service_info("unreachable-test", "127.0.0.1", port, 0).unwrap();
+        assert!(peer_from_info(&info).is_none());
+    }
+
+    #[test]
+    fn the_wildcard_address_advertises_every_interface() {
+        for interface in ["0.0.0.0", "::", "not an address"] {
+            let info = service_info("wildcard-test", interface, 8080, 0).unwrap();
+            assert!(info.is_addr_auto());
+            assert!(info.get_addresses().is_empty());
+        }
+    }
+
+    /// Two instances on one machine, the way the app finds a second copy of
+    /// itself. This goes through the daemons and needs multicast on the
+    /// loopback interface, which containers and CI runners usually lack, so
+    /// it stays opt-in:
+    ///
+    /// `cargo test discovery -- --ignored`
+    ///
+    /// To try it with the app, start two instances with separate data dirs,
+    /// start the LAN server on `127.0.0.1` in one and call `discover_peers`
+    /// from the other.
+    #[test]
+    #[ignore]
+    fn finds_an_instance_on_loopback() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = DiscoveryState::default();
+        advertise(&server, "loopback-test", "127.0.0.1", port, 3).unwrap();
+
+        let client = DiscoveryState::default();
+        let peers = discover_peers(&client, std::time::Duration::from_secs(3)).unwrap();
+        stop_advertising(&server).unwrap();
+
+        let peer = peers
+            .iter()
+            .find(|peer| peer.name == "loopback-test")
+            .expect("advertised instance was not discovered");
+        assert_eq!(peer.port, port);
+        assert_eq!(peer.library_size, Some(3));
+        assert_eq!(peer.url, format!("http://127.0.0.1:{port}/"));
+    }
+}
@@ -0,0 +1,318 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use tauri::Emitter;
+use tauri_plugin_http::reqwest;
+
+use crate::library::now;
+use crate::scrolller::types::{MediaSource, SubredditPost};
+
+pub const PROGRESS_EVENT: &str = "download-progress";
+pub const PARTIAL_EXTENSION: &str = "part";
+
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+const SCROLLLER_URL: &str = "https://scrolller.com";
+
+/// Where a downloaded item came from, stored per media id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceMetadata {
+    /// The post page, not the media file
+    pub source_url: String,
+    pub media_url: String,
+    pub subreddit: Option<String>,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub downloaded_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    pub media_id: String,
+    pub received: u64,
+    pub total: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadResult {
+    pub media_id: String,
+    /// The item was already in the library, nothing new was written
+    pub duplicate: bool,
+}
+
+pub fn get_source_store(data_dir: &std::path::Path) -> Option<jfs::Store> {
+    match jfs::Store::new_with_cfg(
+        data_dir.join("sources"),
+        jfs::Config {
+            single: true,
+            indent: 2,
+            pretty: true,
+        },
+    ) {
+        Ok(store) => Some(store),
+        Err(_) => None,
+    }
+}
+
+pub fn source_for_media(data_dir: &std::path::Path, media_id: &str) -> Option<SourceMetadata> {
+    get_source_store(data_dir).and_then(|store| store.get::<SourceMetadata>(media_id).ok())
+}
+
+/// Sources keyed by media id, for reading many without reopening the store
+pub fn all_sources(
+    data_dir: &std::path::Path,
+) -> std::collections::BTreeMap<String, SourceMetadata> {
+    get_source_store(data_dir)
+        .and_then(|store| store.all::<SourceMetadata>().ok())
+        .unwrap_or_default()
+}
+
+/// Largest source wins, at equal size the original beats the optimized copy
+pub fn best_media_source(sources: &[MediaSource]) -> Option<&MediaSource> {
+    sources.iter().max_by_key(|source| {
+        (
+            source.width.unwrap_or(0) * source.height.unwrap_or(0),
+            !source.is_optimized.unwrap_or(false),
+        )
+    })
+}
+
+fn content_hash(path: &std::path::Path) -> std::io::Result<[u8; 32]> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Another media file with the same bytes, only files of equal size are hashed
+fn find_duplicate(media_dir: &std::path::Path, path: &std::path::Path) -> Option<String> {
+    let size = std::fs::metadata(path).ok()?.len();
+    let hash = content_hash(path).ok()?;
+
+    std::fs::read_dir(media_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|other| other != path && other.is_file())
+        .filter(|other| {
+            std::fs::metadata(other)
+                .map(|metadata| metadata.len() == size)
+                .unwrap_or(false)
+        })
+        .find(|other| content_hash(other).ok() == Some(hash))
+        .map(|other| crate::local_files::media_id_from_path(&other.to_string_lossy()))
+}
+
+fn find_by_source_url(
+    data_dir: &std::path::Path,
+    media_dir: &std::path::Path,
+    url: &str,
+) -> Option<String> {
+    let store = get_source_store(data_dir)?;
+    store
+        .all::<SourceMetadata>()
+        .ok()?
+        .into_iter()
+        .find(|(media_id, source)| {
+            (source.source_url == url || source.media_url == url)
+                && crate::local_files::resolve_media_id(media_dir, media_id).is_some()
+        })
+        .map(|(media_id, _)| media_id)
+}
+
+/// The full length from a `Content-Range: bytes */<length>` header, as sent
+/// with 416 responses
+fn complete_length(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit_once('/')?
+        .1
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Downloads into `<media_id>.part`, resuming from its length with a range
+/// request, and renames it once complete.
+async fn download_to_file(
+    app_handle: &tauri::AppHandle,
+    url: &str,
+    dest_path: &std::path::Path,
+    media_id: &str,
+) -> Result<(), String> {
+    let part_path = dest_path.with_extension(format!(
+        "{}.{PARTIAL_EXTENSION}",
+        dest_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+    ));
+    let mut resume_from = std::fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0);
+
+    let client = reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut response = loop {
+        let mut request = client.get(url);
+        if resume_from > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={resume_from}-"));
+        }
+
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if response.status() != reqwest::StatusCode::RANGE_NOT_SATISFIABLE || resume_from == 0 {
+            break response;
+        }
+
+        // the range starts at or past the end, so the part file is either
+        // complete or longer than the file and useless
+        if complete_length(&response) == Some(resume_from) {
+            let _ = app_handle.emit(
+                PROGRESS_EVENT,
+                DownloadProgress {
+                    media_id: media_id.to_string(),
+                    received: resume_from,
+                    total: Some(resume_from),
+                },
+            );
+            return std::fs::rename(&part_path, dest_path).map_err(|e| e.to_string());
+        }
+        std::fs::remove_file(&part_path).map_err(|e| e.to_string())?;
+        resume_from = 0;
+    };
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("download failed with status {status}"));
+    }
+
+    // servers without range support send the whole file again
+    let is_resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut received = if is_resumed { resume_from } else { 0 };
+    let total = response.content_length().map(|length| length + received);
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(is_resumed)
+        .truncate(!is_resumed)
+        .open(&part_path)
+        .map_err(|e| e.to_string())?;
+
+    let mut last_progress = std::time::Instant::now();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+        received += chunk.len() as u64;
+
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = std::time::Instant::now();
+            let _ = app_handle.emit(
+                PROGRESS_EVENT,
+                DownloadProgress {
+                    media_id: media_id.to_string(),
+                    received,
+                    total,
+                },
+            );
+        }
+    }
+    file.flush().map_err(|e| e.to_string())?;
+
+    if let Some(total) = total {
+        if received < total {
+            return Err(format!(
+                "download ended early at {received} of {total} bytes"
+            ));
+        }
+    }
+
+    let _ = app_handle.emit(
+        PROGRESS_EVENT,
+        DownloadProgress {
+            media_id: media_id.to_string(),
+            received,
+            total: Some(received),
+        },
+    );
+
+    std::fs::rename(&part_path, dest_path).map_err(|e| e.to_string())
+}
+
+/// Saves the best source of a Scrolller post into the media dir. Posts that
+/// were downloaded before, or whose bytes are already in the library, return
+/// the existing media id.
+pub async fn download_post(
+    app_handle: &tauri::AppHandle,
+    data_dir: &std::path::Path,
+    media_dir: &std::path::Path,
+    post: &SubredditPost,
+) -> Result<DownloadResult, String> {
+    let source = best_media_source(&post.media_sources).ok_or("post has no media".to_string())?;
+    let source_url = format!("{SCROLLLER_URL}{}", post.url);
+
+    if let Some(media_id) = find_by_source_url(data_dir, media_dir, &source_url) {
+        return Ok(DownloadResult {
+            media_id,
+            duplicate: true,
+        });
+    }
+
+    let extension = match crate::remote_sources::extension(&source.url) {
+        extension if extension.is_empty() => "bin".to_string(),
+        extension => extension,
+    };
+    let media_id = format!("scrolller-{}.{}", post.id, extension);
+    let dest_path = media_dir.join(&media_id);
+
+    if !dest_path.is_file() {
+        download_to_file(app_handle, &source.url, &dest_path, &media_id).await?;
+    }
+
+    let (media_id, duplicate) = match find_duplicate(media_dir, &dest_path) {
+        Some(existing) => {
+            std::fs::remove_file(&dest_path).map_err(|e| e.to_string())?;
+            (existing, true)
+        }
+        None => (media_id, false),
+    };
+
+    let tags = post.tags.clone().unwrap_or_default();
+    let metadata = SourceMetadata {
+        source_url,
+        media_url: source.url.clone(),
+        subreddit: post.subreddit_title.clone(),
+        title: post.title.clone(),
+        tags: tags.clone(),
+        downloaded_at: now(),
+    };
+    let store = get_source_store(data_dir).ok_or("source store not found".to_string())?;
+    store
+        .save_with_id(&metadata, &media_id)
+        .map_err(|e| e.to_string())?;
+
+    let origin = match &post.subreddit_url {
+        Some(subreddit_url) => format!("scrolller{subreddit_url}"),
+        None => "scrolller".to_string(),
+    };
+    crate::library::record_import(data_dir, &media_id, &origin, None);
+
+    if !tags.is_empty() {
+        let mut all_tags = crate::tags::tags_for_media(data_dir, &media_id);
+        all_tags.extend(tags);
+        crate::tags::set_tags_for_media(data_dir, &media_id, &all_tags)?;
+    }
+
+    Ok(DownloadResult {
+        media_id,
+        duplicate,
+    })
+}
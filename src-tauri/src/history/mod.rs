@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+
+/// Watching less than this, or less than this share of a video, counts as
+/// a skip
+const SKIP_SECONDS: f64 = 3.0;
+const SKIP_SHARE: f64 = 0.1;
+/// Watching this share of a video counts as completing it
+const COMPLETE_SHARE: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViewOutcome {
+    Completed,
+    Skipped,
+    Partial,
+}
+
+impl ViewOutcome {
+    /// Classifies a view from the seconds watched. Without a duration, as
+    /// for images, any view is complete.
+    pub fn from_watched(watched: f64, duration: Option<f64>) -> Self {
+        match duration {
+            Some(duration) if duration > 0.0 => {
+                if watched >= duration * COMPLETE_SHARE {
+                    ViewOutcome::Completed
+                } else if watched < SKIP_SECONDS || watched < duration * SKIP_SHARE {
+                    ViewOutcome::Skipped
+                } else {
+                    ViewOutcome::Partial
+                }
+            }
+            _ => ViewOutcome::Completed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewEvent {
+    pub media_id: String,
+    /// Unix seconds
+    pub viewed_at: u64,
+    /// Seconds actually played
+    pub watched: f64,
+    pub outcome: ViewOutcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryView {
+    RecentlyViewed,
+    MostViewed,
+    NeverViewed,
+}
+
+/// The running totals kept on the library record
+#[derive(Debug, Clone, Serialize)]
+pub struct ViewStats {
+    pub media_id: String,
+    pub views: u32,
+    pub last_viewed_at: Option<u64>,
+    pub watch_time: f64,
+    pub completions: u32,
+    pub skips: u32,
+}
+
+impl ViewStats {
+    fn from_record(media_id: &str, record: &crate::library::MediaRecord) -> Self {
+        ViewStats {
+            media_id: media_id.to_string(),
+            views: record.views,
+            last_viewed_at: record.last_viewed_at,
+            watch_time: record.watch_time,
+            completions: record.completions,
+            skips: record.skips,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaHistory {
+    pub stats: ViewStats,
+    /// Newest first
+    pub events: Vec<ViewEvent>,
+}
+
+fn history_dir(data_dir: &std::path::Path) -> std::path::PathBuf {
+    data_dir.join("history")
+}
+
+/// One file per event rather than a single document, so recording a view
+/// doesn't rewrite the whole history. Each item gets its own directory, so
+/// reading one item's history doesn't read everyone else's.
+fn get_history_store(data_dir: &std::path::Path, media_id: &str) -> Option<jfs::Store> {
+    // media ids are file names, anything else would leave the history dir
+    if media_id.is_empty() || crate::local_files::media_id_from_path(media_id) != media_id {
+        return None;
+    }
+
+    match jfs::Store::new_with_cfg(
+        history_dir(data_dir).join(media_id),
+        jfs::Config {
+            single: false,
+            indent: 2,
+            pretty: false,
+        },
+    ) {
+        Ok(store) => Some(store),
+        Err(_) => None,
+    }
+}
+
+/// Moves events written before they were kept per item into their item's
+/// directory. Runs at startup, returns how many were moved.
+pub fn migrate_history(data_dir: &std::path::Path) -> Result<usize, String> {
+    let Ok(entries) = std::fs::read_dir(history_dir(data_dir)) else {
+        return Ok(0);
+    };
+
+    let mut moved = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let Some(event) = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<ViewEvent>(&text).ok())
+        else {
+            continue;
+        };
+
+        let store = get_history_store(data_dir, &event.media_id)
+            .ok_or(format!("history store not found for {}", event.media_id))?;
+        store.save_with_id(&event, id).map_err(|e| e.to_string())?;
+        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+        moved += 1;
+    }
+
+    Ok(moved)
+}
+
+/// Appends a view event and folds it into the item's totals
+pub fn record_view(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    watched: f64,
+    outcome: ViewOutcome,
+) -> Result<ViewStats, String> {
+    let event = ViewEvent {
+        media_id: media_id.to_string(),
+        viewed_at: crate::library::now(),
+        watched: watched.max(0.0),
+        outcome,
+    };
+
+    let store =
+        get_history_store(data_dir, media_id).ok_or("history store not found".to_string())?;
+    store.save(&event).map_err(|e| e.to_string())?;
+
+    let record = crate::library::update_record(data_dir, media_id, |record| {
+        record.views += 1;
+        record.last_viewed_at = Some(event.viewed_at);
+        record.watch_time += event.watched;
+        match event.outcome {
+            ViewOutcome::Completed => record.completions += 1,
+            ViewOutcome::Skipped => record.skips += 1,
+            ViewOutcome::Partial => {}
+        }
+    })?;
+
+    Ok(ViewStats::from_record(media_id, &record))
+}
+
+/// The totals and every view of one item
+pub fn history_for_media(data_dir: &std::path::Path, media_id: &str) -> MediaHistory {
+    let mut events = get_history_store(data_dir, media_id)
+        .and_then(|store| store.all::<ViewEvent>().ok())
+        .unwrap_or_default()
+        .into_values()
+        .collect::<Vec<_>>();
+    events.sort_by(|a, b| b.viewed_at.cmp(&a.viewed_at));
+
+    MediaHistory {
+        stats: ViewStats::from_record(media_id, &crate::library::get_record(data_dir, media_id)),
+        events,
+    }
+}
+
+/// Items in the media dir for one of the history views
+pub fn query_history(
+    data_dir: &std::path::Path,
+    media_dir: &std::path::Path,
+    view: HistoryView,
+    limit: Option<usize>,
+) -> Vec<ViewStats> {
+    let records = crate::library::all_records(data_dir);
+    let default_record = crate::library::MediaRecord::default();
+
+    let mut stats = crate::local_files::media_ids_in_dir(media_dir)
+        .into_iter()
+        .map(|media_id| {
+            let record = records.get(&media_id).unwrap_or(&default_record);
+            ViewStats::from_record(&media_id, record)
+        })
+        .filter(|stats| match view {
+            HistoryView::NeverViewed => stats.views == 0,
+            _ => stats.views > 0,
+        })
+        .collect::<Vec<_>>();
+
+    match view {
+        HistoryView::RecentlyViewed => {
+            stats.sort_by(|a, b| b.last_viewed_at.cmp(&a.last_viewed_at));
+        }
+        HistoryView::MostViewed => stats.sort_by(|a, b| {
+            b.views
+                .cmp(&a.views)
+                .then_with(|| b.last_viewed_at.cmp(&a.last_viewed_at))
+        }),
+        HistoryView::NeverViewed => stats.sort_by(|a, b| a.media_id.cmp(&b.media_id)),
+    }
+
+    if let Some(limit) = limit {
+        stats.truncate(limit);
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_kept_per_item() {
+        let data = tempfile::tempdir().unwrap();
+        let data_dir = data.path();
+        record_view(data_dir, "a.mp4", 12.0, ViewOutcome::Partial).unwrap();
+        record_view(data_dir, "a.mp4", 40.0, ViewOutcome::Completed).unwrap();
+        record_view(data_dir, "b.jpg", 0.0, ViewOutcome::Completed).unwrap();
+
+        let history = history_for_media(data_dir, "a.mp4");
+        assert_eq!(history.events.len(), 2);
+        assert!(history.events.iter().all(|event| event.media_id == "a.mp4"));
+        assert_eq!(history.stats.views, 2);
+        assert_eq!(history.stats.completions, 1);
+        assert_eq!(
+            history_dir(data_dir)
+                .join("a.mp4")
+                .read_dir()
+                .unwrap()
+                .count(),
+            2
+        );
+
+        assert!(record_view(data_dir, "../a.mp4", 1.0, ViewOutcome::Skipped).is_err());
+        assert!(record_view(data_dir, "..", 1.0, ViewOutcome::Skipped).is_err());
+        assert!(history_for_media(data_dir, "..").events.is_empty());
+    }
+
+    #[test]
+    fn flat_events_move_into_their_item() {
+        let data = tempfile::tempdir().unwrap();
+        let data_dir = data.path();
+        std::fs::create_dir_all(history_dir(data_dir)).unwrap();
+        let event = ViewEvent {
+            media_id: "a.mp4".to_string(),
+            viewed_at: 1_700_000_000,
+            watched: 5.0,
+            outcome: ViewOutcome::Partial,
+        };
+        std::fs::write(
+            history_dir(data_dir).join("old-event.json"),
+            serde_json::to_string(&event).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(history_dir(data_dir).join("broken.json"), "{").unwrap();
+
+        assert_eq!(migrate_history(data_dir).unwrap(), 1);
+        assert!(!history_dir(data_dir).join("old-event.json").exists());
+        assert!(history_dir(data_dir).join("broken.json").exists());
+        let events = history_for_media(data_dir, "a.mp4").events;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].viewed_at, 1_700_000_000);
+
+        // nothing left to move the second time
+        assert_eq!(migrate_history(data_dir).unwrap(), 0);
+    }
+}
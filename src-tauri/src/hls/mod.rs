@@ -0,0 +1,354 @@
+use http::{header::*, response::Builder as ResponseBuilder, status::StatusCode};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// Target duration of one HLS segment in seconds
+pub const SEGMENT_SECONDS: u32 = 6;
+
+/// Packages are evicted, least recently played first, once the cache grows past this
+pub const MAX_CACHE_BYTES: u64 = 5 * 1024 * 1024 * 1024;
+
+/// How long a request waits for ffmpeg to produce a playlist or segment
+const WAIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+const MASTER_PLAYLIST: &str = "master.m3u8";
+
+/// Written once ffmpeg exits successfully. ffmpeg creates the master playlist
+/// right away, so only this marks a finished package.
+const COMPLETE_MARKER: &str = ".complete";
+
+/// Lower-bitrate renditions, only generated when the source is taller than them
+const RENDITIONS: [(&str, i64, &str); 2] = [("720p", 720, "2800k"), ("480p", 480, "1200k")];
+
+#[derive(Default)]
+pub struct HlsState {
+    pub packaging: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Handles `stream://hls/<id>/master.m3u8` and everything the playlists
+/// reference below it. The package is generated on the first request for the
+/// master playlist and served while ffmpeg is still writing it.
+pub fn get_hls_response(
+    request: http::Request<Vec<u8>>,
+    media_dir: &std::path::Path,
+    cache_dir: &std::path::Path,
+    state: &HlsState,
+) -> Result<http::Response<Vec<u8>>, Box<dyn std::error::Error>> {
+    let path = percent_encoding::percent_decode(request.uri().path().as_bytes())
+        .decode_utf8_lossy()
+        .to_string();
+    let path = path.trim_start_matches('/');
+    let path = path.strip_prefix("hls/").unwrap_or(path);
+    println!("hls path: {path}");
+
+    let not_found = || {
+        ResponseBuilder::new()
+            .status(StatusCode::NOT_FOUND)
+            .header(CONTENT_TYPE, "text/plain")
+            .body("not found".as_bytes().to_vec())
+    };
+
+    let (id, file) = match path.split_once('/') {
+        Some((id, file)) => (id, file),
+        None => (path, MASTER_PLAYLIST),
+    };
+
+    let is_safe = file
+        .split('/')
+        .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+    if !is_safe {
+        return Ok(not_found()?);
+    }
+
+    let source_path = match crate::local_files::resolve_media_id(media_dir, id) {
+        Some(source_path) => source_path,
+        None => return Ok(not_found()?),
+    };
+
+    let package_dir = cache_dir.join(id);
+    if file == MASTER_PLAYLIST {
+        ensure_package(&source_path, id, cache_dir, state)?;
+        touch(&package_dir.join(MASTER_PLAYLIST));
+    }
+
+    let file_path = package_dir.join(file);
+    if !wait_for_file(&file_path, id, state) {
+        return Ok(not_found()?);
+    }
+
+    let buf = std::fs::read(&file_path)?;
+    let content_type = match file_path.extension().and_then(|ext| ext.to_str()) {
+        Some("m3u8") => "application/vnd.apple.mpegurl",
+        Some("m4s") => "video/iso.segment",
+        _ => "video/mp4",
+    };
+
+    ResponseBuilder::new()
+        .header(CONTENT_TYPE, content_type)
+        .header(CONTENT_LENGTH, buf.len())
+        .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .body(buf)
+        .map_err(Into::into)
+}
+
+/// Starts packaging in the background unless the package already exists or
+/// is being written.
+pub fn ensure_package(
+    source_path: &std::path::Path,
+    id: &str,
+    cache_dir: &std::path::Path,
+    state: &HlsState,
+) -> Result<(), String> {
+    let package_dir = cache_dir.join(id);
+    {
+        let mut packaging = state.packaging.lock().map_err(|e| e.to_string())?;
+        if packaging.contains(id) || package_dir.join(COMPLETE_MARKER).is_file() {
+            return Ok(());
+        }
+        packaging.insert(id.to_string());
+    }
+
+    evict_packages(cache_dir, MAX_CACHE_BYTES, state);
+
+    let _ = std::fs::remove_dir_all(&package_dir);
+    std::fs::create_dir_all(&package_dir).map_err(|e| e.to_string())?;
+
+    let mut command = build_package_command(source_path, &package_dir);
+    println!("hls command: {:?}", command);
+
+    match command.spawn() {
+        Ok(mut child) => {
+            let id = id.to_string();
+            let cache_dir = cache_dir.to_path_buf();
+            let packaging = state.packaging.clone();
+            std::thread::spawn(move || {
+                let status = child.wait();
+                println!("hls packaging of {id} finished: {:?}", status);
+                let package_dir = cache_dir.join(&id);
+                let complete = matches!(status, Ok(status) if status.success())
+                    && std::fs::write(package_dir.join(COMPLETE_MARKER), b"").is_ok();
+                if !complete {
+                    let _ = std::fs::remove_dir_all(&package_dir);
+                }
+
+                if let Ok(mut packaging) = packaging.lock() {
+                    packaging.remove(&id);
+                }
+            });
+            Ok(())
+        }
+        Err(e) => {
+            if let Ok(mut packaging) = state.packaging.lock() {
+                packaging.remove(id);
+            }
+            Err(e.to_string())
+        }
+    }
+}
+
+fn build_package_command(
+    source_path: &std::path::Path,
+    package_dir: &std::path::Path,
+) -> std::process::Command {
+    let probe = ffprobe::ffprobe(source_path).ok();
+    let source_height = probe
+        .as_ref()
+        .and_then(|probe| {
+            probe
+                .streams
+                .iter()
+                .find(|stream| stream.codec_type.as_deref() == Some("video"))
+        })
+        .and_then(|stream| stream.height)
+        .unwrap_or(0);
+    let has_audio = probe
+        .as_ref()
+        .map(|probe| {
+            probe
+                .streams
+                .iter()
+                .any(|stream| stream.codec_type.as_deref() == Some("audio"))
+        })
+        .unwrap_or(false);
+
+    let renditions = RENDITIONS
+        .iter()
+        .filter(|(_, height, _)| *height < source_height)
+        .collect::<Vec<_>>();
+
+    let mut filter = format!("[0:v]split={}[v0]", renditions.len() + 1);
+    for index in 1..=renditions.len() {
+        filter.push_str(&format!("[s{index}]"));
+    }
+    for (index, (_, height, _)) in renditions.iter().enumerate() {
+        filter.push_str(&format!(";[s{}]scale=-2:{height}[v{}]", index + 1, index + 1));
+    }
+
+    let mut command = std::process::Command::new("ffmpeg");
+    command
+        .arg("-y")
+        .arg("-i")
+        .arg(source_path)
+        .arg("-filter_complex")
+        .arg(&filter);
+
+    let mut stream_map = vec![];
+    for index in 0..=renditions.len() {
+        command.arg("-map").arg(format!("[v{index}]"));
+        if has_audio {
+            command.arg("-map").arg("0:a:0");
+        }
+
+        let name = if index == 0 {
+            "source"
+        } else {
+            renditions[index - 1].0
+        };
+        stream_map.push(if has_audio {
+            format!("v:{index},a:{index},name:{name}")
+        } else {
+            format!("v:{index},name:{name}")
+        });
+    }
+
+    command
+        .arg("-c:v")
+        .arg("libx264")
+        .arg("-preset")
+        .arg("veryfast")
+        .arg("-crf:v:0")
+        .arg("21")
+        .arg("-pix_fmt")
+        .arg("yuv420p")
+        .arg("-sc_threshold")
+        .arg("0")
+        .arg("-force_key_frames")
+        .arg(format!("expr:gte(t,n_forced*{SEGMENT_SECONDS})"));
+
+    for (index, (_, _, bitrate)) in renditions.iter().enumerate() {
+        command
+            .arg(format!("-b:v:{}", index + 1))
+            .arg(bitrate)
+            .arg(format!("-maxrate:v:{}", index + 1))
+            .arg(bitrate)
+            .arg(format!("-bufsize:v:{}", index + 1))
+            .arg(bitrate);
+    }
+
+    if has_audio {
+        command.arg("-c:a").arg("aac").arg("-b:a").arg("128k");
+    }
+
+    command
+        .arg("-f")
+        .arg("hls")
+        .arg("-hls_time")
+        .arg(SEGMENT_SECONDS.to_string())
+        .arg("-hls_playlist_type")
+        .arg("event")
+        .arg("-hls_segment_type")
+        .arg("fmp4")
+        .arg("-hls_fmp4_init_filename")
+        .arg("init.mp4")
+        .arg("-hls_segment_filename")
+        .arg(package_dir.join("%v").join("seg_%05d.m4s"))
+        .arg("-master_pl_name")
+        .arg(MASTER_PLAYLIST)
+        .arg("-var_stream_map")
+        .arg(stream_map.join(" "))
+        .arg(package_dir.join("%v").join("index.m3u8"))
+        .stdin(std::process::Stdio::null());
+
+    command
+}
+
+fn wait_for_file(file_path: &std::path::Path, id: &str, state: &HlsState) -> bool {
+    let start = std::time::Instant::now();
+    loop {
+        if file_path.is_file() {
+            return true;
+        }
+
+        let is_packaging = state
+            .packaging
+            .lock()
+            .map(|packaging| packaging.contains(id))
+            .unwrap_or(false);
+        if !is_packaging || start.elapsed() > WAIT_TIMEOUT {
+            return false;
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+}
+
+fn touch(path: &std::path::Path) {
+    if let Ok(file) = std::fs::File::options().append(true).open(path) {
+        let _ = file.set_modified(std::time::SystemTime::now());
+    }
+}
+
+fn dir_size(dir: &std::path::Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| {
+                    let path = entry.path();
+                    if path.is_dir() {
+                        dir_size(&path)
+                    } else {
+                        entry.metadata().map(|meta| meta.len()).unwrap_or(0)
+                    }
+                })
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Removes the least recently played packages until the cache fits in `max_bytes`.
+pub fn evict_packages(cache_dir: &std::path::Path, max_bytes: u64, state: &HlsState) {
+    let packaging = state
+        .packaging
+        .lock()
+        .map(|packaging| packaging.clone())
+        .unwrap_or_default();
+
+    let mut packages = match std::fs::read_dir(cache_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| {
+                let path = entry.path();
+                let last_played = std::fs::metadata(path.join(MASTER_PLAYLIST))
+                    .and_then(|meta| meta.modified())
+                    .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+                let size = dir_size(&path);
+                (path, last_played, size)
+            })
+            .collect::<Vec<_>>(),
+        Err(_) => return,
+    };
+
+    let mut total = packages.iter().map(|(_, _, size)| size).sum::<u64>();
+    packages.sort_by_key(|(_, last_played, _)| *last_played);
+
+    for (path, _, size) in packages {
+        if total <= max_bytes {
+            break;
+        }
+
+        let id = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if packaging.contains(&id) {
+            continue;
+        }
+
+        println!("evicting hls package {:?}", path);
+        if std::fs::remove_dir_all(&path).is_ok() {
+            total -= size;
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use actix_web::{get, post, put, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+
+use super::auth::ApiError;
+use super::ServerData;
+use crate::jobs::{Job, JobRequest, JobStatus};
+use crate::types::{Dimensions, LocalFile};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// JSON API for scripting, versioned under `/api/v1`
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "scrollocal library API", version = "1"),
+    paths(list_media, get_media, set_media_tags, enqueue_job, list_jobs, get_job),
+    components(schemas(
+        MediaItem,
+        MediaPage,
+        SetTagsBody,
+        Dimensions,
+        Job,
+        JobRequest,
+        JobStatus
+    )),
+    modifiers(&TokenSecurity),
+    security(("token" = []))
+)]
+pub struct ApiDoc;
+
+struct TokenSecurity;
+
+impl Modify for TokenSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MediaItem {
+    pub id: String,
+    /// `image`, `video`, `audio` or `unknown`
+    pub kind: String,
+    pub extension: String,
+    pub dimensions: Option<Dimensions>,
+    pub tags: Vec<String>,
+    pub favorite: bool,
+    /// One to five stars
+    pub rating: Option<u8>,
+    pub note: Option<String>,
+}
+
+impl MediaItem {
+    fn from_local_file(
+        file: LocalFile,
+        record: &crate::library::MediaRecord,
+        tags: Vec<String>,
+    ) -> Self {
+        MediaItem {
+            tags,
+            favorite: record.favorite,
+            rating: record.rating,
+            note: record.note.clone(),
+            kind: file.kind.as_str().to_string(),
+            id: file.id,
+            extension: file.extension,
+            dimensions: file.dimensions,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MediaPage {
+    pub items: Vec<MediaItem>,
+    pub total: usize,
+    /// Offset of the next page, missing on the last one
+    pub next_offset: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListMediaParams {
+    /// Case-insensitive substring of the media ID
+    pub search: Option<String>,
+    /// `image`, `video` or `audio`
+    pub kind: Option<String>,
+    /// Only media carrying this tag
+    pub tag: Option<String>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetTagsBody {
+    pub tags: Vec<String>,
+}
+
+pub fn configure(config: &mut web::ServiceConfig) {
+    config.service(
+        web::scope("/api/v1")
+            .service(openapi_handle)
+            .service(list_media)
+            .service(get_media)
+            .service(set_media_tags)
+            .service(enqueue_job)
+            .service(list_jobs)
+            .service(get_job),
+    );
+}
+
+#[get("/openapi.json")]
+pub async fn openapi_handle() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/media",
+    params(ListMediaParams),
+    responses(
+        (status = 200, body = MediaPage),
+        (status = 401, description = "missing or invalid token")
+    )
+)]
+#[get("/media")]
+pub async fn list_media(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+    params: web::Query<ListMediaParams>,
+) -> Result<HttpResponse, ApiError> {
+    data.guard(&req)?;
+
+    let params = params.into_inner();
+    let offset = params.offset.unwrap_or(0);
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let media_dir = data.media_dir.clone();
+    let data_dir = data.data_dir.clone();
+    let page = web::block(move || {
+        let search = params.search.map(|search| search.to_lowercase());
+        let definitions = crate::tags::tag_definitions(&data_dir);
+        // loaded once for the whole listing rather than once per file
+        let records = crate::library::all_records(&data_dir);
+        let mut assignments = crate::tags::all_assignments(&data_dir);
+        // aliases resolve to their tag, children match their parents
+        let tag = params
+            .tag
+            .map(|tag| crate::tags::resolve_in(&definitions, &tag));
+        let mut files = crate::local_files::load_local_files_from_base_dir(Some(media_dir))
+            .into_iter()
+            .filter(|file| match &search {
+                Some(search) => file.id.to_lowercase().contains(search),
+                None => true,
+            })
+            .filter(|file| match &params.kind {
+                Some(kind) => file.kind.as_str() == kind,
+                None => true,
+            })
+            .filter(|file| match &tag {
+                Some(tag) => assignments.get(&file.id).is_some_and(|tags| {
+                    crate::tags::implied_tags_in(&definitions, tags).contains(tag)
+                }),
+                None => true,
+            })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let total = files.len();
+        let default_record = crate::library::MediaRecord::default();
+        let items = files
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|file| {
+                let record = records.get(&file.id).unwrap_or(&default_record);
+                let tags = assignments.remove(&file.id).unwrap_or_default();
+                MediaItem::from_local_file(file, record, tags)
+            })
+            .collect::<Vec<_>>();
+        let next_offset = if offset + items.len() < total {
+            Some(offset + items.len())
+        } else {
+            None
+        };
+
+        MediaPage {
+            items,
+            total,
+            next_offset,
+        }
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(page))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/media/{id}",
+    params(("id" = String, Path, description = "media ID")),
+    responses(
+        (status = 200, body = MediaItem),
+        (status = 404, description = "no such media")
+    )
+)]
+#[get("/media/{id}")]
+pub async fn get_media(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    data.guard(&req)?;
+
+    let item = load_media_item(&data, &id).await?;
+
+    Ok(HttpResponse::Ok().json(item))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/media/{id}/tags",
+    params(("id" = String, Path, description = "media ID")),
+    request_body = SetTagsBody,
+    responses(
+        (status = 200, body = MediaItem),
+        (status = 404, description = "no such media")
+    )
+)]
+#[put("/media/{id}/tags")]
+pub async fn set_media_tags(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+    id: web::Path<String>,
+    body: web::Json<SetTagsBody>,
+) -> Result<HttpResponse, ApiError> {
+    data.guard(&req)?;
+    data.resolve_media(&id)?;
+
+    crate::tags::set_tags_for_media(&data.data_dir, &id, &body.tags).map_err(ApiError::Internal)?;
+
+    let item = load_media_item(&data, &id).await?;
+
+    Ok(HttpResponse::Ok().json(item))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/jobs",
+    request_body = JobRequest,
+    responses(
+        (status = 200, body = Job),
+        (status = 404, description = "no such media")
+    )
+)]
+#[post("/jobs")]
+pub async fn enqueue_job(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+    body: web::Json<JobRequest>,
+) -> Result<HttpResponse, ApiError> {
+    data.guard(&req)?;
+
+    let request = body.into_inner();
+    match &request {
+        JobRequest::Snip { media_id, .. } | JobRequest::Fix { media_id } => {
+            data.resolve_media(media_id)?;
+        }
+    }
+
+    let job = data.jobs.enqueue(request).map_err(ApiError::Internal)?;
+
+    Ok(HttpResponse::Ok().json(job))
+}
+
+#[utoipa::path(get, path = "/api/v1/jobs", responses((status = 200, body = Vec<Job>)))]
+#[get("/jobs")]
+pub async fn list_jobs(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+) -> Result<HttpResponse, ApiError> {
+    data.guard(&req)?;
+
+    Ok(HttpResponse::Ok().json(data.jobs.list()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs/{id}",
+    params(("id" = u64, Path, description = "job ID")),
+    responses(
+        (status = 200, body = Job),
+        (status = 404, description = "no such job")
+    )
+)]
+#[get("/jobs/{id}")]
+pub async fn get_job(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+    id: web::Path<u64>,
+) -> Result<HttpResponse, ApiError> {
+    data.guard(&req)?;
+
+    let job = data
+        .jobs
+        .get(*id)
+        .ok_or(ApiError::NotFound(format!("job {id}")))?;
+
+    Ok(HttpResponse::Ok().json(job))
+}
+
+async fn load_media_item(data: &ServerData, id: &str) -> Result<MediaItem, ApiError> {
+    let path = data.resolve_media(id)?;
+    let media_dir = data.media_dir.clone();
+    let data_dir = data.data_dir.clone();
+    let id = id.to_string();
+
+    web::block(move || {
+        crate::local_files::load_local_file(&path.to_string_lossy(), &media_dir)
+            .map(|file| {
+                let record = crate::library::get_record(&data_dir, &file.id);
+                let tags = crate::tags::tags_for_media(&data_dir, &file.id);
+                MediaItem::from_local_file(file, &record, tags)
+            })
+            .ok_or(ApiError::NotFound(format!("media {id}")))
+    })
+    .await?
+}
@@ -0,0 +1,246 @@
+use actix_web::{http::StatusCode, HttpRequest, HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use crate::library::now;
+
+/// Requests allowed per client address within one `RATE_LIMIT_WINDOW`
+const RATE_LIMIT_REQUESTS: u32 = 600;
+const RATE_LIMIT_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+
+const PAIRING_DEVICE_NAME: &str = "pairing";
+
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized,
+    NotFound(String),
+    TooManyRequests,
+    Internal(String),
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::BadRequest(message) => write!(f, "bad request: {message}"),
+            ApiError::Unauthorized => write!(f, "missing or invalid access token"),
+            ApiError::NotFound(message) => write!(f, "{message} not found"),
+            ApiError::TooManyRequests => write!(f, "too many requests"),
+            ApiError::Internal(message) => write!(f, "internal error: {message}"),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let ApiError::TooManyRequests = self {
+            response.insert_header(("Retry-After", RATE_LIMIT_WINDOW.as_secs().to_string()));
+        }
+        response.json(serde_json::json!({ "error": self.to_string() }))
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => ApiError::NotFound("file".to_string()),
+            _ => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<actix_web::error::BlockingError> for ApiError {
+    fn from(e: actix_web::error::BlockingError) -> Self {
+        ApiError::Internal(e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessToken {
+    pub token: String,
+    pub device_name: String,
+    pub created_at: u64,
+}
+
+pub fn get_token_store(data_dir: &std::path::Path) -> Option<jfs::Store> {
+    match jfs::Store::new_with_cfg(
+        data_dir.join("access_tokens"),
+        jfs::Config {
+            single: true,
+            indent: 2,
+            pretty: true,
+        },
+    ) {
+        Ok(store) => Some(store),
+        Err(_) => None,
+    }
+}
+
+fn random_token() -> String {
+    let mut x = [0_u8; 24];
+    getrandom::getrandom(&mut x).expect("failed to get random bytes");
+    x.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+/// Device tokens, without the pairing token
+pub fn list_access_tokens(data_dir: &std::path::Path) -> Result<Vec<AccessToken>, String> {
+    let store = get_token_store(data_dir).ok_or("token store not found".to_string())?;
+    let tokens = store.all::<AccessToken>().map_err(|e| e.to_string())?;
+
+    Ok(tokens
+        .into_values()
+        .filter(|token| token.device_name != PAIRING_DEVICE_NAME)
+        .collect())
+}
+
+pub fn create_access_token(
+    data_dir: &std::path::Path,
+    device_name: &str,
+) -> Result<AccessToken, String> {
+    if device_name.is_empty() || device_name == PAIRING_DEVICE_NAME {
+        return Err("invalid device name".to_string());
+    }
+
+    let store = get_token_store(data_dir).ok_or("token store not found".to_string())?;
+    let token = AccessToken {
+        token: random_token(),
+        device_name: device_name.to_string(),
+        created_at: now(),
+    };
+    store
+        .save_with_id(&token, &token.token)
+        .map_err(|e| e.to_string())?;
+
+    Ok(token)
+}
+
+pub fn revoke_access_token(data_dir: &std::path::Path, token: &str) -> Result<(), String> {
+    let store = get_token_store(data_dir).ok_or("token store not found".to_string())?;
+    store.delete(token).map_err(|e| e.to_string())
+}
+
+/// The pairing token is only good for exchanging against a device token, so it
+/// can be shown on screen without handing out library access.
+pub fn get_pairing_token(data_dir: &std::path::Path) -> Result<String, String> {
+    let store = get_token_store(data_dir).ok_or("token store not found".to_string())?;
+    let tokens = store.all::<AccessToken>().map_err(|e| e.to_string())?;
+
+    if let Some(token) = tokens
+        .into_values()
+        .find(|token| token.device_name == PAIRING_DEVICE_NAME)
+    {
+        return Ok(token.token);
+    }
+
+    let token = AccessToken {
+        token: random_token(),
+        device_name: PAIRING_DEVICE_NAME.to_string(),
+        created_at: now(),
+    };
+    store
+        .save_with_id(&token, &token.token)
+        .map_err(|e| e.to_string())?;
+
+    Ok(token.token)
+}
+
+pub fn reset_pairing_token(data_dir: &std::path::Path) -> Result<String, String> {
+    let store = get_token_store(data_dir).ok_or("token store not found".to_string())?;
+    let tokens = store.all::<AccessToken>().map_err(|e| e.to_string())?;
+
+    for token in tokens
+        .into_values()
+        .filter(|token| token.device_name == PAIRING_DEVICE_NAME)
+    {
+        store.delete(&token.token).map_err(|e| e.to_string())?;
+    }
+
+    get_pairing_token(data_dir)
+}
+
+fn find_token(data_dir: &std::path::Path, token: &str) -> Option<AccessToken> {
+    get_token_store(data_dir).and_then(|store| store.get::<AccessToken>(token).ok())
+}
+
+/// Tokens come from an `Authorization: Bearer` header or, for `<video>` and
+/// `<img>` sources that can't set headers, a `token` query parameter.
+pub fn request_token(req: &HttpRequest) -> Option<String> {
+    let header = req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    header.or_else(|| {
+        req.query_string().split('&').find_map(|pair| {
+            pair.strip_prefix("token=").map(|token| {
+                percent_encoding::percent_decode_str(token)
+                    .decode_utf8_lossy()
+                    .to_string()
+            })
+        })
+    })
+}
+
+pub fn authorize(req: &HttpRequest, data_dir: &std::path::Path) -> Result<AccessToken, ApiError> {
+    let token = request_token(req).ok_or(ApiError::Unauthorized)?;
+
+    match find_token(data_dir, &token) {
+        Some(token) if token.device_name != PAIRING_DEVICE_NAME => Ok(token),
+        _ => Err(ApiError::Unauthorized),
+    }
+}
+
+pub fn authorize_pairing(req: &HttpRequest, data_dir: &std::path::Path) -> Result<(), ApiError> {
+    let token = request_token(req).ok_or(ApiError::Unauthorized)?;
+
+    match find_token(data_dir, &token) {
+        Some(token) if token.device_name == PAIRING_DEVICE_NAME => Ok(()),
+        _ => Err(ApiError::Unauthorized),
+    }
+}
+
+/// Fixed-window request counter per client address
+#[derive(Default)]
+pub struct RateLimiter {
+    windows: Mutex<HashMap<IpAddr, (std::time::Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn check(&self, req: &HttpRequest) -> Result<(), ApiError> {
+        let ip = match req.peer_addr() {
+            Some(address) => address.ip(),
+            None => return Ok(()),
+        };
+
+        let mut windows = self
+            .windows
+            .lock()
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        let now = std::time::Instant::now();
+
+        windows.retain(|_, (started, _)| now.duration_since(*started) < RATE_LIMIT_WINDOW);
+        let (_, count) = windows.entry(ip).or_insert((now, 0));
+        *count += 1;
+
+        if *count > RATE_LIMIT_REQUESTS {
+            Err(ApiError::TooManyRequests)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use super::auth::{self, ApiError};
+use super::ServerData;
+use crate::types::{Dimensions, LocalFile};
+
+const DEFAULT_PAGE_SIZE: usize = 30;
+const MAX_PAGE_SIZE: usize = 200;
+
+/// Query accepted by `/feed`, the same shape `RemoteFeedSingle` fetches
+#[derive(Deserialize)]
+pub struct FeedParams {
+    pub iterator: Option<String>,
+    /// Comma-separated tags, an item needs all of them
+    pub tags: Option<String>,
+    /// `video` or `image`
+    pub kind: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct FeedSource {
+    pub url: String,
+    pub dimensions: Option<Dimensions>,
+}
+
+#[derive(Serialize)]
+pub struct FeedItemAdditional {
+    #[serde(rename = "sourceUrl")]
+    pub source_url: String,
+}
+
+/// Mirrors the frontend `FinalFile<{ sourceUrl }>` type
+#[derive(Serialize)]
+pub struct FeedItem {
+    pub name: String,
+    pub src: Vec<FeedSource>,
+    pub kind: &'static str,
+    #[serde(rename = "type")]
+    pub item_type: &'static str,
+    pub extension: String,
+    pub additional: FeedItemAdditional,
+}
+
+#[derive(Serialize)]
+pub struct FeedPage {
+    /// Pass back to get the next page, `null` once the feed is exhausted
+    pub iterator: Option<String>,
+    pub items: Vec<FeedItem>,
+}
+
+/// The cursor carries the shuffle seed, so every page of one session walks
+/// the same random order.
+struct FeedCursor {
+    seed: u64,
+    offset: usize,
+}
+
+impl FeedCursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}:{}", self.seed, self.offset))
+    }
+
+    fn decode(iterator: &str) -> Option<Self> {
+        let decoded = URL_SAFE_NO_PAD.decode(iterator).ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (seed, offset) = decoded.split_once(':')?;
+
+        Some(FeedCursor {
+            seed: seed.parse().ok()?,
+            offset: offset.parse().ok()?,
+        })
+    }
+}
+
+fn feed_kind(file: &LocalFile) -> Option<&'static str> {
+    match file.kind.as_str() {
+        "image" => Some("image"),
+        // the feed plays audio through a video element as well
+        "video" | "audio" => Some("video"),
+        _ => None,
+    }
+}
+
+fn feed_extension(extension: &str) -> String {
+    match extension.to_lowercase().as_str() {
+        "ebml" => "webm".to_string(),
+        "m4v" => "mp4".to_string(),
+        _ => extension.to_string(),
+    }
+}
+
+#[get("/feed")]
+pub async fn feed_handle(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+    params: web::Query<FeedParams>,
+) -> Result<HttpResponse, ApiError> {
+    data.guard(&req)?;
+
+    let params = params.into_inner();
+    let cursor = match params.iterator.as_deref() {
+        Some(iterator) if !iterator.is_empty() => FeedCursor::decode(iterator)
+            .ok_or(ApiError::BadRequest("invalid iterator".to_string()))?,
+        _ => FeedCursor {
+            seed: rand::random(),
+            offset: 0,
+        },
+    };
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let tags = params
+        .tags
+        .map(|tags| {
+            tags.split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    // item URLs are relative, `RemoteFeedSingle` resolves them against the feed URL
+    let token_query = auth::request_token(&req)
+        .map(|token| {
+            format!(
+                "?token={}",
+                percent_encoding::utf8_percent_encode(&token, percent_encoding::NON_ALPHANUMERIC)
+            )
+        })
+        .unwrap_or_default();
+
+    let media_dir = data.media_dir.clone();
+    let data_dir = data.data_dir.clone();
+    let page = web::block(move || {
+        let definitions = crate::tags::tag_definitions(&data_dir);
+        let assignments = crate::tags::all_assignments(&data_dir);
+        // aliases resolve to their tag, children match their parents
+        let tags = tags
+            .iter()
+            .map(|tag| crate::tags::resolve_in(&definitions, tag))
+            .collect::<Vec<_>>();
+        let mut files = crate::local_files::load_local_files_from_base_dir(Some(media_dir))
+            .into_iter()
+            .filter(|file| match (&params.kind, feed_kind(file)) {
+                (_, None) => false,
+                (Some(kind), Some(file_kind)) => kind == file_kind,
+                (None, Some(_)) => true,
+            })
+            .filter(|file| {
+                if tags.is_empty() {
+                    return true;
+                }
+                let file_tags = crate::tags::implied_tags_in(
+                    &definitions,
+                    assignments
+                        .get(&file.id)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default(),
+                );
+                tags.iter().all(|tag| file_tags.contains(tag))
+            })
+            .collect::<Vec<_>>();
+
+        files.sort_by(|a, b| a.id.cmp(&b.id));
+        files.shuffle(&mut rand::rngs::StdRng::seed_from_u64(cursor.seed));
+
+        let total = files.len();
+        let items = files
+            .into_iter()
+            .skip(cursor.offset)
+            .take(limit)
+            .map(|file| {
+                let url = format!(
+                    "media/{}{token_query}",
+                    percent_encoding::utf8_percent_encode(
+                        &file.id,
+                        percent_encoding::NON_ALPHANUMERIC
+                    )
+                );
+                FeedItem {
+                    kind: feed_kind(&file).unwrap_or("video"),
+                    item_type: "remote",
+                    extension: feed_extension(&file.extension),
+                    additional: FeedItemAdditional {
+                        source_url: url.clone(),
+                    },
+                    src: vec![FeedSource {
+                        url,
+                        dimensions: file.dimensions,
+                    }],
+                    name: file.id,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let next_offset = cursor.offset + items.len();
+        FeedPage {
+            iterator: if next_offset < total {
+                Some(
+                    FeedCursor {
+                        seed: cursor.seed,
+                        offset: next_offset,
+                    }
+                    .encode(),
+                )
+            } else {
+                None
+            },
+            items,
+        }
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(page))
+}
@@ -1,7 +1,79 @@
 use actix_files::NamedFile;
-use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
-use serde::Deserialize;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
 use std::net::TcpListener;
+use std::sync::Mutex;
+
+pub mod api;
+pub mod auth;
+pub mod feed;
+
+use auth::ApiError;
+
+/// Everything the request handlers need, shared across actix workers
+pub struct ServerData {
+    pub data_dir: std::path::PathBuf,
+    pub media_dir: std::path::PathBuf,
+    pub thumbnail_dir: std::path::PathBuf,
+    pub rate_limiter: auth::RateLimiter,
+    pub jobs: crate::jobs::JobQueue,
+}
+
+impl ServerData {
+    /// Rate limits and authenticates a request, every handler starts with this
+    pub fn guard(&self, req: &HttpRequest) -> Result<auth::AccessToken, ApiError> {
+        self.rate_limiter.check(req)?;
+        auth::authorize(req, &self.data_dir)
+    }
+
+    pub fn resolve_media(&self, id: &str) -> Result<std::path::PathBuf, ApiError> {
+        crate::local_files::resolve_media_id(&self.media_dir, id)
+            .ok_or(ApiError::NotFound(format!("media {id}")))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LanServerConfig {
+    /// Address of the interface to bind to, `0.0.0.0` for every interface
+    pub interface: Option<String>,
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LanServerStatus {
+    pub running: bool,
+    pub interface: Option<String>,
+    pub port: Option<u16>,
+}
+
+struct RunningServer {
+    handle: actix_web::dev::ServerHandle,
+    address: std::net::SocketAddr,
+}
+
+#[derive(Default)]
+pub struct LanServerState {
+    server: Mutex<Option<RunningServer>>,
+}
+
+impl LanServerState {
+    pub fn status(&self) -> LanServerStatus {
+        let server = self.server.lock().unwrap();
+        LanServerStatus {
+            running: server.is_some(),
+            interface: server.as_ref().map(|server| server.address.ip().to_string()),
+            port: server.as_ref().map(|server| server.address.port()),
+        }
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.server
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|server| server.address.port())
+    }
+}
 
 #[get("/test")]
 pub async fn test_handle() -> impl Responder {
@@ -10,25 +82,199 @@ pub async fn test_handle() -> impl Responder {
 
 #[derive(Debug, Deserialize)]
 pub struct FileHandleParams {
-    pub path: Option<String>,
+    pub id: Option<String>,
 }
 #[get("/file")]
-pub async fn file_handle(req: HttpRequest) -> actix_web::Result<NamedFile> {
-    let params = web::Query::<FileHandleParams>::from_query(req.query_string()).unwrap();
-    println!("{:?}", params);
+pub async fn file_handle(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+) -> Result<NamedFile, ApiError> {
+    data.guard(&req)?;
 
-    let path = params.path.as_ref().unwrap();
-    println!("{:?}", path);
+    let params = web::Query::<FileHandleParams>::from_query(req.query_string())
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let id = params
+        .id
+        .as_ref()
+        .ok_or(ApiError::BadRequest("missing id".to_string()))?;
 
-    Ok(NamedFile::open(path)?)
+    Ok(NamedFile::open(data.resolve_media(id)?)?)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PairParams {
+    pub device: Option<String>,
+}
+/// Trades the pairing token for a device token
+#[post("/pair")]
+pub async fn pair_handle(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+) -> Result<HttpResponse, ApiError> {
+    data.rate_limiter.check(&req)?;
+    auth::authorize_pairing(&req, &data.data_dir)?;
+
+    let params = web::Query::<PairParams>::from_query(req.query_string())
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let device = params
+        .device
+        .as_ref()
+        .ok_or(ApiError::BadRequest("missing device".to_string()))?;
+
+    let token =
+        auth::create_access_token(&data.data_dir, device).map_err(ApiError::BadRequest)?;
+
+    Ok(HttpResponse::Ok().json(token))
+}
+
+#[get("/library")]
+pub async fn library_handle(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+) -> Result<HttpResponse, ApiError> {
+    data.guard(&req)?;
+
+    let media_dir = data.media_dir.clone();
+    let files = web::block(move || {
+        crate::local_files::load_local_files_from_base_dir(Some(media_dir))
+            .into_iter()
+            // the name is the path on this machine, clients only need the id
+            .map(|file| crate::types::LocalFile {
+                name: file.id.clone(),
+                ..file
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(files))
+}
+
+#[get("/media/{id}")]
+pub async fn media_handle(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+    id: web::Path<String>,
+) -> Result<NamedFile, ApiError> {
+    data.guard(&req)?;
+
+    // NamedFile answers range requests on its own
+    Ok(NamedFile::open(data.resolve_media(&id)?)?)
+}
+
+#[get("/thumbnail/{id}")]
+pub async fn thumbnail_handle(
+    req: HttpRequest,
+    data: web::Data<ServerData>,
+    id: web::Path<String>,
+) -> Result<NamedFile, ApiError> {
+    data.guard(&req)?;
+
+    let path = data.resolve_media(&id)?;
+    let thumbnail_dir = data.thumbnail_dir.clone();
+
+    let thumbnail_path =
+        web::block(move || crate::local_files::get_thumbnail(&path, &thumbnail_dir))
+            .await?
+            .map_err(ApiError::Internal)?;
+
+    Ok(NamedFile::open(thumbnail_path)?)
+}
+
+/// Binds the LAN server and runs it on its own actix system thread.
+pub fn start(
+    state: &LanServerState,
+    config: LanServerConfig,
+    data: ServerData,
+) -> Result<LanServerStatus, String> {
+    let mut server = state.server.lock().map_err(|e| e.to_string())?;
+    if server.is_some() {
+        return Err("server already running".to_string());
+    }
+
+    let interface = config.interface.unwrap_or("0.0.0.0".to_string());
+    let port = match config.port {
+        Some(port) => port,
+        None => get_available_port_on(&interface).ok_or("no available port".to_string())?,
+    };
+
+    let data = web::Data::new(data);
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    // the server has to be built inside the actix system that drives it
+    std::thread::spawn(move || {
+        actix_web::rt::System::new().block_on(async move {
+            let http_server = actix_web::HttpServer::new(move || {
+                actix_web::App::new()
+                    .app_data(data.clone())
+                    .wrap(actix_cors::Cors::permissive())
+                    .service(test_handle)
+                    .service(file_handle)
+                    .service(pair_handle)
+                    .service(library_handle)
+                    .service(media_handle)
+                    .service(thumbnail_handle)
+                    .service(feed::feed_handle)
+                    .configure(api::configure)
+            })
+            .workers(2)
+            .bind((interface.as_str(), port));
+
+            let http_server = match http_server {
+                Ok(http_server) => http_server,
+                Err(e) => {
+                    let _ = sender.send(Err(e.to_string()));
+                    return;
+                }
+            };
+
+            let address = http_server.addrs().first().copied();
+            let running = http_server.run();
+            let _ = sender.send(
+                address
+                    .map(|address| (running.handle(), address))
+                    .ok_or("server has no address".to_string()),
+            );
+
+            let res = running.await;
+            println!("lan server stopped: {:?}", res);
+        });
+    });
+
+    let (handle, address) = receiver.recv().map_err(|e| e.to_string())??;
+
+    println!("lan server listening on {address}");
+    *server = Some(RunningServer { handle, address });
+
+    Ok(LanServerStatus {
+        running: true,
+        interface: Some(address.ip().to_string()),
+        port: Some(address.port()),
+    })
+}
+
+pub async fn stop(state: &LanServerState) -> Result<LanServerStatus, String> {
+    let server = state.server.lock().map_err(|e| e.to_string())?.take();
+
+    match server {
+        Some(server) => {
+            server.handle.stop(true).await;
+            Ok(state.status())
+        }
+        None => Err("server not running".to_string()),
+    }
 }
 
 pub fn get_available_port() -> Option<u16> {
-    (8000..9000).find(|port| port_is_available(*port))
+    get_available_port_on("127.0.0.1")
+}
+
+pub fn get_available_port_on(interface: &str) -> Option<u16> {
+    (8000..9000).find(|port| port_is_available_on(interface, *port))
 }
 
-pub fn port_is_available(port: u16) -> bool {
-    match TcpListener::bind(("127.0.0.1", port)) {
+pub fn port_is_available_on(interface: &str, port: u16) -> bool {
+    match TcpListener::bind((interface, port)) {
         Ok(_) => true,
         Err(_) => false,
     }
@@ -0,0 +1,411 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+
+/// Serializes read-modify-write cycles on the rule store
+static RULE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchTarget {
+    #[default]
+    FileName,
+    /// The path below the import root, with `/` between folders
+    Path,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleKind {
+    /// Every folder between the import root and the file becomes a tag
+    Folders {
+        /// Folder names that don't make a tag, compared ignoring case
+        #[serde(default)]
+        ignore: Vec<String>,
+    },
+    /// A regex over the file name or path. Tags are templates that can use
+    /// capture groups as `$1` or `${name}`.
+    Pattern {
+        pattern: String,
+        #[serde(default)]
+        target: MatchTarget,
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRule {
+    pub name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub kind: RuleKind,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportRuleInfo {
+    pub id: String,
+    #[serde(flatten)]
+    pub rule: ImportRule,
+}
+
+/// Tags a rerun would add to one item
+#[derive(Debug, Clone, Serialize)]
+pub struct RulePreview {
+    pub media_id: String,
+    /// The path the rules matched against
+    pub import_path: String,
+    /// Only tags the item doesn't have yet
+    pub tags: Vec<String>,
+}
+
+fn get_rule_store(data_dir: &std::path::Path) -> Option<jfs::Store> {
+    match jfs::Store::new_with_cfg(
+        data_dir.join("import_rules"),
+        jfs::Config {
+            single: true,
+            indent: 2,
+            pretty: true,
+        },
+    ) {
+        Ok(store) => Some(store),
+        Err(_) => None,
+    }
+}
+
+fn validate(rule: &ImportRule) -> Result<(), String> {
+    if rule.name.trim().is_empty() {
+        return Err("rule name is empty".to_string());
+    }
+    if let RuleKind::Pattern { pattern, tags, .. } = &rule.kind {
+        Regex::new(pattern).map_err(|e| e.to_string())?;
+        if tags.iter().all(|tag| tag.trim().is_empty()) {
+            return Err("pattern rule has no tags".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+pub fn list_rules(data_dir: &std::path::Path) -> Vec<ImportRuleInfo> {
+    let mut rules = get_rule_store(data_dir)
+        .and_then(|store| store.all::<ImportRule>().ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(id, rule)| ImportRuleInfo { id, rule })
+        .collect::<Vec<_>>();
+    rules.sort_by(|a, b| a.rule.name.to_lowercase().cmp(&b.rule.name.to_lowercase()));
+
+    rules
+}
+
+pub fn add_rule(data_dir: &std::path::Path, rule: ImportRule) -> Result<ImportRuleInfo, String> {
+    validate(&rule)?;
+
+    let _lock = RULE_LOCK.lock().map_err(|e| e.to_string())?;
+    let store = get_rule_store(data_dir).ok_or("rule store not found".to_string())?;
+    let id = store.save(&rule).map_err(|e| e.to_string())?;
+
+    Ok(ImportRuleInfo { id, rule })
+}
+
+pub fn update_rule(
+    data_dir: &std::path::Path,
+    id: &str,
+    rule: ImportRule,
+) -> Result<ImportRuleInfo, String> {
+    validate(&rule)?;
+
+    let _lock = RULE_LOCK.lock().map_err(|e| e.to_string())?;
+    let store = get_rule_store(data_dir).ok_or("rule store not found".to_string())?;
+    store
+        .get::<ImportRule>(id)
+        .map_err(|_| format!("rule {id} not found"))?;
+    store.save_with_id(&rule, id).map_err(|e| e.to_string())?;
+
+    Ok(ImportRuleInfo {
+        id: id.to_string(),
+        rule,
+    })
+}
+
+pub fn delete_rule(data_dir: &std::path::Path, id: &str) -> Result<(), String> {
+    let _lock = RULE_LOCK.lock().map_err(|e| e.to_string())?;
+    let store = get_rule_store(data_dir).ok_or("rule store not found".to_string())?;
+    store.delete(id).map_err(|e| e.to_string())
+}
+
+/// `path` below `root` with `/` between folders, the form rules match
+/// against and the library keeps
+pub fn relative_path(root: &std::path::Path, path: &std::path::Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+enum CompiledRule {
+    Folders {
+        ignore: HashSet<String>,
+    },
+    Pattern {
+        regex: Regex,
+        target: MatchTarget,
+        tags: Vec<String>,
+    },
+}
+
+/// The enabled rules with their patterns compiled, loaded once per import
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+}
+
+impl RuleSet {
+    pub fn load(data_dir: &std::path::Path) -> Self {
+        Self::compile(list_rules(data_dir).into_iter().map(|info| info.rule))
+    }
+
+    fn compile(rules: impl IntoIterator<Item = ImportRule>) -> Self {
+        let rules = rules
+            .into_iter()
+            .filter(|rule| rule.enabled)
+            .filter_map(|rule| match rule.kind {
+                RuleKind::Folders { ignore } => Some(CompiledRule::Folders {
+                    ignore: ignore.iter().map(|name| name.to_lowercase()).collect(),
+                }),
+                RuleKind::Pattern {
+                    pattern,
+                    target,
+                    tags,
+                } => match Regex::new(&pattern) {
+                    Ok(regex) => Some(CompiledRule::Pattern {
+                        regex,
+                        target,
+                        tags,
+                    }),
+                    Err(e) => {
+                        println!("skipping import rule {}: {e}", rule.name);
+                        None
+                    }
+                },
+            })
+            .collect();
+
+        RuleSet { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Tags for a file at `import_path`, as returned by [`relative_path`]
+    pub fn tags_for(&self, import_path: &str) -> Vec<String> {
+        let (folders, file_name) = match import_path.rsplit_once('/') {
+            Some((folders, file_name)) => (folders, file_name),
+            None => ("", import_path),
+        };
+
+        let mut tags = vec![];
+        for rule in &self.rules {
+            match rule {
+                CompiledRule::Folders { ignore } => tags.extend(
+                    folders
+                        .split('/')
+                        .filter(|folder| !ignore.contains(&folder.to_lowercase()))
+                        .map(|folder| folder.to_string()),
+                ),
+                CompiledRule::Pattern {
+                    regex,
+                    target,
+                    tags: templates,
+                } => {
+                    let text = match target {
+                        MatchTarget::FileName => file_name,
+                        MatchTarget::Path => import_path,
+                    };
+                    if let Some(captures) = regex.captures(text) {
+                        for template in templates {
+                            let mut tag = String::new();
+                            captures.expand(template, &mut tag);
+                            tags.push(tag);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut tags = tags
+            .into_iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect::<Vec<_>>();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+}
+
+/// Runs the rules over everything in the media dir and adds the tags they
+/// produce. With `dry_run` nothing is written and the result shows what
+/// would change. Items imported before paths were recorded only have their
+/// file name to go on.
+pub fn apply_rules(
+    data_dir: &std::path::Path,
+    media_dir: &std::path::Path,
+    dry_run: bool,
+) -> Result<Vec<RulePreview>, String> {
+    let rules = RuleSet::load(data_dir);
+    if rules.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let records = crate::library::all_records(data_dir);
+    let media_ids = crate::local_files::media_ids_in_dir(media_dir);
+
+    let mut import_paths = BTreeMap::new();
+    let mut additions = BTreeMap::new();
+    for media_id in media_ids {
+        let import_path = records
+            .get(&media_id)
+            .and_then(|record| record.import_path.clone())
+            .unwrap_or(media_id.clone());
+        let tags = rules.tags_for(&import_path);
+        if !tags.is_empty() {
+            additions.insert(media_id.clone(), tags);
+            import_paths.insert(media_id, import_path);
+        }
+    }
+
+    // the store resolves aliases and drops tags the item has, the preview
+    // shows what it actually ends up with
+    let added = crate::tags::add_tags_to_media(data_dir, &additions, dry_run)?;
+
+    Ok(added
+        .into_iter()
+        .map(|(media_id, tags)| RulePreview {
+            import_path: import_paths.remove(&media_id).unwrap_or(media_id.clone()),
+            media_id,
+            tags,
+        })
+        .collect())
+}
+
+/// Tags for every item of an import, keyed by media id
+pub fn tag_imports(data_dir: &std::path::Path, imports: &BTreeMap<String, String>) {
+    let rules = RuleSet::load(data_dir);
+    if rules.is_empty() {
+        return;
+    }
+
+    let additions = imports
+        .iter()
+        .map(|(media_id, import_path)| (media_id.clone(), rules.tags_for(import_path)))
+        .filter(|(_, tags)| !tags.is_empty())
+        .collect();
+    if let Err(e) = crate::tags::add_tags_to_media(data_dir, &additions, false) {
+        println!("could not tag import: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folders(ignore: &[&str]) -> ImportRule {
+        ImportRule {
+            name: "folders".to_string(),
+            enabled: true,
+            kind: RuleKind::Folders {
+                ignore: ignore.iter().map(|name| name.to_string()).collect(),
+            },
+        }
+    }
+
+    fn pattern(pattern: &str, target: MatchTarget, tags: &[&str]) -> ImportRule {
+        ImportRule {
+            name: pattern.to_string(),
+            enabled: true,
+            kind: RuleKind::Pattern {
+                pattern: pattern.to_string(),
+                target,
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn folders_become_tags_except_ignored_ones() {
+        let rules = RuleSet::compile([folders(&["photos", "Misc"])]);
+
+        assert_eq!(
+            rules.tags_for("Photos/Cats/Kittens/001.jpg"),
+            ["Cats", "Kittens"]
+        );
+        assert_eq!(rules.tags_for("misc/Dogs/001.jpg"), ["Dogs"]);
+    }
+
+    #[test]
+    fn files_at_the_root_have_no_folders() {
+        let rules = RuleSet::compile([folders(&[])]);
+
+        assert!(rules.tags_for("001.jpg").is_empty());
+    }
+
+    #[test]
+    fn patterns_expand_capture_groups() {
+        let rules = RuleSet::compile([pattern(
+            r"^(\d{4})-(?P<event>[a-z]+)",
+            MatchTarget::FileName,
+            &["year $1", "${event}", "dated"],
+        )]);
+
+        assert_eq!(
+            rules.tags_for("Trips/2023-wedding-042.jpg"),
+            ["dated", "wedding", "year 2023"]
+        );
+        assert!(rules.tags_for("Trips/wedding.jpg").is_empty());
+    }
+
+    #[test]
+    fn path_patterns_see_the_folders() {
+        let rules = RuleSet::compile([
+            pattern(r"^Trips/([^/]+)/", MatchTarget::Path, &["$1"]),
+            // the file name alone has no folders to match
+            pattern(r"^Trips/", MatchTarget::FileName, &["never"]),
+        ]);
+
+        assert_eq!(rules.tags_for("Trips/Paris/001.jpg"), ["Paris"]);
+        assert!(rules.tags_for("001.jpg").is_empty());
+    }
+
+    #[test]
+    fn tags_are_trimmed_and_deduplicated() {
+        let rules = RuleSet::compile([
+            folders(&[]),
+            pattern(r"cat", MatchTarget::FileName, &[" Cats ", "Cats", "   "]),
+            // an empty optional group expands to nothing
+            pattern(r"^(x)?cat", MatchTarget::FileName, &["$1"]),
+        ]);
+
+        assert_eq!(rules.tags_for("Cats/cat.jpg"), ["Cats"]);
+    }
+
+    #[test]
+    fn disabled_and_broken_rules_are_skipped() {
+        let mut disabled = folders(&[]);
+        disabled.enabled = false;
+        let rules = RuleSet::compile([disabled, pattern("(", MatchTarget::Path, &["broken"])]);
+
+        assert!(rules.is_empty());
+    }
+}
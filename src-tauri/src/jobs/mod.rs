@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{mpsc, Arc, Mutex};
+use utoipa::ToSchema;
+
+/// Work that is too slow to run inside a request, all paths are media IDs
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobRequest {
+    Snip {
+        media_id: String,
+        from: String,
+        to: String,
+        clip_name: String,
+        extension: String,
+        save_to_gallery: Option<bool>,
+    },
+    Fix {
+        media_id: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done { result: String },
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Job {
+    pub id: u64,
+    pub request: JobRequest,
+    pub status: JobStatus,
+    pub created_at: u64,
+}
+
+/// A single worker thread running jobs in the order they were enqueued
+#[derive(Clone)]
+pub struct JobQueue {
+    jobs: Arc<Mutex<BTreeMap<u64, Job>>>,
+    sender: mpsc::Sender<u64>,
+}
+
+impl JobQueue {
+    pub fn new(media_dir: std::path::PathBuf, editor_dir: std::path::PathBuf) -> Self {
+        let jobs = Arc::new(Mutex::new(BTreeMap::<u64, Job>::new()));
+        let (sender, receiver) = mpsc::channel::<u64>();
+
+        let worker_jobs = jobs.clone();
+        std::thread::spawn(move || {
+            for id in receiver {
+                let request = {
+                    let mut jobs = worker_jobs.lock().unwrap();
+                    match jobs.get_mut(&id) {
+                        Some(job) => {
+                            job.status = JobStatus::Running;
+                            job.request.clone()
+                        }
+                        None => continue,
+                    }
+                };
+
+                println!("running job {id}: {:?}", request);
+                // ffmpeg helpers still panic on some failures, don't let that kill the worker
+                let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    run_job(&request, &media_dir, &editor_dir)
+                }));
+                let status = match res {
+                    Ok(Ok(result)) => JobStatus::Done { result },
+                    Ok(Err(error)) => JobStatus::Failed { error },
+                    Err(_) => JobStatus::Failed {
+                        error: "job panicked".to_string(),
+                    },
+                };
+                println!("job {id} finished: {:?}", status);
+
+                if let Some(job) = worker_jobs.lock().unwrap().get_mut(&id) {
+                    job.status = status;
+                }
+            }
+        });
+
+        JobQueue { jobs, sender }
+    }
+
+    pub fn enqueue(&self, request: JobRequest) -> Result<Job, String> {
+        let job = {
+            let mut jobs = self.jobs.lock().map_err(|e| e.to_string())?;
+            let id = jobs.keys().next_back().map(|id| id + 1).unwrap_or(1);
+            let job = Job {
+                id,
+                request,
+                status: JobStatus::Queued,
+                created_at: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+            };
+            jobs.insert(id, job.clone());
+            job
+        };
+
+        self.sender.send(job.id).map_err(|e| e.to_string())?;
+
+        Ok(job)
+    }
+
+    pub fn get(&self, id: u64) -> Option<Job> {
+        self.jobs.lock().ok().and_then(|jobs| jobs.get(&id).cloned())
+    }
+
+    pub fn list(&self) -> Vec<Job> {
+        self.jobs
+            .lock()
+            .map(|jobs| jobs.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+fn run_job(
+    request: &JobRequest,
+    media_dir: &std::path::Path,
+    editor_dir: &std::path::Path,
+) -> Result<String, String> {
+    let resolve = |media_id: &str| {
+        crate::local_files::resolve_media_id(media_dir, media_id)
+            .map(|path| path.to_string_lossy().to_string())
+            .ok_or(format!("media {media_id} not found"))
+    };
+
+    match request {
+        JobRequest::Snip {
+            media_id,
+            from,
+            to,
+            clip_name,
+            extension,
+            save_to_gallery,
+        } => {
+            let base_dir = if save_to_gallery.unwrap_or(false) {
+                media_dir
+            } else {
+                editor_dir
+            };
+            crate::local_files::snip_file_to_base_dir(
+                &resolve(media_id)?,
+                &base_dir.to_path_buf(),
+                from,
+                to,
+                clip_name,
+                extension,
+            )
+        }
+        JobRequest::Fix { media_id } => crate::local_files::try_fixing_file(&resolve(media_id)?),
+    }
+}
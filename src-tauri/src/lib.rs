@@ -1,12 +1,23 @@
 use http::{header::*, response::Builder as ResponseBuilder, status::StatusCode};
-use rand::seq::SliceRandom;
-use rand::thread_rng;
 use tauri::Manager;
 
+mod collections;
+mod discovery;
+mod downloads;
+mod history;
+mod hls;
 mod http_server;
+mod import_rules;
+mod jobs;
+mod library;
 mod local_files;
+mod query;
+mod remote_sources;
 mod scrolller;
+mod search;
 mod streaming;
+mod tags;
+mod transcoding;
 mod types;
 
 struct AppData {
@@ -14,12 +25,161 @@ struct AppData {
 }
 
 #[tauri::command(async)]
-async fn get_scrolller_data(iterator: Option<String>, filter: String, is_nsfw: bool) -> String {
-    scrolller::fech_data(iterator, &filter, is_nsfw).await
+async fn get_scrolller_data(
+    client: tauri::State<'_, scrolller::ScrolllerClient>,
+    iterator: Option<String>,
+    params: Option<scrolller::types::DiscoverParams>,
+) -> Result<scrolller::types::Page<scrolller::types::Subreddit>, scrolller::ScrolllerError> {
+    client
+        .discover_filtered_subreddits(iterator, &params.unwrap_or_default())
+        .await
 }
 
-fn scramble_vec<T>(data: &mut Vec<T>) {
-    data.shuffle(&mut thread_rng());
+#[tauri::command(async)]
+async fn get_scrolller_subreddit(
+    client: tauri::State<'_, scrolller::ScrolllerClient>,
+    url: String,
+    iterator: Option<String>,
+    filter: Option<scrolller::types::MediaFilter>,
+    limit: Option<u32>,
+) -> Result<scrolller::types::Page<scrolller::types::SubredditPost>, scrolller::ScrolllerError> {
+    client.subreddit_posts(&url, iterator, filter, limit).await
+}
+
+#[tauri::command(async)]
+async fn search_scrolller_subreddits(
+    client: tauri::State<'_, scrolller::ScrolllerClient>,
+    query: String,
+    is_nsfw: Option<bool>,
+    limit: Option<u32>,
+) -> Result<Vec<scrolller::types::Subreddit>, scrolller::ScrolllerError> {
+    client.search_subreddits(&query, is_nsfw, limit).await
+}
+
+#[tauri::command(async)]
+async fn get_scrolller_album(
+    client: tauri::State<'_, scrolller::ScrolllerClient>,
+    url: String,
+    iterator: Option<String>,
+    limit: Option<u32>,
+) -> Result<scrolller::types::Album, scrolller::ScrolllerError> {
+    client.album(&url, iterator, limit).await
+}
+
+#[tauri::command]
+fn clear_scrolller_cache(
+    client: tauri::State<'_, scrolller::ScrolllerClient>,
+) -> Result<(), String> {
+    client.clear_cache()
+}
+
+#[tauri::command]
+fn set_scrolller_offline(client: tauri::State<'_, scrolller::ScrolllerClient>, offline: bool) {
+    client.set_offline(offline)
+}
+
+#[tauri::command]
+fn get_scrolller_offline(client: tauri::State<'_, scrolller::ScrolllerClient>) -> bool {
+    client.is_offline()
+}
+
+#[tauri::command(async)]
+async fn download_remote_media(
+    app_handle: tauri::AppHandle,
+    post: scrolller::types::SubredditPost,
+) -> Result<downloads::DownloadResult, String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let media_dir = get_media_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+
+    let result = downloads::download_post(&app_handle, &data_dir, &media_dir, &post).await?;
+
+    if let Some(search_index) = app_handle.try_state::<search::SearchIndex>() {
+        if let Err(e) = search_index.index_media(&data_dir, &media_dir, &result.media_id) {
+            println!("could not index {}: {e}", result.media_id);
+        }
+    }
+
+    Ok(result)
+}
+
+#[tauri::command(async)]
+fn search_media(
+    app_handle: tauri::AppHandle,
+    query: &str,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<search::SearchResults, String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let media_dir = get_media_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let search_index = app_handle
+        .try_state::<search::SearchIndex>()
+        .ok_or("search index not available".to_string())?;
+
+    search_index.sync(&data_dir, &media_dir)?;
+    search_index.search(query, &data_dir, &media_dir, offset, limit)
+}
+
+#[tauri::command(async)]
+fn rebuild_search_index(app_handle: tauri::AppHandle) -> Result<usize, String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let media_dir = get_media_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let search_index = app_handle
+        .try_state::<search::SearchIndex>()
+        .ok_or("search index not available".to_string())?;
+
+    search_index.rebuild(&data_dir, &media_dir)
+}
+
+#[tauri::command]
+fn get_media_source(
+    app_handle: tauri::AppHandle,
+    media_id: &str,
+) -> Option<downloads::SourceMetadata> {
+    get_data_dir(app_handle).and_then(|data_dir| downloads::source_for_media(&data_dir, media_id))
+}
+
+#[tauri::command]
+fn list_remote_sources(
+    registry: tauri::State<'_, remote_sources::RemoteSourceRegistry>,
+) -> Vec<remote_sources::RemoteSourceInfo> {
+    registry.list()
+}
+
+#[tauri::command(async)]
+async fn fetch_remote_page(
+    registry: tauri::State<'_, remote_sources::RemoteSourceRegistry>,
+    source_id: String,
+    iterator: Option<String>,
+    filter: Option<remote_sources::RemoteFilter>,
+) -> Result<remote_sources::RemotePage, String> {
+    let source = registry
+        .get(&source_id)
+        .ok_or(format!("no remote source {source_id}"))?;
+
+    source
+        .fetch_page(iterator, &filter.unwrap_or_default())
+        .await
+}
+
+#[tauri::command]
+fn add_remote_source(
+    app_handle: tauri::AppHandle,
+    config: remote_sources::RemoteSourceConfig,
+) -> Result<remote_sources::RemoteSourceInfo, String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+
+    app_handle
+        .state::<remote_sources::RemoteSourceRegistry>()
+        .add_source(&data_dir, config)
+}
+
+#[tauri::command]
+fn remove_remote_source(app_handle: tauri::AppHandle, id: &str) -> Result<(), String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+
+    app_handle
+        .state::<remote_sources::RemoteSourceRegistry>()
+        .remove_source(&data_dir, id)
 }
 
 fn get_media_dir(app_handle: tauri::AppHandle) -> Option<std::path::PathBuf> {
@@ -30,6 +190,18 @@ fn get_editor_dir(app_handle: tauri::AppHandle) -> Option<std::path::PathBuf> {
     get_data_dir(app_handle).map(|data_dir| data_dir.join("editor"))
 }
 
+fn get_thumbnail_dir(app_handle: tauri::AppHandle) -> Option<std::path::PathBuf> {
+    get_data_dir(app_handle).map(|data_dir| data_dir.join("thumbnails"))
+}
+
+fn get_hls_dir(app_handle: tauri::AppHandle) -> Option<std::path::PathBuf> {
+    get_data_dir(app_handle).map(|data_dir| data_dir.join("hls"))
+}
+
+fn get_transcode_dir(app_handle: tauri::AppHandle) -> Option<std::path::PathBuf> {
+    get_data_dir(app_handle).map(|data_dir| data_dir.join("transcode"))
+}
+
 fn get_data_dir(app_handle: tauri::AppHandle) -> Option<std::path::PathBuf> {
     let data_dir = app_handle.path().app_data_dir();
     let data_dir = match data_dir {
@@ -39,6 +211,82 @@ fn get_data_dir(app_handle: tauri::AppHandle) -> Option<std::path::PathBuf> {
     data_dir
 }
 
+/// `file_name` in `media_dir`, or with a number added to the stem when that
+/// is taken, so files with the same name from different folders all land
+fn unique_destination(
+    media_dir: &std::path::Path,
+    file_name: &std::ffi::OsStr,
+) -> std::path::PathBuf {
+    let destination_path = media_dir.join(file_name);
+    if !destination_path.exists() {
+        return destination_path;
+    }
+
+    let name = std::path::Path::new(file_name);
+    let stem = name.file_stem().unwrap_or(file_name).to_string_lossy();
+    let extension = name
+        .extension()
+        .map(|extension| extension.to_string_lossy());
+    (1..)
+        .map(|n| match &extension {
+            Some(extension) => media_dir.join(format!("{stem}-{n}.{extension}")),
+            None => media_dir.join(format!("{stem}-{n}")),
+        })
+        .find(|path| !path.exists())
+        .unwrap_or(destination_path)
+}
+
+/// Hard links every file below `dir` into the media dir, collecting the
+/// media id and path below `root` of each. `imported` maps paths from an
+/// earlier import of the same root to their media id: paths whose file is
+/// still in the media dir are skipped, the others come back under their old
+/// id so their record and tags apply again.
+fn import_dir(
+    root: &std::path::Path,
+    dir: &std::path::Path,
+    media_dir: &std::path::Path,
+    imported: &std::collections::BTreeMap<String, String>,
+    imports: &mut std::collections::BTreeMap<String, String>,
+) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir).map_err(|_| "source path not found".to_string())?;
+    for entry in entries {
+        let entry = entry.map_err(|_| "source path not found".to_string())?;
+        let path = entry.path();
+
+        // file_type doesn't follow symlinks, so a linked folder can't loop
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            import_dir(root, &path, media_dir, imported, imports)?;
+            continue;
+        }
+
+        if !path.is_file() {
+            continue;
+        }
+
+        let import_path = import_rules::relative_path(root, &path);
+        let previous_id = imported.get(&import_path);
+        if previous_id.is_some_and(|id| local_files::resolve_media_id(media_dir, id).is_some()) {
+            continue;
+        }
+
+        let destination_path = match previous_id.map(|id| media_dir.join(id)) {
+            Some(previous_path) if !previous_path.exists() => previous_path,
+            _ => unique_destination(media_dir, &entry.file_name()),
+        };
+        match std::fs::hard_link(&path, &destination_path) {
+            Ok(_) => {
+                imports.insert(
+                    local_files::media_id_from_path(&destination_path.to_string_lossy()),
+                    import_path,
+                );
+            }
+            Err(e) => println!("could not import {}: {e}", path.display()),
+        }
+    }
+
+    Ok(())
+}
+
 #[tauri::command(async)]
 fn move_files_to_data_dir(app_handle: tauri::AppHandle, path: &str) -> Result<String, String> {
     let home = app_handle.path().home_dir();
@@ -47,6 +295,7 @@ fn move_files_to_data_dir(app_handle: tauri::AppHandle, path: &str) -> Result<St
         Ok(home) => Some(home),
         Err(_) => None,
     };
+    let library_dir = get_data_dir(app_handle.clone());
     let data_dir = get_media_dir(app_handle);
 
     let source_path = home.map(|home| home.join(path));
@@ -54,27 +303,37 @@ fn move_files_to_data_dir(app_handle: tauri::AppHandle, path: &str) -> Result<St
     let res = match (source_path, data_dir) {
         (Some(source_path), Some(data_dir)) => {
             if source_path.is_dir() && data_dir.is_dir() {
-                let source_dir = std::fs::read_dir(source_path);
-                if let Ok(source_dir) = source_dir {
-                    for entry in source_dir {
-                        if let Ok(entry) = entry {
-                            let path = entry.path();
-                            let destination_path = data_dir.join(entry.file_name());
-
-                            if path.is_file() && !destination_path.exists() {
-                                //std::fs::copy(&path, &destination_path);
-                                // symlink
-                                std::fs::hard_link(&path, &destination_path);
-                            }
-                        } else {
-                            return Err("source path not found".to_string());
-                        }
-                    }
+                let origin = source_path.to_string_lossy().to_string();
+                let imported = library_dir
+                    .as_ref()
+                    .map(|library_dir| library::all_records(library_dir))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|(_, record)| record.origin.as_deref() == Some(origin.as_str()))
+                    .filter_map(|(media_id, record)| Some((record.import_path?, media_id)))
+                    .collect();
+                let mut imports = std::collections::BTreeMap::new();
+                let res = import_dir(
+                    &source_path,
+                    &source_path,
+                    &data_dir,
+                    &imported,
+                    &mut imports,
+                );
 
-                    Ok("success".to_string())
-                } else {
-                    Err("source path not found".to_string())
+                if let Some(library_dir) = &library_dir {
+                    for (media_id, import_path) in &imports {
+                        library::record_import(
+                            library_dir,
+                            media_id,
+                            &origin,
+                            Some(import_path.as_str()),
+                        );
+                    }
+                    import_rules::tag_imports(library_dir, &imports);
                 }
+
+                res.map(|_| "success".to_string())
             } else {
                 Err("source path or data dir not found".to_string())
             }
@@ -100,31 +359,366 @@ fn clean_data_dir(app_handle: tauri::AppHandle) -> Result<String, String> {
     }
 }
 
-fn load_files_base(app_handle: tauri::AppHandle) -> Vec<types::LocalFile> {
+fn load_files_base(
+    app_handle: tauri::AppHandle,
+    query: Option<String>,
+) -> Result<Vec<types::LocalFile>, String> {
     let start = std::time::Instant::now();
 
-    let data_dir = get_media_dir(app_handle);
+    let data_dir = get_media_dir(app_handle.clone());
     println!("data_dir: {:?}", data_dir);
 
     let base_dir = data_dir;
 
-    let files = local_files::load_local_files_from_base_dir(base_dir);
+    let library_dir = get_data_dir(app_handle);
+    let mut files = match (query, &base_dir, &library_dir) {
+        (Some(query), Some(media_dir), Some(data_dir)) => {
+            let media_ids = local_files::media_ids_in_dir(media_dir);
+            query::filter_media_ids(&query, media_ids, media_dir, data_dir)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .filter_map(|media_id| {
+                    let path = media_dir.join(media_id);
+                    local_files::load_local_file(path.to_str()?, media_dir)
+                })
+                .collect()
+        }
+        _ => local_files::load_local_files_from_base_dir(base_dir.clone()),
+    };
+    if let Some(data_dir) = &library_dir {
+        library::annotate(data_dir, files.iter_mut());
+    }
 
     println!("loaded {} files in {:?}", files.len(), start.elapsed());
 
-    files
+    Ok(files)
+}
+
+#[tauri::command(async)]
+fn load_files(
+    app_handle: tauri::AppHandle,
+    query: Option<String>,
+) -> Result<Vec<types::LocalFile>, String> {
+    load_files_base(app_handle, query)
+}
+
+#[tauri::command(async)]
+fn record_view(
+    app_handle: tauri::AppHandle,
+    media_id: &str,
+    watched: f64,
+    duration: Option<f64>,
+    outcome: Option<history::ViewOutcome>,
+) -> Result<history::ViewStats, String> {
+    let (data_dir, media_id) = library_media_id(&app_handle, media_id)?;
+
+    let outcome = outcome.unwrap_or_else(|| {
+        let duration = duration.or(library::get_record(&data_dir, &media_id).duration);
+        history::ViewOutcome::from_watched(watched, duration)
+    });
+    history::record_view(&data_dir, &media_id, watched, outcome)
+}
+
+#[tauri::command(async)]
+fn view_history(
+    app_handle: tauri::AppHandle,
+    view: history::HistoryView,
+    limit: Option<usize>,
+) -> Result<Vec<history::ViewStats>, String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let media_dir = get_media_dir(app_handle).ok_or("data dir not found".to_string())?;
+
+    Ok(history::query_history(&data_dir, &media_dir, view, limit))
+}
+
+#[tauri::command(async)]
+fn media_history(
+    app_handle: tauri::AppHandle,
+    media_id: &str,
+) -> Result<history::MediaHistory, String> {
+    let data_dir = get_data_dir(app_handle).ok_or("data dir not found".to_string())?;
+
+    Ok(history::history_for_media(
+        &data_dir,
+        &local_files::media_id_from_path(media_id),
+    ))
+}
+
+/// Resolves a gallery id or path to a media id that exists in the media dir
+fn library_media_id(
+    app_handle: &tauri::AppHandle,
+    media_id: &str,
+) -> Result<(std::path::PathBuf, String), String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let media_dir = get_media_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let media_id = local_files::media_id_from_path(media_id);
+    if local_files::resolve_media_id(&media_dir, &media_id).is_none() {
+        return Err(format!("{media_id} is not in the library"));
+    }
+
+    Ok((data_dir, media_id))
+}
+
+#[tauri::command(async)]
+fn set_favorite(
+    app_handle: tauri::AppHandle,
+    media_id: &str,
+    favorite: bool,
+) -> Result<library::MediaRecord, String> {
+    let (data_dir, media_id) = library_media_id(&app_handle, media_id)?;
+    library::set_favorite(&data_dir, &media_id, favorite)
+}
+
+#[tauri::command(async)]
+fn set_rating(
+    app_handle: tauri::AppHandle,
+    media_id: &str,
+    rating: Option<u8>,
+) -> Result<library::MediaRecord, String> {
+    let (data_dir, media_id) = library_media_id(&app_handle, media_id)?;
+    library::set_rating(&data_dir, &media_id, rating)
+}
+
+#[tauri::command(async)]
+fn set_note(
+    app_handle: tauri::AppHandle,
+    media_id: &str,
+    note: Option<String>,
+) -> Result<library::MediaRecord, String> {
+    let (data_dir, media_id) = library_media_id(&app_handle, media_id)?;
+    let record = library::set_note(&data_dir, &media_id, note)?;
+
+    if let (Some(search_index), Some(media_dir)) = (
+        app_handle.try_state::<search::SearchIndex>(),
+        get_media_dir(app_handle.clone()),
+    ) {
+        if let Err(e) = search_index.index_media(&data_dir, &media_dir, &media_id) {
+            println!("could not index {media_id}: {e}");
+        }
+    }
+
+    Ok(record)
+}
+
+#[tauri::command(async)]
+fn list_import_rules(app_handle: tauri::AppHandle) -> Vec<import_rules::ImportRuleInfo> {
+    get_data_dir(app_handle)
+        .map(|data_dir| import_rules::list_rules(&data_dir))
+        .unwrap_or_default()
+}
+
+#[tauri::command(async)]
+fn add_import_rule(
+    app_handle: tauri::AppHandle,
+    rule: import_rules::ImportRule,
+) -> Result<import_rules::ImportRuleInfo, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => import_rules::add_rule(&data_dir, rule),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn update_import_rule(
+    app_handle: tauri::AppHandle,
+    id: &str,
+    rule: import_rules::ImportRule,
+) -> Result<import_rules::ImportRuleInfo, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => import_rules::update_rule(&data_dir, id, rule),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn delete_import_rule(app_handle: tauri::AppHandle, id: &str) -> Result<(), String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => import_rules::delete_rule(&data_dir, id),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+/// Reruns the import rules over the library, `dry_run` only reports the
+/// tags that would be added
+#[tauri::command(async)]
+fn apply_import_rules(
+    app_handle: tauri::AppHandle,
+    dry_run: bool,
+) -> Result<Vec<import_rules::RulePreview>, String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let media_dir = get_media_dir(app_handle).ok_or("data dir not found".to_string())?;
+
+    import_rules::apply_rules(&data_dir, &media_dir, dry_run)
+}
+
+#[tauri::command(async)]
+fn list_collections(app_handle: tauri::AppHandle) -> Vec<collections::CollectionInfo> {
+    get_data_dir(app_handle)
+        .map(|data_dir| collections::list_collections(&data_dir))
+        .unwrap_or_default()
+}
+
+#[tauri::command(async)]
+fn create_collection(
+    app_handle: tauri::AppHandle,
+    collection: collections::Collection,
+) -> Result<collections::CollectionInfo, String> {
+    let collection = match collection.content {
+        collections::CollectionContent::Playlist { items } => collections::Collection {
+            content: collections::CollectionContent::Playlist {
+                items: items
+                    .iter()
+                    .map(|item| local_files::media_id_from_path(item))
+                    .collect(),
+            },
+            ..collection
+        },
+        _ => collection,
+    };
+
+    match get_data_dir(app_handle) {
+        Some(data_dir) => collections::create_collection(&data_dir, collection),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn rename_collection(
+    app_handle: tauri::AppHandle,
+    id: &str,
+    name: &str,
+) -> Result<collections::CollectionInfo, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => collections::rename_collection(&data_dir, id, name),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn set_collection_query(
+    app_handle: tauri::AppHandle,
+    id: &str,
+    query: &str,
+) -> Result<collections::CollectionInfo, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => collections::set_smart_query(&data_dir, id, query),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn delete_collection(app_handle: tauri::AppHandle, id: &str) -> Result<(), String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => collections::delete_collection(&data_dir, id),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn add_to_playlist(
+    app_handle: tauri::AppHandle,
+    id: &str,
+    media_ids: Vec<String>,
+    position: Option<usize>,
+) -> Result<collections::CollectionInfo, String> {
+    let media_ids = media_ids
+        .iter()
+        .map(|media_id| local_files::media_id_from_path(media_id))
+        .collect::<Vec<_>>();
+
+    match get_data_dir(app_handle) {
+        Some(data_dir) => collections::add_to_playlist(&data_dir, id, &media_ids, position),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn remove_from_playlist(
+    app_handle: tauri::AppHandle,
+    id: &str,
+    media_ids: Vec<String>,
+) -> Result<collections::CollectionInfo, String> {
+    let media_ids = media_ids
+        .iter()
+        .map(|media_id| local_files::media_id_from_path(media_id))
+        .collect::<Vec<_>>();
+
+    match get_data_dir(app_handle) {
+        Some(data_dir) => collections::remove_from_playlist(&data_dir, id, &media_ids),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn move_in_playlist(
+    app_handle: tauri::AppHandle,
+    id: &str,
+    from: usize,
+    to: usize,
+) -> Result<collections::CollectionInfo, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => collections::move_in_playlist(&data_dir, id, from, to),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+/// Returns the playlist, and also writes it when given a path
+#[tauri::command(async)]
+fn export_collection_m3u(
+    app_handle: tauri::AppHandle,
+    id: &str,
+    path: Option<String>,
+) -> Result<String, String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let media_dir = get_media_dir(app_handle).ok_or("data dir not found".to_string())?;
+
+    let m3u = collections::export_m3u(&data_dir, &media_dir, id)?;
+    if let Some(path) = path {
+        std::fs::write(path, &m3u).map_err(|e| e.to_string())?;
+    }
+
+    Ok(m3u)
 }
 
 #[tauri::command(async)]
-fn load_files(app_handle: tauri::AppHandle) -> Vec<types::LocalFile> {
-    load_files_base(app_handle)
+fn list_media(
+    app_handle: tauri::AppHandle,
+    params: Option<library::listing::ListMediaParams>,
+) -> Result<library::listing::MediaPage, String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let media_dir = get_media_dir(app_handle).ok_or("data dir not found".to_string())?;
+
+    library::listing::list_media(&data_dir, &media_dir, &params.unwrap_or_default())
 }
 
 #[tauri::command(async)]
-fn load_files_random(app_handle: tauri::AppHandle) -> Vec<types::LocalFile> {
-    let mut result = load_files_base(app_handle);
-    scramble_vec(&mut result);
-    result
+fn load_files_random(
+    app_handle: tauri::AppHandle,
+    query: Option<String>,
+) -> Result<Vec<types::LocalFile>, String> {
+    let mut result = load_files_base(app_handle.clone(), query)?;
+    if let Some(data_dir) = get_data_dir(app_handle) {
+        library::weighting::weighted_shuffle(&data_dir, &mut result);
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+fn get_ordering_weights(app_handle: tauri::AppHandle) -> library::weighting::OrderingWeights {
+    get_data_dir(app_handle)
+        .map(|data_dir| library::weighting::get_weights(&data_dir))
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+fn set_ordering_weights(
+    app_handle: tauri::AppHandle,
+    weights: library::weighting::OrderingWeights,
+) -> Result<library::weighting::OrderingWeights, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => library::weighting::set_weights(&data_dir, &weights),
+        None => Err("data dir not found".to_string()),
+    }
 }
 
 #[tauri::command(async)]
@@ -147,20 +741,36 @@ fn snip_file(
     extension: &str,
     should_save_to_gallery: Option<bool>,
 ) -> Result<String, String> {
-    let base_dir = if should_save_to_gallery.unwrap_or(false) {
+    let should_save_to_gallery = should_save_to_gallery.unwrap_or(false);
+    let library_dir = get_data_dir(app_handle.clone());
+    let base_dir = if should_save_to_gallery {
         get_media_dir(app_handle)
     } else {
         get_editor_dir(app_handle)
     };
     if let Some(base_dir) = base_dir {
-        local_files::snip_file_to_base_dir(
+        let res = local_files::snip_file_to_base_dir(
             source_path_string,
             &base_dir,
             from,
             to,
             clip_name,
             extension,
-        )
+        );
+
+        match (&res, &library_dir) {
+            (Ok(path), Some(library_dir)) if should_save_to_gallery => {
+                let origin = std::path::Path::new(source_path_string)
+                    .parent()
+                    .map(|parent| parent.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let media_id = local_files::media_id_from_path(path);
+                library::record_import(library_dir, &media_id, &origin, None);
+            }
+            _ => {}
+        }
+
+        res
     } else {
         Err("data dir not found".to_string())
     }
@@ -173,9 +783,272 @@ fn try_fixing_file(source_path_string: &str) -> Result<String, String> {
 
 #[tauri::command]
 fn get_http_port(app_handle: tauri::AppHandle) -> u16 {
+    let lan_server = app_handle.state::<http_server::LanServerState>();
     let state = app_handle.state::<AppData>();
 
-    state.port
+    lan_server.port().unwrap_or(state.port)
+}
+
+#[tauri::command(async)]
+fn start_lan_server(
+    app_handle: tauri::AppHandle,
+    interface: Option<String>,
+    port: Option<u16>,
+    instance_name: Option<String>,
+) -> Result<http_server::LanServerStatus, String> {
+    let data_dir = get_data_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let media_dir = get_media_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+    let thumbnail_dir =
+        get_thumbnail_dir(app_handle.clone()).ok_or("data dir not found".to_string())?;
+
+    // make sure there is something to pair with before anyone can connect
+    http_server::auth::get_pairing_token(&data_dir)?;
+
+    let library_size = local_files::media_ids_in_dir(&media_dir).len();
+
+    let status = http_server::start(
+        &app_handle.state::<http_server::LanServerState>(),
+        http_server::LanServerConfig { interface, port },
+        http_server::ServerData {
+            data_dir,
+            media_dir,
+            thumbnail_dir,
+            rate_limiter: http_server::auth::RateLimiter::default(),
+            jobs: app_handle.state::<jobs::JobQueue>().inner().clone(),
+        },
+    )?;
+
+    // the server is still useful without discovery, so only log failures
+    if let (Some(interface), Some(port)) = (&status.interface, status.port) {
+        let instance_name = instance_name.unwrap_or_else(discovery::default_instance_name);
+        if let Err(e) = discovery::advertise(
+            &app_handle.state::<discovery::DiscoveryState>(),
+            &instance_name,
+            interface,
+            port,
+            library_size,
+        ) {
+            println!("could not advertise lan server: {e}");
+        }
+    }
+
+    Ok(status)
+}
+
+#[tauri::command(async)]
+async fn stop_lan_server(
+    app_handle: tauri::AppHandle,
+) -> Result<http_server::LanServerStatus, String> {
+    if let Err(e) = discovery::stop_advertising(&app_handle.state::<discovery::DiscoveryState>()) {
+        println!("could not stop advertising lan server: {e}");
+    }
+
+    http_server::stop(&app_handle.state::<http_server::LanServerState>()).await
+}
+
+#[tauri::command(async)]
+fn discover_peers(
+    app_handle: tauri::AppHandle,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<discovery::Peer>, String> {
+    discovery::discover_peers(
+        &app_handle.state::<discovery::DiscoveryState>(),
+        std::time::Duration::from_millis(timeout_ms.unwrap_or(2000)),
+    )
+}
+
+#[tauri::command(async)]
+fn get_pairing_token(app_handle: tauri::AppHandle) -> Result<String, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => http_server::auth::get_pairing_token(&data_dir),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn reset_pairing_token(app_handle: tauri::AppHandle) -> Result<String, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => http_server::auth::reset_pairing_token(&data_dir),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn list_access_tokens(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<http_server::auth::AccessToken>, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => http_server::auth::list_access_tokens(&data_dir),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn create_access_token(
+    app_handle: tauri::AppHandle,
+    device_name: &str,
+) -> Result<http_server::auth::AccessToken, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => http_server::auth::create_access_token(&data_dir, device_name),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn revoke_access_token(app_handle: tauri::AppHandle, token: &str) -> Result<(), String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => http_server::auth::revoke_access_token(&data_dir, token),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command]
+fn get_lan_server_status(app_handle: tauri::AppHandle) -> http_server::LanServerStatus {
+    app_handle.state::<http_server::LanServerState>().status()
+}
+
+#[tauri::command(async)]
+fn list_tags(app_handle: tauri::AppHandle) -> Vec<tags::Tag> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => tags::list_tags(&data_dir),
+        None => vec![],
+    }
+}
+
+#[tauri::command(async)]
+fn create_tag(app_handle: tauri::AppHandle, tag: tags::Tag) -> Result<tags::Tag, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => tags::create_tag(&data_dir, tag),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn delete_tag(app_handle: tauri::AppHandle, name: &str) -> Result<(), String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => tags::delete_tag(&data_dir, name),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn assign_tag(
+    app_handle: tauri::AppHandle,
+    media_id: &str,
+    name: &str,
+) -> Result<Vec<String>, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => {
+            tags::assign_tag(&data_dir, &local_files::media_id_from_path(media_id), name)
+        }
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn unassign_tag(
+    app_handle: tauri::AppHandle,
+    media_id: &str,
+    name: &str,
+) -> Result<Vec<String>, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => {
+            tags::unassign_tag(&data_dir, &local_files::media_id_from_path(media_id), name)
+        }
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn tags_for_media(app_handle: tauri::AppHandle, media_id: &str) -> Vec<tags::Tag> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => {
+            tags::tag_definitions_for_media(&data_dir, &local_files::media_id_from_path(media_id))
+        }
+        None => vec![],
+    }
+}
+
+#[tauri::command(async)]
+fn list_tag_assignments(
+    app_handle: tauri::AppHandle,
+) -> std::collections::BTreeMap<String, Vec<String>> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => tags::all_assignments(&data_dir),
+        None => Default::default(),
+    }
+}
+
+#[tauri::command(async)]
+fn set_tag_parent(
+    app_handle: tauri::AppHandle,
+    name: &str,
+    parent: Option<&str>,
+) -> Result<tags::Tag, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => tags::set_tag_parent(&data_dir, name, parent),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn add_tag_alias(
+    app_handle: tauri::AppHandle,
+    name: &str,
+    alias: &str,
+) -> Result<tags::Tag, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => tags::add_tag_alias(&data_dir, name, alias),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn remove_tag_alias(
+    app_handle: tauri::AppHandle,
+    name: &str,
+    alias: &str,
+) -> Result<tags::Tag, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => tags::remove_tag_alias(&data_dir, name, alias),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn rename_tag(
+    app_handle: tauri::AppHandle,
+    name: &str,
+    new_name: &str,
+) -> Result<tags::Tag, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => tags::rename_tag(&data_dir, name, new_name),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn merge_tags(
+    app_handle: tauri::AppHandle,
+    sources: Vec<String>,
+    target: &str,
+) -> Result<tags::Tag, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => tags::merge_tags(&data_dir, &sources, target),
+        None => Err("data dir not found".to_string()),
+    }
+}
+
+#[tauri::command(async)]
+fn import_local_storage_tags(
+    app_handle: tauri::AppHandle,
+    tags: Vec<tags::Tag>,
+    assignments: std::collections::HashMap<String, Vec<String>>,
+) -> Result<tags::TagImportSummary, String> {
+    match get_data_dir(app_handle) {
+        Some(data_dir) => tags::import_local_storage_tags(&data_dir, tags, assignments),
+        None => Err("data dir not found".to_string()),
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -190,6 +1063,9 @@ pub fn run() {
             let port = http_server::get_available_port().unwrap_or(8080);
 
             app.manage(AppData { port });
+            app.manage(hls::HlsState::default());
+            app.manage(http_server::LanServerState::default());
+            app.manage(discovery::DiscoveryState::default());
 
             let data_dir = app.path().app_data_dir();
 
@@ -198,52 +1074,280 @@ pub fn run() {
                 Err(_) => None,
             };
 
+            let scrolller_client = scrolller::ScrolllerClient::default();
+            let scrolller_client = match &data_dir {
+                Some(data_dir) => scrolller_client.with_cache(
+                    data_dir.join("cache").join("scrolller"),
+                    scrolller::cache::DEFAULT_TTL,
+                ),
+                None => scrolller_client,
+            };
+
+            let remote_sources = remote_sources::RemoteSourceRegistry::default();
+            remote_sources.register(std::sync::Arc::new(
+                remote_sources::scrolller::ScrolllerSource::new(scrolller_client.clone()),
+            ));
+            if let Some(data_dir) = &data_dir {
+                remote_sources.load(data_dir);
+            }
+
+            app.manage(scrolller_client);
+            app.manage(remote_sources);
+
             if let Some(data_dir) = data_dir {
                 std::fs::create_dir_all(&data_dir);
 
                 let media_dir = data_dir.join("media");
                 let editor_dir = data_dir.join("editor");
+                let transcode_dir = data_dir.join("transcode");
+                let hls_dir = data_dir.join("hls");
 
-                std::fs::create_dir_all(media_dir);
-                std::fs::create_dir_all(editor_dir);
-            }
+                std::fs::create_dir_all(&media_dir);
+                std::fs::create_dir_all(&editor_dir);
+                std::fs::create_dir_all(transcode_dir);
+                std::fs::create_dir_all(hls_dir);
 
-            //tauri::async_runtime::spawn(
-            //    actix_web::HttpServer::new(|| {
-            //        actix_web::App::new()
-            //            .wrap(actix_cors::Cors::permissive())
-            //            .service(http_server::test_handle)
-            //            .service(http_server::file_handle)
-            //    })
-            //    .bind(("127.0.0.1", port.clone()))?
-            //    .run(),
-            //);
+                if let Err(e) = tags::recover_stores(&data_dir) {
+                    println!("could not recover tag stores: {e}");
+                }
+                match history::migrate_history(&data_dir) {
+                    Ok(0) => {}
+                    Ok(moved) => println!("moved {moved} view events into per-item history"),
+                    Err(e) => println!("could not migrate view history: {e}"),
+                }
+
+                match search::SearchIndex::open(&data_dir.join("index").join("search")) {
+                    Ok(search_index) => {
+                        app.manage(search_index);
+                    }
+                    Err(e) => println!("could not open search index: {e}"),
+                }
+
+                app.manage(jobs::JobQueue::new(media_dir, editor_dir));
+            }
 
             Ok(())
         })
-        .register_asynchronous_uri_scheme_protocol("stream", move |_ctx, request, responder| {
-            match streaming::get_stream_response(request) {
-                Ok(http_response) => responder.respond(http_response),
-                Err(e) => responder.respond(
-                    ResponseBuilder::new()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .header(CONTENT_TYPE, "text/plain")
-                        .body(e.to_string().as_bytes().to_vec())
-                        .unwrap(),
-                ),
-            }
+        .register_asynchronous_uri_scheme_protocol("stream", move |ctx, request, responder| {
+            let app_handle = ctx.app_handle().clone();
+            let is_transcode = request.uri().host() == Some("transcode")
+                || request.uri().path().starts_with("/transcode/");
+            let is_hls =
+                request.uri().host() == Some("hls") || request.uri().path().starts_with("/hls/");
+
+            // transcoding can take seconds, keep it off the protocol thread
+            std::thread::spawn(move || {
+                let response = if is_transcode {
+                    match (
+                        get_media_dir(app_handle.clone()),
+                        get_transcode_dir(app_handle),
+                    ) {
+                        (Some(media_dir), Some(transcode_dir)) => {
+                            transcoding::get_transcode_response(request, &media_dir, &transcode_dir)
+                        }
+                        _ => Err("data dir not found".into()),
+                    }
+                } else if is_hls {
+                    match (
+                        get_media_dir(app_handle.clone()),
+                        get_hls_dir(app_handle.clone()),
+                    ) {
+                        (Some(media_dir), Some(hls_dir)) => hls::get_hls_response(
+                            request,
+                            &media_dir,
+                            &hls_dir,
+                            &app_handle.state::<hls::HlsState>(),
+                        ),
+                        _ => Err("data dir not found".into()),
+                    }
+                } else {
+                    streaming::get_stream_response(request)
+                };
+
+                match response {
+                    Ok(http_response) => responder.respond(http_response),
+                    Err(e) => responder.respond(
+                        ResponseBuilder::new()
+                            .status(StatusCode::INTERNAL_SERVER_ERROR)
+                            .header(CONTENT_TYPE, "text/plain")
+                            .body(e.to_string().as_bytes().to_vec())
+                            .unwrap(),
+                    ),
+                }
+            });
         })
         .invoke_handler(tauri::generate_handler![
             load_files,
             load_files_random,
+            list_media,
+            get_ordering_weights,
+            list_collections,
+            create_collection,
+            rename_collection,
+            set_collection_query,
+            delete_collection,
+            add_to_playlist,
+            remove_from_playlist,
+            move_in_playlist,
+            export_collection_m3u,
+            list_import_rules,
+            add_import_rule,
+            update_import_rule,
+            delete_import_rule,
+            apply_import_rules,
+            set_favorite,
+            set_rating,
+            set_note,
+            record_view,
+            view_history,
+            media_history,
+            set_ordering_weights,
             move_files_to_data_dir,
             clean_data_dir,
             get_scrolller_data,
+            get_scrolller_subreddit,
+            search_scrolller_subreddits,
+            get_scrolller_album,
+            clear_scrolller_cache,
+            set_scrolller_offline,
+            get_scrolller_offline,
+            download_remote_media,
+            get_media_source,
+            search_media,
+            rebuild_search_index,
+            list_remote_sources,
+            fetch_remote_page,
+            add_remote_source,
+            remove_remote_source,
+            list_tags,
+            create_tag,
+            delete_tag,
+            assign_tag,
+            unassign_tag,
+            tags_for_media,
+            list_tag_assignments,
+            import_local_storage_tags,
+            set_tag_parent,
+            add_tag_alias,
+            remove_tag_alias,
+            rename_tag,
+            merge_tags,
             move_file_to_data_dir,
             get_http_port,
+            start_lan_server,
+            stop_lan_server,
+            get_lan_server_status,
+            discover_peers,
+            get_pairing_token,
+            reset_pairing_token,
+            list_access_tokens,
+            create_access_token,
+            revoke_access_token,
             snip_file,
             try_fixing_file
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn write(path: &std::path::Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    /// Paths by media id, flipped into the form a later import is given
+    fn by_path(imports: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+        imports
+            .iter()
+            .map(|(media_id, import_path)| (import_path.clone(), media_id.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn imports_files_with_the_same_name_from_different_folders() {
+        let root = tempfile::tempdir().unwrap();
+        let media_dir = tempfile::tempdir().unwrap();
+        write(&root.path().join("cats/001.jpg"), "cat");
+        write(&root.path().join("dogs/001.jpg"), "dog");
+
+        let mut imports = BTreeMap::new();
+        import_dir(
+            root.path(),
+            root.path(),
+            media_dir.path(),
+            &BTreeMap::new(),
+            &mut imports,
+        )
+        .unwrap();
+
+        assert_eq!(imports.len(), 2);
+        assert!(imports.contains_key("001.jpg"));
+        assert!(imports.contains_key("001-1.jpg"));
+        let mut contents = imports
+            .keys()
+            .map(|media_id| std::fs::read_to_string(media_dir.path().join(media_id)).unwrap())
+            .collect::<Vec<_>>();
+        contents.sort();
+        assert_eq!(contents, ["cat", "dog"]);
+    }
+
+    #[test]
+    fn reimports_after_the_media_dir_was_cleared() {
+        let root = tempfile::tempdir().unwrap();
+        let media_dir = tempfile::tempdir().unwrap();
+        write(&root.path().join("cats/001.jpg"), "cat");
+        write(&root.path().join("dogs/001.jpg"), "dog");
+        write(&root.path().join("top.mp4"), "clip");
+
+        let mut first = BTreeMap::new();
+        import_dir(
+            root.path(),
+            root.path(),
+            media_dir.path(),
+            &BTreeMap::new(),
+            &mut first,
+        )
+        .unwrap();
+        assert_eq!(first.len(), 3);
+
+        // loading the folder again while everything is still there
+        let mut again = BTreeMap::new();
+        import_dir(
+            root.path(),
+            root.path(),
+            media_dir.path(),
+            &by_path(&first),
+            &mut again,
+        )
+        .unwrap();
+        assert!(again.is_empty());
+
+        // what clean_data_dir does before a reload
+        for media_id in first.keys() {
+            std::fs::remove_file(media_dir.path().join(media_id)).unwrap();
+        }
+        let mut second = BTreeMap::new();
+        import_dir(
+            root.path(),
+            root.path(),
+            media_dir.path(),
+            &by_path(&first),
+            &mut second,
+        )
+        .unwrap();
+
+        // every file is back under the id its record and tags use
+        assert_eq!(second, first);
+        for (media_id, import_path) in &second {
+            assert_eq!(
+                std::fs::read(media_dir.path().join(media_id)).unwrap(),
+                std::fs::read(root.path().join(import_path)).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use crate::types::LocalFile;
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    Name,
+    Added,
+    Modified,
+    Size,
+    Duration,
+    Resolution,
+    Views,
+    LastViewed,
+    Rating,
+    /// Favorites first, or last when descending
+    Favorite,
+    /// The order of the selected collection, by name without one
+    Position,
+    Random,
+    /// Random, but biased by views, ratings and tags, see [`super::weighting`]
+    Weighted,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ListMediaParams {
+    pub sort: SortKey,
+    pub descending: bool,
+    /// Seeds the `random` and `weighted` orders, the same seed gives the
+    /// same order
+    pub seed: Option<u64>,
+    pub query: Option<String>,
+    /// Only items of this collection, see [`crate::collections`]
+    pub collection: Option<String>,
+    /// Opaque, from the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct MediaPage {
+    pub items: Vec<LocalFile>,
+    /// `None` on the last page
+    pub cursor: Option<String>,
+    pub total: usize,
+}
+
+/// Items without a value for the sort key go last in either direction.
+/// Numbers are integers so they survive the round trip through the cursor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum SortValue {
+    Number(i64),
+    Text(String),
+    Missing,
+}
+
+fn compare_values(a: &SortValue, b: &SortValue, descending: bool) -> Ordering {
+    let ordering = match (a, b) {
+        (SortValue::Missing, SortValue::Missing) => return Ordering::Equal,
+        (SortValue::Missing, _) => return Ordering::Greater,
+        (_, SortValue::Missing) => return Ordering::Less,
+        (SortValue::Number(a), SortValue::Number(b)) => a.cmp(b),
+        (SortValue::Text(a), SortValue::Text(b)) => a.cmp(b),
+        (SortValue::Number(_), SortValue::Text(_)) => Ordering::Less,
+        (SortValue::Text(_), SortValue::Number(_)) => Ordering::Greater,
+    };
+
+    if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+/// Total order over (value, media id), the id breaks ties so every item has
+/// exactly one position
+fn compare_keys(a: &(SortValue, String), b: &(SortValue, String), descending: bool) -> Ordering {
+    compare_values(&a.0, &b.0, descending).then_with(|| a.1.cmp(&b.1))
+}
+
+/// The cursor is the key of the last item handed out, so pages stay
+/// consistent when items are added or removed in between
+fn encode_cursor(key: &(SortValue, String)) -> Option<String> {
+    serde_json::to_vec(key)
+        .ok()
+        .map(|json| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(SortValue, String), String> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or("invalid cursor".to_string())
+}
+
+pub(super) fn random_value(seed: u64, media_id: &str) -> u64 {
+    // DefaultHasher::new is unkeyed, the order only depends on the seed
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    seed.hash(&mut hasher);
+    media_id.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn system_time_secs(time: std::io::Result<std::time::SystemTime>) -> Option<i64> {
+    time.ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs() as i64)
+}
+
+fn is_timed(path: &std::path::Path) -> bool {
+    file_format::FileFormat::from_file(path)
+        .map(|format| {
+            matches!(
+                format.kind(),
+                file_format::Kind::Video | file_format::Kind::Audio
+            )
+        })
+        .unwrap_or(false)
+}
+
+fn sort_value(
+    sort: SortKey,
+    seed: u64,
+    weighted: Option<&HashMap<String, i64>>,
+    data_dir: &std::path::Path,
+    media_dir: &std::path::PathBuf,
+    media_id: &str,
+    record: &super::MediaRecord,
+) -> SortValue {
+    let path = media_dir.join(media_id);
+    let number = |value: Option<i64>| value.map(SortValue::Number).unwrap_or(SortValue::Missing);
+
+    match sort {
+        SortKey::Name | SortKey::Position => SortValue::Text(media_id.to_lowercase()),
+        SortKey::Added => number(super::added_at(record, &path).map(|added| added as i64)),
+        SortKey::Modified => number(
+            std::fs::metadata(&path)
+                .ok()
+                .and_then(|metadata| system_time_secs(metadata.modified())),
+        ),
+        SortKey::Size => number(
+            std::fs::metadata(&path)
+                .ok()
+                .map(|metadata| metadata.len() as i64),
+        ),
+        SortKey::Duration => number(
+            match record.duration {
+                Some(duration) => Some(duration),
+                None if is_timed(&path) => super::probe_duration(data_dir, media_id, &path),
+                None => None,
+            }
+            .map(|duration| (duration * 1000.0) as i64),
+        ),
+        SortKey::Resolution => number(
+            path.to_str()
+                .and_then(|path| {
+                    crate::local_files::get_media_dimensions_from_path(path, media_dir)
+                })
+                .map(|dims| dims.width * dims.height),
+        ),
+        SortKey::Views => SortValue::Number(record.views as i64),
+        SortKey::Rating => number(record.rating.map(|rating| rating as i64)),
+        SortKey::Favorite => SortValue::Number(if record.favorite { 0 } else { 1 }),
+        SortKey::LastViewed => number(record.last_viewed_at.map(|viewed| viewed as i64)),
+        SortKey::Random => SortValue::Number(random_value(seed, media_id) as i64),
+        SortKey::Weighted => number(weighted.and_then(|weighted| weighted.get(media_id).copied())),
+    }
+}
+
+/// One page of the media dir in a stable order. Only the keys are computed
+/// for the whole library, files are loaded for the returned page.
+pub fn list_media(
+    data_dir: &std::path::Path,
+    media_dir: &std::path::PathBuf,
+    params: &ListMediaParams,
+) -> Result<MediaPage, String> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let seed = params.seed.unwrap_or(0);
+
+    let ids = match params.query.as_deref().map(str::trim) {
+        Some(query) if !query.is_empty() => crate::query::filter_media_ids(
+            query,
+            crate::local_files::media_ids_in_dir(media_dir),
+            media_dir,
+            data_dir,
+        )
+        .map_err(|e| e.to_string())?,
+        _ => crate::local_files::media_ids_in_dir(media_dir),
+    };
+
+    let collection_ids = match &params.collection {
+        Some(collection) => Some(crate::collections::collection_media_ids(
+            data_dir, media_dir, collection,
+        )?),
+        None => None,
+    };
+    let ids = match &collection_ids {
+        Some(collection_ids) => {
+            let matching = ids.into_iter().collect::<HashSet<_>>();
+            collection_ids
+                .iter()
+                .filter(|media_id| matching.contains(*media_id))
+                .cloned()
+                .collect()
+        }
+        None => ids,
+    };
+    let positions = collection_ids
+        .iter()
+        .flatten()
+        .enumerate()
+        .map(|(position, media_id)| (media_id.as_str(), position as i64))
+        .collect::<HashMap<_, _>>();
+
+    let records = super::all_records(data_dir);
+    let default_record = super::MediaRecord::default();
+    // fixed per seed, so the cursor stays valid while views come in. Without
+    // a seed there is no feed to keep stable.
+    let weighted = match (params.sort, params.seed) {
+        (SortKey::Weighted, Some(seed)) => Some(super::weighting::seeded_values(
+            data_dir, seed, &ids, &records,
+        )),
+        (SortKey::Weighted, None) => {
+            let weighting = super::weighting::Weighting::load(data_dir);
+            Some(
+                ids.iter()
+                    .map(|media_id| {
+                        let record = records.get(media_id).unwrap_or(&default_record);
+                        (
+                            media_id.clone(),
+                            weighting.sort_value(seed, media_id, record),
+                        )
+                    })
+                    .collect(),
+            )
+        }
+        _ => None,
+    };
+    let mut keys = ids
+        .into_iter()
+        .map(|media_id| {
+            let record = records.get(&media_id).unwrap_or(&default_record);
+            let value = match (params.sort, positions.get(media_id.as_str())) {
+                (SortKey::Position, Some(position)) => SortValue::Number(*position),
+                _ => sort_value(
+                    params.sort,
+                    seed,
+                    weighted.as_ref(),
+                    data_dir,
+                    media_dir,
+                    &media_id,
+                    record,
+                ),
+            };
+            (value, media_id)
+        })
+        .collect::<Vec<_>>();
+    keys.sort_by(|a, b| compare_keys(a, b, params.descending));
+
+    let start = match &params.cursor {
+        Some(cursor) => {
+            let after = decode_cursor(cursor)?;
+            keys.partition_point(|key| {
+                compare_keys(key, &after, params.descending) != Ordering::Greater
+            })
+        }
+        None => 0,
+    };
+    let page = &keys[start.min(keys.len())..(start + limit).min(keys.len())];
+
+    let cursor = if start + limit < keys.len() {
+        page.last().and_then(encode_cursor)
+    } else {
+        None
+    };
+    let mut items = page
+        .iter()
+        .filter_map(|(_, media_id)| {
+            let path = media_dir.join(media_id);
+            crate::local_files::load_local_file(path.to_str()?, media_dir)
+        })
+        .collect::<Vec<_>>();
+    super::annotate(data_dir, items.iter_mut());
+
+    Ok(MediaPage {
+        items,
+        cursor,
+        total: keys.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(media_dir: &std::path::Path, media_id: &str) {
+        std::fs::write(media_dir.join(media_id), b"not really an image").unwrap();
+    }
+
+    fn ids(page: &MediaPage) -> Vec<String> {
+        page.items.iter().map(|file| file.id.clone()).collect()
+    }
+
+    /// Follows the cursors from the first page to the last
+    fn all_pages(
+        data_dir: &std::path::Path,
+        media_dir: &std::path::PathBuf,
+        params: &ListMediaParams,
+    ) -> Vec<String> {
+        let mut params = params.clone();
+        let mut media_ids = vec![];
+        loop {
+            let page = list_media(data_dir, media_dir, &params).unwrap();
+            media_ids.extend(ids(&page));
+            match page.cursor {
+                Some(cursor) => params.cursor = Some(cursor),
+                None => return media_ids,
+            }
+        }
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        for key in [
+            (SortValue::Number(-42), "a.jpg".to_string()),
+            (SortValue::Number(i64::MAX), "b c.mp4".to_string()),
+            (
+                SortValue::Text("ünïcode/?&".to_string()),
+                "d.png".to_string(),
+            ),
+            (SortValue::Missing, "e.gif".to_string()),
+        ] {
+            let cursor = encode_cursor(&key).unwrap();
+            assert!(cursor
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+            assert_eq!(decode_cursor(&cursor).unwrap(), key);
+        }
+
+        assert!(decode_cursor("not a cursor").is_err());
+        assert!(decode_cursor("").is_err());
+    }
+
+    #[test]
+    fn pages_hand_out_every_item_once() {
+        let data = tempfile::tempdir().unwrap();
+        let media = tempfile::tempdir().unwrap();
+        let media_dir = media.path().to_path_buf();
+        for n in 0..25 {
+            add(&media_dir, &format!("item-{n:02}.jpg"));
+        }
+        let expected = (0..25)
+            .map(|n| format!("item-{n:02}.jpg"))
+            .collect::<Vec<_>>();
+
+        for (sort, descending) in [
+            (SortKey::Name, false),
+            (SortKey::Name, true),
+            (SortKey::Random, false),
+            // nothing has been viewed, ties fall back to the id
+            (SortKey::LastViewed, true),
+        ] {
+            let params = ListMediaParams {
+                sort,
+                descending,
+                seed: Some(3),
+                limit: Some(7),
+                ..Default::default()
+            };
+            let first = list_media(data.path(), &media_dir, &params).unwrap();
+            assert_eq!(first.items.len(), 7);
+            assert_eq!(first.total, 25);
+
+            let mut paged = all_pages(data.path(), &media_dir, &params);
+            assert_eq!(paged.len(), 25);
+            if sort == SortKey::Name {
+                let mut sorted = expected.clone();
+                if descending {
+                    sorted.reverse();
+                }
+                assert_eq!(paged, sorted);
+            }
+            paged.sort();
+            assert_eq!(paged, expected);
+        }
+    }
+
+    #[test]
+    fn pages_continue_after_the_cursor_when_items_change() {
+        let data = tempfile::tempdir().unwrap();
+        let media = tempfile::tempdir().unwrap();
+        let media_dir = media.path().to_path_buf();
+        for name in ["b.jpg", "d.jpg", "f.jpg", "h.jpg", "j.jpg"] {
+            add(&media_dir, name);
+        }
+        let params = ListMediaParams {
+            limit: Some(2),
+            ..Default::default()
+        };
+
+        let first = list_media(data.path(), &media_dir, &params).unwrap();
+        assert_eq!(ids(&first), ["b.jpg", "d.jpg"]);
+
+        // the last item handed out goes away, one lands before the cursor
+        // and one after it
+        std::fs::remove_file(media_dir.join("d.jpg")).unwrap();
+        add(&media_dir, "a.jpg");
+        add(&media_dir, "e.jpg");
+
+        let next = ListMediaParams {
+            cursor: first.cursor,
+            ..params.clone()
+        };
+        let second = list_media(data.path(), &media_dir, &next).unwrap();
+        assert_eq!(ids(&second), ["e.jpg", "f.jpg"]);
+        assert_eq!(second.total, 6);
+
+        // removing the rest leaves a cursor that points past the end
+        for name in ["f.jpg", "h.jpg", "j.jpg"] {
+            std::fs::remove_file(media_dir.join(name)).unwrap();
+        }
+        let last = list_media(
+            data.path(),
+            &media_dir,
+            &ListMediaParams {
+                cursor: second.cursor,
+                ..params
+            },
+        )
+        .unwrap();
+        assert!(last.items.is_empty());
+        assert_eq!(last.cursor, None);
+    }
+}
@@ -0,0 +1,190 @@
+pub mod listing;
+pub mod weighting;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Serializes read-modify-write cycles on the library store
+static LIBRARY_LOCK: Mutex<()> = Mutex::new(());
+
+/// What we know about a media item beyond the file itself, keyed by media id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MediaRecord {
+    /// The folder or source the item was imported from
+    pub origin: Option<String>,
+    /// Where the file was below `origin`, for rerunning import rules
+    pub import_path: Option<String>,
+    /// Unix seconds of the first import
+    pub added_at: Option<u64>,
+    /// Seconds, probed once and cached
+    pub duration: Option<f64>,
+    /// Free-form text the user wrote about the item, searchable
+    pub note: Option<String>,
+    pub views: u32,
+    /// Unix seconds of the last view
+    pub last_viewed_at: Option<u64>,
+    /// Seconds played across all views
+    pub watch_time: f64,
+    pub completions: u32,
+    pub skips: u32,
+    /// One to five stars
+    pub rating: Option<u8>,
+    pub favorite: bool,
+}
+
+pub fn get_library_store(data_dir: &std::path::Path) -> Option<jfs::Store> {
+    match jfs::Store::new_with_cfg(
+        data_dir.join("library"),
+        jfs::Config {
+            single: true,
+            indent: 2,
+            pretty: true,
+        },
+    ) {
+        Ok(store) => Some(store),
+        Err(_) => None,
+    }
+}
+
+/// Unix seconds, the unit every stored timestamp uses
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn get_record(data_dir: &std::path::Path, media_id: &str) -> MediaRecord {
+    get_library_store(data_dir)
+        .and_then(|store| store.get::<MediaRecord>(media_id).ok())
+        .unwrap_or_default()
+}
+
+pub fn all_records(data_dir: &std::path::Path) -> BTreeMap<String, MediaRecord> {
+    get_library_store(data_dir)
+        .and_then(|store| store.all::<MediaRecord>().ok())
+        .unwrap_or_default()
+}
+
+pub fn update_record(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    update: impl FnOnce(&mut MediaRecord),
+) -> Result<MediaRecord, String> {
+    let _lock = LIBRARY_LOCK.lock().map_err(|e| e.to_string())?;
+    let store = get_library_store(data_dir).ok_or("library store not found".to_string())?;
+
+    let mut record = store.get::<MediaRecord>(media_id).unwrap_or_default();
+    update(&mut record);
+    store
+        .save_with_id(&record, media_id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(record)
+}
+
+/// Notes where an item came from. Reimports keep the first `added_at`.
+pub fn record_import(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    origin: &str,
+    import_path: Option<&str>,
+) {
+    let res = update_record(data_dir, media_id, |record| {
+        if record.origin.is_none() {
+            record.origin = Some(origin.to_string());
+            record.import_path = import_path.map(|path| path.to_string());
+        }
+        if record.added_at.is_none() {
+            record.added_at = Some(now());
+        }
+    });
+
+    if let Err(e) = res {
+        println!("could not record import of {media_id}: {e}");
+    }
+}
+
+/// Copies favorite, rating and note from the records onto loaded files
+pub fn annotate<'a>(
+    data_dir: &std::path::Path,
+    files: impl IntoIterator<Item = &'a mut crate::types::LocalFile>,
+) {
+    let records = all_records(data_dir);
+    for file in files {
+        if let Some(record) = records.get(&file.id) {
+            file.favorite = record.favorite;
+            file.rating = record.rating;
+            file.note = record.note.clone();
+        }
+    }
+}
+
+pub fn set_favorite(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    favorite: bool,
+) -> Result<MediaRecord, String> {
+    update_record(data_dir, media_id, |record| record.favorite = favorite)
+}
+
+/// `None` clears the rating
+pub fn set_rating(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    rating: Option<u8>,
+) -> Result<MediaRecord, String> {
+    if rating.is_some_and(|rating| !(1..=5).contains(&rating)) {
+        return Err("rating must be between 1 and 5".to_string());
+    }
+
+    update_record(data_dir, media_id, |record| record.rating = rating)
+}
+
+/// Empty notes are removed
+pub fn set_note(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    note: Option<String>,
+) -> Result<MediaRecord, String> {
+    let note = note
+        .map(|note| note.trim().to_string())
+        .filter(|note| !note.is_empty());
+
+    update_record(data_dir, media_id, |record| record.note = note)
+}
+
+/// Items imported before the library existed fall back to the file times
+pub fn added_at(record: &MediaRecord, path: &std::path::Path) -> Option<u64> {
+    record.added_at.or_else(|| {
+        let metadata = std::fs::metadata(path).ok()?;
+        metadata
+            .created()
+            .or_else(|_| metadata.modified())
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs())
+    })
+}
+
+/// Probes the duration and caches it in the record, for callers that
+/// already found none there
+pub fn probe_duration(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    path: &std::path::Path,
+) -> Option<f64> {
+    let duration = ffprobe::ffprobe(path)
+        .ok()?
+        .format
+        .duration
+        .and_then(|duration| duration.parse::<f64>().ok())?;
+    let _ = update_record(data_dir, media_id, |record| {
+        record.duration = Some(duration)
+    });
+
+    Some(duration)
+}
@@ -0,0 +1,395 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+
+const WEIGHTS_ID: &str = "weights";
+const SECONDS_PER_DAY: f64 = 86_400.0;
+/// Seeds whose order is kept. Every feed brings its own seed, older ones
+/// belong to feeds that were replaced.
+const MAX_SNAPSHOTS: usize = 4;
+
+/// Weighted sort values per seed, see [`seeded_values`]
+struct OrderSnapshot {
+    seed: u64,
+    data_dir: std::path::PathBuf,
+    values: HashMap<String, i64>,
+}
+
+static SNAPSHOTS: Mutex<VecDeque<OrderSnapshot>> = Mutex::new(VecDeque::new());
+
+/// Knobs for the weighted order. Every term is added to an item's log
+/// weight, so a term of 1.0 makes an item about e times more likely to come
+/// up early.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrderingWeights {
+    /// Pushes often viewed items back, per `ln(1 + views)`
+    pub views: f64,
+    /// Pulls items forward that were not viewed recently, never viewed
+    /// items get the full amount
+    pub recency: f64,
+    /// Days after which a viewed item has recovered half of `recency`
+    pub recency_half_life_days: f64,
+    /// Per star above or below three
+    pub rating: f64,
+    pub favorite: f64,
+    /// Extra weight per tag, implied parent tags count too
+    pub tags: HashMap<String, f64>,
+    /// Never viewed items come before everything else
+    pub unseen_first: bool,
+}
+
+impl Default for OrderingWeights {
+    fn default() -> Self {
+        OrderingWeights {
+            views: 1.0,
+            recency: 1.0,
+            recency_half_life_days: 7.0,
+            rating: 0.5,
+            favorite: 1.0,
+            tags: HashMap::new(),
+            unseen_first: false,
+        }
+    }
+}
+
+fn get_weights_store(data_dir: &std::path::Path) -> Option<jfs::Store> {
+    match jfs::Store::new_with_cfg(
+        data_dir.join("ordering"),
+        jfs::Config {
+            single: true,
+            indent: 2,
+            pretty: true,
+        },
+    ) {
+        Ok(store) => Some(store),
+        Err(_) => None,
+    }
+}
+
+pub fn get_weights(data_dir: &std::path::Path) -> OrderingWeights {
+    get_weights_store(data_dir)
+        .and_then(|store| store.get::<OrderingWeights>(WEIGHTS_ID).ok())
+        .unwrap_or_default()
+}
+
+pub fn set_weights(
+    data_dir: &std::path::Path,
+    weights: &OrderingWeights,
+) -> Result<OrderingWeights, String> {
+    if weights.recency_half_life_days <= 0.0 {
+        return Err("recency half-life must be positive".to_string());
+    }
+
+    let store = get_weights_store(data_dir).ok_or("ordering store not found".to_string())?;
+    store
+        .save_with_id(weights, WEIGHTS_ID)
+        .map_err(|e| e.to_string())?;
+    // orders handed out so far used the old weights
+    SNAPSHOTS.lock().map_err(|e| e.to_string())?.clear();
+
+    Ok(weights.clone())
+}
+
+/// Everything the weighted order needs, loaded once per listing
+pub struct Weighting {
+    weights: OrderingWeights,
+    definitions: BTreeMap<String, crate::tags::Tag>,
+    assignments: BTreeMap<String, Vec<String>>,
+    now: u64,
+}
+
+impl Weighting {
+    pub fn load(data_dir: &std::path::Path) -> Self {
+        Weighting {
+            weights: get_weights(data_dir),
+            definitions: crate::tags::tag_definitions(data_dir),
+            assignments: crate::tags::all_assignments(data_dir),
+            now: super::now(),
+        }
+    }
+
+    fn log_weight(&self, media_id: &str, record: &super::MediaRecord) -> f64 {
+        let weights = &self.weights;
+        let mut log_weight = -weights.views * (1.0 + record.views as f64).ln();
+
+        log_weight += weights.recency
+            * match record.last_viewed_at {
+                Some(last_viewed_at) => {
+                    let days = self.now.saturating_sub(last_viewed_at) as f64 / SECONDS_PER_DAY;
+                    1.0 - 0.5f64.powf(days / weights.recency_half_life_days)
+                }
+                None => 1.0,
+            };
+
+        if let Some(rating) = record.rating {
+            log_weight += weights.rating * (rating as f64 - 3.0);
+        }
+        if record.favorite {
+            log_weight += weights.favorite;
+        }
+
+        if !weights.tags.is_empty() {
+            let tags = self
+                .assignments
+                .get(media_id)
+                .map(|tags| crate::tags::implied_tags_in(&self.definitions, tags))
+                .unwrap_or_default();
+            log_weight += tags
+                .iter()
+                .filter_map(|tag| weights.tags.get(tag))
+                .sum::<f64>();
+        }
+
+        log_weight
+    }
+
+    /// Weighted sampling without replacement (Efraimidis-Spirakis): with
+    /// `u` uniform in (0, 1), sorting by `-ln(u) / weight` ascending draws
+    /// heavier items earlier. `u` comes from the seed, so the order is
+    /// stable for a seed.
+    ///
+    /// The result is an order-preserving integer so it fits the listing
+    /// cursor exactly. Seen items get a high bit set when unseen ones go first.
+    pub fn sort_value(&self, seed: u64, media_id: &str, record: &super::MediaRecord) -> i64 {
+        let u = (super::listing::random_value(seed, media_id) >> 11) as f64 + 1.0;
+        let u = u / ((1u64 << 53) as f64 + 1.0);
+        let weight = self.log_weight(media_id, record).clamp(-700.0, 700.0).exp();
+        let key = -u.ln() / weight;
+
+        // non-negative floats order like their bit patterns
+        let bits = (key.to_bits() >> 1) as i64;
+        if self.weights.unseen_first && record.views > 0 {
+            bits | (1 << 62)
+        } else {
+            bits
+        }
+    }
+}
+
+/// The weighted sort value of each item for `seed`. Values are computed the
+/// first time an item is listed with a seed and reused after that, so views
+/// recorded while a feed is paged don't move items the cursor already
+/// passed. Callers pass a new seed for every feed, which is what picks up
+/// views, ratings and tags changed since. New weights start over.
+pub fn seeded_values(
+    data_dir: &std::path::Path,
+    seed: u64,
+    media_ids: &[String],
+    records: &BTreeMap<String, super::MediaRecord>,
+) -> HashMap<String, i64> {
+    let mut snapshots = SNAPSHOTS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut snapshot = snapshots
+        .iter()
+        .position(|snapshot| snapshot.seed == seed && snapshot.data_dir == data_dir)
+        .and_then(|position| snapshots.remove(position))
+        .unwrap_or_else(|| OrderSnapshot {
+            seed,
+            data_dir: data_dir.to_path_buf(),
+            values: HashMap::new(),
+        });
+
+    if media_ids
+        .iter()
+        .any(|media_id| !snapshot.values.contains_key(media_id))
+    {
+        let weighting = Weighting::load(data_dir);
+        let default_record = super::MediaRecord::default();
+        for media_id in media_ids {
+            if !snapshot.values.contains_key(media_id) {
+                let record = records.get(media_id).unwrap_or(&default_record);
+                let value = weighting.sort_value(seed, media_id, record);
+                snapshot.values.insert(media_id.clone(), value);
+            }
+        }
+    }
+
+    let values = media_ids
+        .iter()
+        .filter_map(|media_id| Some((media_id.clone(), *snapshot.values.get(media_id)?)))
+        .collect();
+    snapshots.push_front(snapshot);
+    snapshots.truncate(MAX_SNAPSHOTS);
+
+    values
+}
+
+/// Weighted order for files that are already loaded, with a fresh seed
+pub fn weighted_shuffle(data_dir: &std::path::Path, files: &mut [crate::types::LocalFile]) {
+    let weighting = Weighting::load(data_dir);
+    let records = super::all_records(data_dir);
+    let default_record = super::MediaRecord::default();
+    let seed = rand::random::<u64>();
+
+    files.sort_by_cached_key(|file| {
+        let record = records.get(&file.id).unwrap_or(&default_record);
+        weighting.sort_value(seed, &file.id, record)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::MediaRecord;
+
+    const NOW: u64 = 1_700_000_000;
+
+    /// Every knob off, tests turn on the one they look at
+    fn flat_weights() -> OrderingWeights {
+        OrderingWeights {
+            views: 0.0,
+            recency: 0.0,
+            rating: 0.0,
+            favorite: 0.0,
+            ..Default::default()
+        }
+    }
+
+    fn weighting(weights: OrderingWeights) -> Weighting {
+        Weighting {
+            weights,
+            definitions: BTreeMap::new(),
+            assignments: BTreeMap::new(),
+            now: NOW,
+        }
+    }
+
+    fn ids(count: usize) -> Vec<String> {
+        (0..count).map(|n| format!("item-{n}.jpg")).collect()
+    }
+
+    #[test]
+    fn sort_values_order_like_the_float_keys() {
+        let weighting = weighting(flat_weights());
+        let record = MediaRecord::default();
+
+        for seed in 0..5 {
+            // equal weights leave -ln(u) as the key, which falls as u rises
+            let mut by_value = ids(200);
+            by_value.sort_by_key(|media_id| weighting.sort_value(seed, media_id, &record));
+            let mut by_u = ids(200);
+            by_u.sort_by_key(|media_id| {
+                std::cmp::Reverse(super::super::listing::random_value(seed, media_id) >> 11)
+            });
+
+            assert_eq!(by_value, by_u);
+            assert!(by_value
+                .iter()
+                .all(|media_id| weighting.sort_value(seed, media_id, &record) >= 0));
+        }
+    }
+
+    #[test]
+    fn heavier_items_get_smaller_values_for_the_same_draw() {
+        let weighting = weighting(OrderingWeights {
+            favorite: 1.0,
+            ..flat_weights()
+        });
+        let plain = MediaRecord::default();
+        let favorite = MediaRecord {
+            favorite: true,
+            ..Default::default()
+        };
+
+        for media_id in ids(50) {
+            assert!(
+                weighting.sort_value(7, &media_id, &favorite)
+                    < weighting.sort_value(7, &media_id, &plain)
+            );
+        }
+    }
+
+    #[test]
+    fn unseen_items_come_first() {
+        let weighting = weighting(OrderingWeights {
+            unseen_first: true,
+            // would pull the seen items forward without the flag
+            favorite: 50.0,
+            ..flat_weights()
+        });
+        let unseen = MediaRecord::default();
+        let seen = MediaRecord {
+            views: 1,
+            last_viewed_at: Some(NOW),
+            favorite: true,
+            ..Default::default()
+        };
+
+        let ids = ids(100);
+        let latest_unseen = ids
+            .iter()
+            .map(|media_id| weighting.sort_value(3, media_id, &unseen))
+            .max()
+            .unwrap();
+        let earliest_seen = ids
+            .iter()
+            .map(|media_id| weighting.sort_value(3, media_id, &seen))
+            .min()
+            .unwrap();
+        assert!(latest_unseen < earliest_seen);
+    }
+
+    #[test]
+    fn heavier_weights_come_earlier_on_average() {
+        let weighting = weighting(OrderingWeights {
+            favorite: 2.0,
+            ..flat_weights()
+        });
+        let favorite = MediaRecord {
+            favorite: true,
+            ..Default::default()
+        };
+        let plain = MediaRecord::default();
+        let record = |n: usize| if n % 2 == 0 { &favorite } else { &plain };
+
+        let (mut favorite_ranks, mut plain_ranks) = (0, 0);
+        for seed in 0..20 {
+            let mut order = (0..200).collect::<Vec<_>>();
+            order.sort_by_key(|n| weighting.sort_value(seed, &format!("item-{n}.jpg"), record(*n)));
+            for (rank, n) in order.iter().enumerate() {
+                if n % 2 == 0 {
+                    favorite_ranks += rank;
+                } else {
+                    plain_ranks += rank;
+                }
+            }
+        }
+
+        // about e^2 times as likely to come next, so well ahead
+        assert!(
+            favorite_ranks * 3 < plain_ranks * 2,
+            "{favorite_ranks} vs {plain_ranks}"
+        );
+    }
+
+    #[test]
+    fn seeded_values_stay_put_until_the_seed_changes() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let ids = ids(20);
+        let mut records = BTreeMap::new();
+
+        let first = seeded_values(data_dir.path(), 11, &ids, &records);
+        for media_id in &ids {
+            records.insert(
+                media_id.clone(),
+                MediaRecord {
+                    views: 100,
+                    last_viewed_at: Some(super::super::now()),
+                    ..Default::default()
+                },
+            );
+        }
+
+        // a later page of the same feed sees the values it started with
+        assert_eq!(seeded_values(data_dir.path(), 11, &ids, &records), first);
+        // a new feed weighs the views
+        let fresh = seeded_values(data_dir.path(), 12, &ids, &records);
+        let weighting = Weighting::load(data_dir.path());
+        for media_id in &ids {
+            assert_eq!(
+                fresh[media_id],
+                weighting.sort_value(12, media_id, &records[media_id])
+            );
+        }
+    }
+}
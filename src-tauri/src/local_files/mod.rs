@@ -47,6 +47,45 @@ pub fn try_fixing_file(source_path_string: &str) -> Result<String, String> {
     }
 }
 
+/// `[HH:]MM:SS[.ms]` or plain seconds, the forms ffmpeg takes for `-ss`
+/// and `-t`
+fn is_timestamp(value: &str) -> bool {
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, "0"));
+    let parts = whole.split(':').collect::<Vec<_>>();
+
+    // minutes and seconds after the leading part are two digits below 60
+    is_number(fraction)
+        && parts.len() <= 3
+        && is_number(parts[0])
+        && parts[1..].iter().all(|part| {
+            part.len() == 2 && is_number(part) && part.parse::<u32>().is_ok_and(|n| n < 60)
+        })
+}
+
+/// Everything here ends up in a path or on the ffmpeg command line, and
+/// reaches us from LAN clients through jobs
+fn validate_snip(from: &str, to: &str, clip_name: &str, extension: &str) -> Result<(), String> {
+    if clip_name.is_empty()
+        || clip_name.contains(['/', '\\'])
+        || clip_name == "."
+        || clip_name == ".."
+    {
+        return Err("invalid clip name".to_string());
+    }
+    if extension.is_empty()
+        || extension.len() > 8
+        || !extension.bytes().all(|b| b.is_ascii_alphanumeric())
+    {
+        return Err("invalid extension".to_string());
+    }
+    if !is_timestamp(from) || !is_timestamp(to) {
+        return Err("invalid clip times".to_string());
+    }
+
+    Ok(())
+}
+
 pub fn snip_file_to_base_dir(
     source_path_string: &str,
     base_dir: &std::path::PathBuf,
@@ -55,68 +94,62 @@ pub fn snip_file_to_base_dir(
     clip_name: &str,
     extension: &str,
 ) -> Result<String, String> {
+    validate_snip(from, to, clip_name, extension)?;
+
     let source_path = std::path::Path::new(source_path_string);
     // ffmpeg -ss from -i input -c copy -to to output
+    if !source_path.is_file() {
+        return Err("Source path is not a file".to_string());
+    }
 
-    if source_path.is_file() {
-        let source_extension = source_path.extension().unwrap();
-        let dest_path = base_dir.join(clip_name).with_extension(&source_extension);
-        let status = std::process::Command::new("ffmpeg")
-            .arg("-ss")
-            .arg(from)
-            .arg("-i")
-            .arg(&source_path)
-            .arg("-c")
-            .arg("copy")
-            .arg("-t")
-            .arg(to)
-            .arg(&dest_path)
-            .status()
-            .expect("Failed to execute FFmpeg");
+    let source_extension = source_path
+        .extension()
+        .ok_or("Source file has no extension".to_string())?;
+    let dest_path = base_dir.join(clip_name).with_extension(source_extension);
+    let status = std::process::Command::new("ffmpeg")
+        .arg("-ss")
+        .arg(from)
+        .arg("-i")
+        .arg(source_path)
+        .arg("-c")
+        .arg("copy")
+        .arg("-t")
+        .arg(to)
+        .arg(&dest_path)
+        .status()
+        .map_err(|e| format!("Failed to execute FFmpeg: {e}"))?;
+    if !status.success() {
+        return Err("Failed to execute FFmpeg".to_string());
+    }
 
-        let status = if status.success() {
-            Ok(dest_path.to_str().unwrap().to_string())
-        } else {
-            Err("Failed to execute FFmpeg".to_string())
-        };
+    if source_extension == extension {
+        return Ok(dest_path.to_string_lossy().to_string());
+    }
 
-        if (status.is_ok()) {
-            if (source_extension == extension) {
-                Ok(dest_path.to_str().unwrap().to_string())
-            } else {
-                // ffmpeg -i input.mp4 -c:v libvpx-vp9 -crf 30 -b:v 0 -b:a 128k -c:a libopus output.webm
-                let status = std::process::Command::new("ffmpeg")
-                    .arg("-i")
-                    .arg(&dest_path)
-                    .arg("-c:v")
-                    .arg("libvpx-vp9")
-                    .arg("-crf")
-                    .arg("30")
-                    .arg("-b:v")
-                    .arg("0")
-                    .arg("-b:a")
-                    .arg("128k")
-                    .arg("-c:a")
-                    .arg("libopus")
-                    .arg(&dest_path.with_extension(extension))
-                    .status();
-
-                if status.unwrap().success() {
-                    std::fs::remove_file(&dest_path);
-                    Ok(dest_path
-                        .with_extension(extension)
-                        .to_str()
-                        .unwrap()
-                        .to_string())
-                } else {
-                    Err("could not convert".to_string())
-                }
-            }
-        } else {
-            Err(status.unwrap_err())
+    // ffmpeg -i input.mp4 -c:v libvpx-vp9 -crf 30 -b:v 0 -b:a 128k -c:a libopus output.webm
+    let converted_path = dest_path.with_extension(extension);
+    let status = std::process::Command::new("ffmpeg")
+        .arg("-i")
+        .arg(&dest_path)
+        .arg("-c:v")
+        .arg("libvpx-vp9")
+        .arg("-crf")
+        .arg("30")
+        .arg("-b:v")
+        .arg("0")
+        .arg("-b:a")
+        .arg("128k")
+        .arg("-c:a")
+        .arg("libopus")
+        .arg(&converted_path)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {
+            let _ = std::fs::remove_file(&dest_path);
+            Ok(converted_path.to_string_lossy().to_string())
         }
-    } else {
-        Err("Source path is not a file".to_string())
+        _ => Err("could not convert".to_string()),
     }
 }
 
@@ -145,6 +178,75 @@ pub fn hard_link_file_to_base_dir(
     }
 }
 
+/// Returns a small jpeg preview for the media, rendering and caching one for
+/// videos on first use. Images are small enough to be their own thumbnail.
+pub fn get_thumbnail(
+    source_path: &std::path::Path,
+    thumbnail_dir: &std::path::Path,
+) -> Result<std::path::PathBuf, String> {
+    let kind = file_format::FileFormat::from_file(source_path)
+        .map(|format| format.kind())
+        .unwrap_or(file_format::Kind::Other);
+
+    if kind == file_format::Kind::Image {
+        return Ok(source_path.to_path_buf());
+    }
+
+    let file_name = source_path
+        .file_name()
+        .ok_or("Source path is not a file".to_string())?;
+    // the whole name, so a.mp4 and a.mkv don't share a thumbnail
+    let mut thumbnail_name = file_name.to_os_string();
+    thumbnail_name.push(".thumbnail.jpg");
+    let thumbnail_path = thumbnail_dir.join(thumbnail_name);
+
+    if thumbnail_path.is_file() {
+        return Ok(thumbnail_path);
+    }
+
+    std::fs::create_dir_all(thumbnail_dir).map_err(|e| e.to_string())?;
+
+    // a second in skips black intro frames, clips shorter than that get
+    // their first frame
+    let mut res = Err("FFmpeg produced no thumbnail".to_string());
+    for seek in ["1", "0"] {
+        if let Err(e) = render_thumbnail(source_path, &thumbnail_path, seek) {
+            res = Err(e);
+        }
+        if thumbnail_path.is_file() {
+            return Ok(thumbnail_path);
+        }
+    }
+
+    res
+}
+
+fn render_thumbnail(
+    source_path: &std::path::Path,
+    thumbnail_path: &std::path::Path,
+    seek: &str,
+) -> Result<(), String> {
+    // ffmpeg -ss 1 -i input -frames:v 1 -vf scale=320:-2 output.jpg
+    let status = std::process::Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-ss")
+        .arg(seek)
+        .arg("-i")
+        .arg(source_path)
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-vf")
+        .arg("scale=320:-2")
+        .arg(thumbnail_path)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(_) => Err("Failed to execute FFmpeg".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
 pub fn clean_dir<F>(dir: &std::path::PathBuf, filter: F) -> Result<String, String>
 where
     F: Fn(&std::fs::DirEntry) -> bool,
@@ -240,6 +342,51 @@ pub fn get_file_path_strings_from_dir(dir: &std::path::Path) -> Option<Vec<Strin
     }
 }
 
+/// The metadata store and downloads that are still running share the media
+/// dir with the media
+fn is_media_path(path: &str) -> bool {
+    !path.ends_with(".json") && !path.ends_with(".part")
+}
+
+/// Media ids of every file in the media dir, in no particular order
+pub fn media_ids_in_dir(media_dir: &std::path::Path) -> Vec<String> {
+    get_file_path_strings_from_dir(media_dir)
+        .unwrap_or_default()
+        .iter()
+        .filter(|path| std::path::Path::new(path).is_file())
+        .filter(|path| is_media_path(path))
+        .map(|path| media_id_from_path(path))
+        .collect()
+}
+
+/// Media IDs are file names inside the flat media dir, which is how imports
+/// already name the hard links they create.
+pub fn media_id_from_path(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+pub fn resolve_media_id(base_dir: &std::path::Path, id: &str) -> Option<std::path::PathBuf> {
+    let is_plain_name = !id.is_empty()
+        && id != "."
+        && id != ".."
+        && !id.contains('/')
+        && !id.contains('\\');
+
+    if !is_plain_name {
+        return None;
+    }
+
+    let path = base_dir.join(id);
+    if path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
 pub fn get_local_file_metadata_store(base_dir: &std::path::Path) -> Option<jfs::Store> {
     match jfs::Store::new_with_cfg(
         base_dir.join("metadata"),
@@ -254,72 +401,126 @@ pub fn get_local_file_metadata_store(base_dir: &std::path::Path) -> Option<jfs::
     }
 }
 
-pub fn load_local_files_from_base_dir(base_dir: Option<std::path::PathBuf>) -> Vec<LocalFile> {
-    if let Some(base_dir) = base_dir {
-        let paths = get_file_path_strings_from_dir(&base_dir).unwrap_or(vec![]);
+/// The kind and extension of a media file, sniffed from its content with the
+/// file name as a fallback
+pub fn detect_format(path: &str) -> (file_format::Kind, String) {
+    let fmt = file_format::FileFormat::from_file(path);
 
-        let mut files = vec![];
-        for path in paths {
-            let is_json = path.ends_with(".json");
+    // Extract file extension for fallback detection
+    let file_extension = std::path::Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase();
 
-            if is_json {
-                continue;
+    // Determine file kind with MP4 fallback logic
+    let file_kind = match fmt {
+        Ok(format) => format.kind(),
+        Err(_) => {
+            // Fallback for when file-format crate fails
+            match file_extension.as_str() {
+                "mp4" | "m4v" | "mov" | "avi" | "mkv" | "webm" | "flv" => file_format::Kind::Video,
+                "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "svg" => file_format::Kind::Image,
+                "mp3" | "wav" | "flac" | "aac" | "ogg" => file_format::Kind::Audio,
+                _ => file_format::Kind::Other,
             }
+        }
+    };
 
-            let fmt = file_format::FileFormat::from_file(&path);
-            let dims = get_media_dimensions_from_path(&path, &base_dir);
+    // Determine extension with MP4 handling
+    let extension = match fmt {
+        Ok(format) => {
+            let detected_ext = format.extension().to_string();
+            // Handle special cases where file-format might return incorrect extensions
+            if detected_ext.is_empty() || detected_ext == "bin" {
+                file_extension
+            } else {
+                detected_ext
+            }
+        },
+        Err(_) => file_extension,
+    };
 
-            // Extract file extension for fallback detection
-            let file_extension = std::path::Path::new(&path)
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .unwrap_or("")
-                .to_lowercase();
-
-            // Determine file kind with MP4 fallback logic
-            let file_kind = match fmt {
-                Ok(format) => format.kind(),
-                Err(_) => {
-                    // Fallback for when file-format crate fails
-                    match file_extension.as_str() {
-                        "mp4" | "m4v" | "mov" | "avi" | "mkv" | "webm" | "flv" => file_format::Kind::Video,
-                        "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "svg" => file_format::Kind::Image,
-                        "mp3" | "wav" | "flac" | "aac" | "ogg" => file_format::Kind::Audio,
-                        _ => file_format::Kind::Other,
-                    }
-                }
-            };
-
-            // Determine extension with MP4 handling
-            let extension = match fmt {
-                Ok(format) => {
-                    let detected_ext = format.extension().to_string();
-                    // Handle special cases where file-format might return incorrect extensions
-                    if detected_ext.is_empty() || detected_ext == "bin" {
-                        file_extension
-                    } else {
-                        detected_ext
-                    }
-                },
-                Err(_) => file_extension,
-            };
-
-            let file = LocalFile {
-                name: path,
-                lazy: true,
-                data: None,
-                dimensions: dims,
-                kind: KindWrapper(file_kind),
-                extension,
-            };
-            files.push(file);
-            //} else {
-            //    println!("path: {}, file not ok", path);
-            //}
-        }
+    (file_kind, extension)
+}
+
+pub fn load_local_file(path: &str, base_dir: &std::path::PathBuf) -> Option<LocalFile> {
+    if !is_media_path(path) {
+        return None;
+    }
+
+    let (file_kind, extension) = detect_format(path);
+    let dims = get_media_dimensions_from_path(path, base_dir);
+
+    Some(LocalFile {
+        id: media_id_from_path(path),
+        name: path.to_string(),
+        lazy: true,
+        data: None,
+        dimensions: dims,
+        kind: KindWrapper(file_kind),
+        extension,
+        // filled from the library records, see `library::annotate`
+        favorite: false,
+        rating: None,
+        note: None,
+    })
+}
+
+pub fn load_local_files_from_base_dir(base_dir: Option<std::path::PathBuf>) -> Vec<LocalFile> {
+    if let Some(base_dir) = base_dir {
+        let paths = get_file_path_strings_from_dir(&base_dir).unwrap_or(vec![]);
 
-        files
+        paths
+            .iter()
+            .filter_map(|path| load_local_file(path, &base_dir))
+            .collect()
     } else {
         vec![]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_ffmpeg_timestamps() {
+        for value in ["0", "12", "1.5", "01:30", "1:02:03", "00:00:01.250"] {
+            assert!(is_timestamp(value), "{value}");
+        }
+        for value in [
+            "",
+            "-1",
+            "1.",
+            ".5",
+            "1:60",
+            "1:2",
+            "1:02:03:04",
+            "1e3",
+            "-i",
+        ] {
+            assert!(!is_timestamp(value), "{value}");
+        }
+    }
+
+    #[test]
+    fn rejects_snips_that_leave_the_target_dir() {
+        assert!(validate_snip("0", "5", "clip", "mp4").is_ok());
+
+        for clip_name in ["", ".", "..", "a/b", "a\\b"] {
+            assert!(
+                validate_snip("0", "5", clip_name, "mp4").is_err(),
+                "{clip_name}"
+            );
+        }
+        for extension in ["", "m.p4", "../x", "webmwebmw", "mp 4"] {
+            assert!(
+                validate_snip("0", "5", "clip", extension).is_err(),
+                "{extension}"
+            );
+        }
+        assert!(validate_snip("-y", "5", "clip", "mp4").is_err());
+        assert!(validate_snip("0", "5 -f", "clip", "mp4").is_err());
+    }
+}
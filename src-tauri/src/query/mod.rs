@@ -0,0 +1,991 @@
+//! A small query language for the local library:
+//!
+//! `cat AND NOT blurry kind:video duration:10..60 aspect:portrait added:<7d`
+//! `views:0`, `viewed:>30d`, `favorite:yes rating:>=4 note:"redo"`
+//!
+//! Bare words and quoted strings are tags, words next to each other are
+//! ANDed, `-word` is `NOT word`, and parentheses group.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::tags::Tag;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryError {
+    pub message: String,
+    /// Character offset the error was found at
+    pub position: usize,
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+fn error<T>(message: impl Into<String>, position: usize) -> Result<T, QueryError> {
+    Err(QueryError {
+        message: message.into(),
+        position,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AspectClass {
+    Portrait,
+    Landscape,
+    Square,
+}
+
+/// An inclusive range, open on either side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// `>` and `<` rather than `>=` and `<=`
+    pub exclusive: bool,
+}
+
+impl Range {
+    fn contains(&self, value: f64) -> bool {
+        let above = match self.min {
+            Some(min) if self.exclusive => value > min,
+            Some(min) => value >= min,
+            None => true,
+        };
+        let below = match self.max {
+            Some(max) if self.exclusive => value < max,
+            Some(max) => value <= max,
+            None => true,
+        };
+        above && below
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Tag(String),
+    Kind(String),
+    Extension(String),
+    /// Seconds
+    Duration(Range),
+    /// Bytes
+    Size(Range),
+    Width(Range),
+    Height(Range),
+    /// The shorter side, so `res:>=1080` means at least 1080p either way up
+    Resolution(Range),
+    Aspect(AspectClass),
+    Folder(String),
+    /// Unix seconds
+    Added(Range),
+    Views(Range),
+    /// Unix seconds of the last view, never viewed items don't match
+    Viewed(Range),
+    Favorite(bool),
+    /// Unrated items don't match
+    Rating(Range),
+    /// Case-insensitive substring of the note
+    Note(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    All,
+    Predicate(Predicate),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+/// The facts about one item a query is evaluated against. Probing is slow,
+/// so the duration and dimensions are only looked up when the query asks for
+/// them.
+pub struct MediaFacts<'a> {
+    pub kind: &'a str,
+    pub extension: &'a str,
+    /// Assigned tags including the parents they imply
+    pub tags: &'a [String],
+    pub size: Option<u64>,
+    pub origin: Option<&'a str>,
+    pub added_at: Option<u64>,
+    pub views: u32,
+    pub last_viewed_at: Option<u64>,
+    pub favorite: bool,
+    pub rating: Option<u8>,
+    pub note: Option<&'a str>,
+    pub duration: &'a dyn Fn() -> Option<f64>,
+    /// Width and height
+    pub dimensions: &'a dyn Fn() -> Option<(i64, i64)>,
+}
+
+impl Query {
+    pub fn matches(&self, facts: &MediaFacts) -> bool {
+        match self {
+            Query::All => true,
+            Query::Predicate(predicate) => predicate.matches(facts),
+            Query::Not(query) => !query.matches(facts),
+            Query::And(a, b) => a.matches(facts) && b.matches(facts),
+            Query::Or(a, b) => a.matches(facts) || b.matches(facts),
+        }
+    }
+
+    /// Rewrites tag names to their canonical names, so aliases match
+    pub fn resolve_tags(self, definitions: &BTreeMap<String, Tag>) -> Self {
+        match self {
+            Query::Predicate(Predicate::Tag(tag)) => {
+                Query::Predicate(Predicate::Tag(crate::tags::resolve_in(definitions, &tag)))
+            }
+            Query::Not(query) => Query::Not(Box::new(query.resolve_tags(definitions))),
+            Query::And(a, b) => Query::And(
+                Box::new(a.resolve_tags(definitions)),
+                Box::new(b.resolve_tags(definitions)),
+            ),
+            Query::Or(a, b) => Query::Or(
+                Box::new(a.resolve_tags(definitions)),
+                Box::new(b.resolve_tags(definitions)),
+            ),
+            query => query,
+        }
+    }
+}
+
+impl Predicate {
+    fn matches(&self, facts: &MediaFacts) -> bool {
+        let in_range = |value: Option<f64>, range: &Range| value.is_some_and(|v| range.contains(v));
+
+        match self {
+            Predicate::Tag(tag) => facts.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+            Predicate::Kind(kind) => facts.kind == kind.as_str(),
+            Predicate::Extension(extension) => facts.extension.eq_ignore_ascii_case(extension),
+            Predicate::Duration(range) => in_range((facts.duration)(), range),
+            Predicate::Size(range) => in_range(facts.size.map(|size| size as f64), range),
+            Predicate::Width(range) => in_range((facts.dimensions)().map(|(w, _)| w as f64), range),
+            Predicate::Height(range) => {
+                in_range((facts.dimensions)().map(|(_, h)| h as f64), range)
+            }
+            Predicate::Resolution(range) => {
+                in_range((facts.dimensions)().map(|(w, h)| w.min(h) as f64), range)
+            }
+            Predicate::Aspect(class) => match (facts.dimensions)() {
+                Some((w, h)) if w > 0 && h > 0 => {
+                    let ratio = w as f64 / h as f64;
+                    // within 5% counts as square
+                    let actual = if (ratio - 1.0).abs() <= 0.05 {
+                        AspectClass::Square
+                    } else if ratio < 1.0 {
+                        AspectClass::Portrait
+                    } else {
+                        AspectClass::Landscape
+                    };
+                    actual == *class
+                }
+                _ => false,
+            },
+            Predicate::Folder(folder) => facts
+                .origin
+                .is_some_and(|origin| origin.to_lowercase().contains(&folder.to_lowercase())),
+            Predicate::Added(range) => in_range(facts.added_at.map(|t| t as f64), range),
+            Predicate::Views(range) => range.contains(facts.views as f64),
+            Predicate::Viewed(range) => in_range(facts.last_viewed_at.map(|t| t as f64), range),
+            Predicate::Favorite(favorite) => facts.favorite == *favorite,
+            Predicate::Rating(range) => in_range(facts.rating.map(|r| r as f64), range),
+            Predicate::Note(note) => facts
+                .note
+                .is_some_and(|text| text.to_lowercase().contains(&note.to_lowercase())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, QueryError> {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push((Token::Open, i));
+            i += 1;
+        } else if c == ')' {
+            tokens.push((Token::Close, i));
+            i += 1;
+        } else if c == '-' {
+            // only a leading dash negates, dates keep theirs inside the word
+            tokens.push((Token::Not, i));
+            i += 1;
+        } else if c == '"' {
+            let start = i;
+            i += 1;
+            let mut value = String::new();
+            while i < chars.len() && chars[i] != '"' {
+                value.push(chars[i]);
+                i += 1;
+            }
+            if i >= chars.len() {
+                return error("unclosed quote", start);
+            }
+            i += 1;
+            tokens.push((Token::Quoted(value), start));
+        } else {
+            let start = i;
+            let mut value = String::new();
+            while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '(' && chars[i] != ')'
+            {
+                // `folder:"My Videos"`
+                if chars[i] == '"' {
+                    i += 1;
+                    while i < chars.len() && chars[i] != '"' {
+                        value.push(chars[i]);
+                        i += 1;
+                    }
+                    if i >= chars.len() {
+                        return error("unclosed quote", start);
+                    }
+                    i += 1;
+                    continue;
+                }
+                value.push(chars[i]);
+                i += 1;
+            }
+
+            let token = match value.as_str() {
+                "AND" | "&&" => Token::And,
+                "OR" | "||" => Token::Or,
+                "NOT" | "!" => Token::Not,
+                _ => Token::Word(value),
+            };
+            tokens.push((token, start));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// `10`, `1.5k`, `20mb`, `2gb`
+fn parse_size(value: &str) -> Option<f64> {
+    let value = value.to_lowercase();
+    let (number, multiplier) = [
+        ("gb", 1024.0 * 1024.0 * 1024.0),
+        ("mb", 1024.0 * 1024.0),
+        ("kb", 1024.0),
+        ("g", 1024.0 * 1024.0 * 1024.0),
+        ("m", 1024.0 * 1024.0),
+        ("k", 1024.0),
+        ("b", 1.0),
+    ]
+    .iter()
+    .find_map(|(suffix, multiplier)| {
+        value
+            .strip_suffix(suffix)
+            .map(|number| (number.to_string(), *multiplier))
+    })
+    .unwrap_or((value, 1.0));
+
+    number.trim().parse::<f64>().ok().map(|n| n * multiplier)
+}
+
+/// `90`, `90s`, `5m`, `1h`, `1:30`
+fn parse_duration(value: &str) -> Option<f64> {
+    let value = value.to_lowercase();
+    if value.contains(':') {
+        return value.split(':').try_fold(0.0, |total, part| {
+            part.parse::<f64>().ok().map(|n| total * 60.0 + n)
+        });
+    }
+
+    let (number, multiplier) = match value.chars().last()? {
+        's' => (&value[..value.len() - 1], 1.0),
+        'm' => (&value[..value.len() - 1], 60.0),
+        'h' => (&value[..value.len() - 1], 3600.0),
+        _ => (value.as_str(), 1.0),
+    };
+    number.parse::<f64>().ok().map(|n| n * multiplier)
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+    value.parse::<f64>().ok()
+}
+
+/// Days since the unix epoch for a civil date, from Howard Hinnant's algorithm
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// `2024-05-01` as the first and last second of that day in unix seconds,
+/// or `7d`/`2w`/`12h` as the single moment that long ago
+fn parse_date(value: &str, now: u64) -> Option<(f64, f64)> {
+    let parts = value.split('-').collect::<Vec<_>>();
+    if let [year, month, day] = parts.as_slice() {
+        let (year, month, day) = (
+            year.parse::<i64>().ok()?,
+            month.parse::<i64>().ok()?,
+            day.parse::<i64>().ok()?,
+        );
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        let start = days_from_civil(year, month, day) * 86400;
+        return Some((start as f64, (start + 86399) as f64));
+    }
+
+    let unit = match value.chars().last()? {
+        'h' => 3600,
+        'd' => 86400,
+        'w' => 7 * 86400,
+        'y' => 365 * 86400,
+        _ => return None,
+    };
+    let amount = value[..value.len() - 1].parse::<u64>().ok()?;
+    let moment = now.saturating_sub(amount.checked_mul(unit)?) as f64;
+    Some((moment, moment))
+}
+
+/// `>10`, `>=10`, `<10`, `<=10`, `=10`, `10`, `10..20`, `10..`, `..20`
+fn parse_range(value: &str, parse: impl Fn(&str) -> Option<f64>) -> Option<Range> {
+    parse_span_range(value, |part| parse(part).map(|value| (value, value)))
+}
+
+/// Like [`parse_range`] for values that cover a span, such as a whole day.
+/// Bounds take the side of the span that keeps all of it in or out, so
+/// `2024-05-01` matches the whole day and `>2024-05-01` starts the next.
+fn parse_span_range(value: &str, parse: impl Fn(&str) -> Option<(f64, f64)>) -> Option<Range> {
+    let start = |part: &str| parse(part).map(|(start, _)| start);
+    let end = |part: &str| parse(part).map(|(_, end)| end);
+
+    let (min, max, exclusive) = if let Some(rest) = value.strip_prefix(">=") {
+        (Some(start(rest)?), None, false)
+    } else if let Some(rest) = value.strip_prefix("<=") {
+        (None, Some(end(rest)?), false)
+    } else if let Some(rest) = value.strip_prefix('>') {
+        (Some(end(rest)?), None, true)
+    } else if let Some(rest) = value.strip_prefix('<') {
+        (None, Some(start(rest)?), true)
+    } else if let Some((from, to)) = value.split_once("..") {
+        let min = if from.is_empty() {
+            None
+        } else {
+            Some(start(from)?)
+        };
+        let max = if to.is_empty() { None } else { Some(end(to)?) };
+        (min, max, false)
+    } else {
+        let exact = value.strip_prefix('=').unwrap_or(value);
+        (Some(start(exact)?), Some(end(exact)?), false)
+    };
+
+    Some(Range {
+        min,
+        max,
+        exclusive,
+    })
+}
+
+/// `added:<7d` reads as "added less than 7 days ago", which is the later
+/// side of the cutoff, so relative dates flip their comparison. A bare `7d`
+/// means the same as `<7d`, and `7d..30d` is between 30 and 7 days ago.
+/// `viewed:` reads the same way.
+fn parse_added(value: &str, now: u64) -> Option<Range> {
+    let is_relative = |part: &str| {
+        part.chars()
+            .last()
+            .is_some_and(|c| matches!(c, 'h' | 'd' | 'w' | 'y'))
+    };
+    let trimmed = value.trim_start_matches(['<', '>', '=']);
+    let range = parse_span_range(value, |part| parse_date(part, now))?;
+
+    if trimmed.contains("..") {
+        return Some(match (range.min, range.max) {
+            (Some(min), Some(max)) if min > max => Range {
+                min: Some(max),
+                max: Some(min),
+                ..range
+            },
+            _ => range,
+        });
+    }
+    if !is_relative(trimmed) {
+        return Some(range);
+    }
+
+    if trimmed.len() == value.trim_start_matches('=').len() {
+        // a single moment would match a single second
+        Some(Range {
+            min: range.min,
+            max: None,
+            exclusive: true,
+        })
+    } else {
+        Some(Range {
+            min: range.max,
+            max: range.min,
+            exclusive: range.exclusive,
+        })
+    }
+}
+
+fn parse_field(
+    field: &str,
+    value: &str,
+    position: usize,
+    now: u64,
+) -> Result<Predicate, QueryError> {
+    let invalid = |what: &str| error(format!("invalid {what} `{value}`"), position);
+
+    let predicate = match field.to_lowercase().as_str() {
+        "tag" => Predicate::Tag(value.to_string()),
+        "kind" | "type" => match value.to_lowercase().as_str() {
+            kind @ ("video" | "image" | "audio") => Predicate::Kind(kind.to_string()),
+            _ => return invalid("kind"),
+        },
+        "ext" | "extension" => Predicate::Extension(value.trim_start_matches('.').to_string()),
+        "duration" | "length" => match parse_range(value, parse_duration) {
+            Some(range) => Predicate::Duration(range),
+            None => return invalid("duration"),
+        },
+        "size" => match parse_range(value, parse_size) {
+            Some(range) => Predicate::Size(range),
+            None => return invalid("size"),
+        },
+        "width" => match parse_range(value, parse_number) {
+            Some(range) => Predicate::Width(range),
+            None => return invalid("width"),
+        },
+        "height" => match parse_range(value, parse_number) {
+            Some(range) => Predicate::Height(range),
+            None => return invalid("height"),
+        },
+        "res" | "resolution" => {
+            match parse_range(value, |v| parse_number(v.trim_end_matches('p'))) {
+                Some(range) => Predicate::Resolution(range),
+                None => return invalid("resolution"),
+            }
+        }
+        "aspect" | "orientation" => match value.to_lowercase().as_str() {
+            "portrait" | "vertical" => Predicate::Aspect(AspectClass::Portrait),
+            "landscape" | "horizontal" => Predicate::Aspect(AspectClass::Landscape),
+            "square" => Predicate::Aspect(AspectClass::Square),
+            _ => return invalid("aspect"),
+        },
+        "folder" | "origin" => Predicate::Folder(value.to_string()),
+        "added" | "date" => match parse_added(value, now) {
+            Some(range) => Predicate::Added(range),
+            None => return invalid("date"),
+        },
+        "views" => match parse_range(value, parse_number) {
+            Some(range) => Predicate::Views(range),
+            None => return invalid("view count"),
+        },
+        "viewed" | "watched" => match parse_added(value, now) {
+            Some(range) => Predicate::Viewed(range),
+            None => return invalid("date"),
+        },
+        "favorite" | "fav" => match value.to_lowercase().as_str() {
+            "yes" | "true" | "1" => Predicate::Favorite(true),
+            "no" | "false" | "0" => Predicate::Favorite(false),
+            _ => return invalid("favorite"),
+        },
+        "rating" | "stars" => match parse_range(value, parse_number) {
+            Some(range) => Predicate::Rating(range),
+            None => return invalid("rating"),
+        },
+        "note" => Predicate::Note(value.to_string()),
+        _ => return error(format!("unknown field `{field}`"), position),
+    };
+
+    Ok(predicate)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+    end: usize,
+    now: u64,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .map(|(_, position)| *position)
+            .unwrap_or(self.end)
+    }
+
+    fn or(&mut self) -> Result<Query, QueryError> {
+        let mut query = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.index += 1;
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, QueryError> {
+        let mut query = self.not()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.index += 1;
+                }
+                // juxtaposition is an implicit AND
+                Some(Token::Word(_) | Token::Quoted(_) | Token::Not | Token::Open) => {}
+                _ => break,
+            }
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> Result<Query, QueryError> {
+        if self.peek() == Some(&Token::Not) {
+            self.index += 1;
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Query, QueryError> {
+        let position = self.position();
+        let Some((token, _)) = self.tokens.get(self.index).cloned() else {
+            return error("expected a term", position);
+        };
+        self.index += 1;
+
+        match token {
+            Token::Open => {
+                let query = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return error("expected `)`", self.position());
+                }
+                self.index += 1;
+                Ok(query)
+            }
+            Token::Quoted(tag) => Ok(Query::Predicate(Predicate::Tag(tag))),
+            Token::Word(word) => match word.split_once(':') {
+                Some((field, value)) if !value.is_empty() => Ok(Query::Predicate(parse_field(
+                    field, value, position, self.now,
+                )?)),
+                Some(_) => error(format!("`{word}` has no value"), position),
+                None => Ok(Query::Predicate(Predicate::Tag(word))),
+            },
+            token => error(format!("unexpected {token:?}"), position),
+        }
+    }
+}
+
+/// Parses a query, an empty one matches everything
+pub fn parse(input: &str) -> Result<Query, QueryError> {
+    parse_at(input, crate::library::now())
+}
+
+/// [`parse`] with relative dates counted back from `now`
+fn parse_at(input: &str, now: u64) -> Result<Query, QueryError> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(Query::All);
+    }
+
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        end: input.chars().count(),
+        now,
+    };
+    let query = parser.or()?;
+    if parser.index < parser.tokens.len() {
+        return error("unexpected input", parser.position());
+    }
+
+    Ok(query)
+}
+
+/// Keeps the media ids matching `input`, tags are matched through their
+/// aliases and parents. Only the library records are loaded up front, files
+/// are looked at one by one and probed only for fields the query uses.
+pub fn filter_media_ids(
+    input: &str,
+    media_ids: Vec<String>,
+    media_dir: &std::path::PathBuf,
+    data_dir: &std::path::Path,
+) -> Result<Vec<String>, QueryError> {
+    let query = parse(input)?;
+    if query == Query::All {
+        return Ok(media_ids);
+    }
+
+    let definitions = crate::tags::tag_definitions(data_dir);
+    let query = query.resolve_tags(&definitions);
+    let assignments = crate::tags::all_assignments(data_dir);
+    let records = crate::library::all_records(data_dir);
+    let default_record = crate::library::MediaRecord::default();
+
+    Ok(media_ids
+        .into_iter()
+        .filter(|media_id| {
+            let path = media_dir.join(media_id);
+            let Some(path_string) = path.to_str() else {
+                return false;
+            };
+            let record = records.get(media_id).unwrap_or(&default_record);
+            let tags = crate::tags::implied_tags_in(
+                &definitions,
+                assignments
+                    .get(media_id)
+                    .map(|tags| tags.as_slice())
+                    .unwrap_or(&[]),
+            );
+            let (kind, extension) = crate::local_files::detect_format(path_string);
+            let kind = crate::types::KindWrapper(kind);
+            let is_timed = matches!(kind.as_str(), "video" | "audio");
+            // the record caches probed durations, only unprobed files hit ffprobe
+            let duration = || match record.duration {
+                Some(duration) => Some(duration),
+                None if is_timed => crate::library::probe_duration(data_dir, media_id, &path),
+                None => None,
+            };
+            let dimensions = || {
+                crate::local_files::get_media_dimensions_from_path(path_string, media_dir)
+                    .map(|dims| (dims.width, dims.height))
+            };
+
+            query.matches(&MediaFacts {
+                kind: kind.as_str(),
+                extension: &extension,
+                tags: &tags,
+                size: std::fs::metadata(&path).ok().map(|metadata| metadata.len()),
+                origin: record.origin.as_deref(),
+                added_at: crate::library::added_at(record, &path),
+                views: record.views,
+                last_viewed_at: record.last_viewed_at,
+                favorite: record.favorite,
+                rating: record.rating,
+                note: record.note.as_deref(),
+                duration: &duration,
+                dimensions: &dimensions,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+    const DAY: f64 = 86_400.0;
+
+    fn tag(name: &str) -> Query {
+        Query::Predicate(Predicate::Tag(name.to_string()))
+    }
+
+    fn not(query: Query) -> Query {
+        Query::Not(Box::new(query))
+    }
+
+    fn and(a: Query, b: Query) -> Query {
+        Query::And(Box::new(a), Box::new(b))
+    }
+
+    fn or(a: Query, b: Query) -> Query {
+        Query::Or(Box::new(a), Box::new(b))
+    }
+
+    fn field(input: &str) -> Predicate {
+        match parse_at(input, NOW).unwrap() {
+            Query::Predicate(predicate) => predicate,
+            query => panic!("{input} parsed to {query:?}"),
+        }
+    }
+
+    fn range(input: &str) -> Range {
+        match field(input) {
+            Predicate::Duration(range)
+            | Predicate::Size(range)
+            | Predicate::Width(range)
+            | Predicate::Height(range)
+            | Predicate::Resolution(range)
+            | Predicate::Added(range)
+            | Predicate::Views(range)
+            | Predicate::Viewed(range)
+            | Predicate::Rating(range) => range,
+            predicate => panic!("{input} has no range: {predicate:?}"),
+        }
+    }
+
+    fn between(min: f64, max: f64) -> Range {
+        Range {
+            min: Some(min),
+            max: Some(max),
+            exclusive: false,
+        }
+    }
+
+    #[test]
+    fn words_next_to_each_other_are_anded() {
+        assert_eq!(
+            parse_at("cat dog", NOW).unwrap(),
+            and(tag("cat"), tag("dog"))
+        );
+        assert_eq!(
+            parse_at("cat dog", NOW).unwrap(),
+            parse_at("cat AND dog", NOW).unwrap()
+        );
+        assert_eq!(parse_at("", NOW).unwrap(), Query::All);
+        assert_eq!(parse_at("  ", NOW).unwrap(), Query::All);
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse_at("a OR b c", NOW).unwrap(),
+            or(tag("a"), and(tag("b"), tag("c")))
+        );
+        assert_eq!(
+            parse_at("a b || c", NOW).unwrap(),
+            or(and(tag("a"), tag("b")), tag("c"))
+        );
+        assert_eq!(
+            parse_at("(a OR b) c", NOW).unwrap(),
+            and(or(tag("a"), tag("b")), tag("c"))
+        );
+    }
+
+    #[test]
+    fn dash_and_not_negate_the_next_term() {
+        assert_eq!(parse_at("-blurry", NOW).unwrap(), not(tag("blurry")));
+        assert_eq!(
+            parse_at("cat NOT blurry", NOW).unwrap(),
+            and(tag("cat"), not(tag("blurry")))
+        );
+        assert_eq!(
+            parse_at("-(a OR b)", NOW).unwrap(),
+            not(or(tag("a"), tag("b")))
+        );
+        assert_eq!(parse_at("! ! a", NOW).unwrap(), not(not(tag("a"))));
+    }
+
+    #[test]
+    fn quotes_keep_spaces() {
+        assert_eq!(parse_at("\"two words\"", NOW).unwrap(), tag("two words"));
+        assert_eq!(
+            field("folder:\"My Videos\""),
+            Predicate::Folder("My Videos".to_string())
+        );
+        assert_eq!(
+            field("note:\"redo (later)\""),
+            Predicate::Note("redo (later)".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_where_parsing_failed() {
+        let error = parse_at("cat \"open", NOW).unwrap_err();
+        assert_eq!(
+            (error.message.as_str(), error.position),
+            ("unclosed quote", 4)
+        );
+
+        let error = parse_at("(cat", NOW).unwrap_err();
+        assert_eq!(
+            (error.message.as_str(), error.position),
+            ("expected `)`", 4)
+        );
+
+        assert_eq!(parse_at("cat )", NOW).unwrap_err().position, 4);
+        assert_eq!(parse_at("cat AND", NOW).unwrap_err().position, 7);
+        assert!(parse_at("colour:red", NOW).is_err());
+        assert!(parse_at("kind:", NOW).is_err());
+        assert!(parse_at("kind:text", NOW).is_err());
+    }
+
+    #[test]
+    fn numbers_take_unit_suffixes() {
+        assert_eq!(
+            range("size:>20mb"),
+            Range {
+                min: Some(20.0 * 1024.0 * 1024.0),
+                max: None,
+                exclusive: true,
+            }
+        );
+        assert_eq!(
+            range("size:1.5k..2g"),
+            between(1536.0, 2.0 * 1024.0 * 1024.0 * 1024.0)
+        );
+        assert_eq!(range("duration:90"), between(90.0, 90.0));
+        assert_eq!(range("duration:5m..1h"), between(300.0, 3600.0));
+        assert_eq!(range("length:1:30"), between(90.0, 90.0));
+        assert_eq!(range("duration:=45s"), between(45.0, 45.0));
+        assert_eq!(range("res:>=1080p").min, Some(1080.0));
+        assert_eq!(range("rating:<=2").max, Some(2.0));
+        assert!(parse_at("size:lots", NOW).is_err());
+        assert!(parse_at("duration:5x", NOW).is_err());
+    }
+
+    #[test]
+    fn relative_dates_flip_their_comparison() {
+        let week_ago = NOW as f64 - 7.0 * DAY;
+
+        // added less than a week ago
+        assert_eq!(
+            range("added:<7d"),
+            Range {
+                min: Some(week_ago),
+                max: None,
+                exclusive: true,
+            }
+        );
+        // added more than a week ago
+        assert_eq!(
+            range("added:>1w"),
+            Range {
+                min: None,
+                max: Some(week_ago),
+                exclusive: true,
+            }
+        );
+        assert_eq!(range("viewed:<=7d").min, Some(week_ago));
+        assert_eq!(
+            range("added:7d..30d"),
+            between(NOW as f64 - 30.0 * DAY, week_ago)
+        );
+    }
+
+    #[test]
+    fn a_bare_relative_date_means_within() {
+        assert_eq!(range("added:7d"), range("added:<7d"));
+        assert_eq!(range("viewed:12h").min, Some(NOW as f64 - 12.0 * 3600.0));
+        assert_eq!(range("viewed:12h").max, None);
+    }
+
+    #[test]
+    fn a_bare_date_covers_the_whole_day() {
+        // 2024-05-01T00:00:00Z
+        let start = 1_714_521_600.0;
+
+        assert_eq!(range("added:2024-05-01"), between(start, start + DAY - 1.0));
+        assert_eq!(range("added:>2024-05-01").min, Some(start + DAY - 1.0));
+        assert_eq!(range("added:<2024-05-01").max, Some(start));
+        assert_eq!(
+            range("added:2024-05-01..2024-05-02"),
+            between(start, start + 2.0 * DAY - 1.0)
+        );
+        assert!(parse_at("added:2024-13-01", NOW).is_err());
+    }
+
+    #[test]
+    fn huge_relative_dates_are_invalid_rather_than_overflowing() {
+        assert!(parse_at("added:<99999999999999999y", NOW).is_err());
+        assert!(parse_at("added:99999999999999999999d", NOW).is_err());
+        // fits, and reaches back to the epoch
+        assert_eq!(range("added:>100000y").max, Some(0.0));
+    }
+
+    fn facts<'a>(
+        tags: &'a [String],
+        duration: &'a dyn Fn() -> Option<f64>,
+        dimensions: &'a dyn Fn() -> Option<(i64, i64)>,
+    ) -> MediaFacts<'a> {
+        MediaFacts {
+            kind: "video",
+            extension: "MP4",
+            tags,
+            size: Some(30 * 1024 * 1024),
+            origin: Some("/home/me/Videos/Cats"),
+            added_at: Some(NOW - 3 * 86_400),
+            views: 2,
+            last_viewed_at: None,
+            favorite: true,
+            rating: Some(4),
+            note: Some("Redo the intro"),
+            duration,
+            dimensions,
+        }
+    }
+
+    fn matches(input: &str) -> bool {
+        let tags = ["cats".to_string(), "animals".to_string()];
+        let duration = || Some(42.0);
+        let dimensions = || Some((1080, 1920));
+        parse_at(input, NOW)
+            .unwrap()
+            .matches(&facts(&tags, &duration, &dimensions))
+    }
+
+    #[test]
+    fn evaluates_against_the_facts() {
+        assert!(matches("cats"));
+        assert!(matches("CATS animals"));
+        assert!(!matches("cats dogs"));
+        assert!(matches("cats OR dogs"));
+        assert!(matches("-dogs"));
+        assert!(!matches("-(cats OR dogs)"));
+        assert!(matches("kind:video ext:.mp4 aspect:portrait res:1080"));
+        assert!(matches("duration:30..60 size:>20mb"));
+        assert!(!matches("duration:>1m"));
+        assert!(matches("folder:videos/cats"));
+        assert!(matches("added:<7d"));
+        assert!(!matches("added:>7d"));
+        assert!(matches("views:>=2 fav:yes rating:4.. note:redo"));
+        // never viewed items have no last view to compare
+        assert!(!matches("viewed:<7d"));
+        assert!(!matches("viewed:>7d"));
+        assert!(matches("-viewed:<7d"));
+    }
+
+    #[test]
+    fn probes_only_run_when_asked_for() {
+        let tags = [];
+        let probed_duration = std::cell::Cell::new(false);
+        let duration = || {
+            probed_duration.set(true);
+            None
+        };
+        let probed_dimensions = std::cell::Cell::new(false);
+        let dimensions = || {
+            probed_dimensions.set(true);
+            None
+        };
+
+        parse_at("kind:video OR duration:>1 OR aspect:square", NOW)
+            .unwrap()
+            .matches(&facts(&tags, &duration, &dimensions));
+        assert!(!probed_duration.get());
+        assert!(!probed_dimensions.get());
+
+        parse_at("duration:>1", NOW)
+            .unwrap()
+            .matches(&facts(&tags, &duration, &dimensions));
+        assert!(probed_duration.get());
+        assert!(!probed_dimensions.get());
+
+        parse_at("res:>720", NOW)
+            .unwrap()
+            .matches(&facts(&tags, &duration, &dimensions));
+        assert!(probed_dimensions.get());
+    }
+}
@@ -0,0 +1,321 @@
+use tauri_plugin_http::reqwest;
+
+use super::{
+    dimensions, extension, RemoteFilter, RemoteItem, RemoteItemSource, RemotePage, RemoteSource,
+    RemoteSourceInfo,
+};
+
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+const DEFAULT_PAGE_SIZE: usize = 30;
+
+const VIDEO_EXTENSIONS: [&str; 5] = ["mp4", "webm", "m4v", "mov", "mkv"];
+const IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "gif", "webp", "avif"];
+
+/// An RSS, Atom or JSON Feed document, paged locally since feeds are a
+/// single document.
+pub struct FeedSource {
+    id: String,
+    name: String,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl FeedSource {
+    pub fn new(id: String, name: String, url: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        FeedSource {
+            id,
+            name,
+            url,
+            client,
+        }
+    }
+}
+
+/// `video` or `image` from the MIME type, falling back to the extension
+fn media_kind(content_type: Option<&str>, extension: &str) -> Option<&'static str> {
+    match content_type.and_then(|mime| mime.split('/').next()) {
+        Some("video") => Some("video"),
+        Some("image") => Some("image"),
+        _ if VIDEO_EXTENSIONS.contains(&extension) => Some("video"),
+        _ if IMAGE_EXTENSIONS.contains(&extension) => Some("image"),
+        _ => None,
+    }
+}
+
+/// Every image and video in the document, in feed order. Enclosures,
+/// `media:content` and JSON Feed attachments all end up in `entry.media`.
+pub fn parse_feed(body: &[u8], base_url: &str) -> Result<Vec<(RemoteItem, Vec<String>)>, String> {
+    let feed = feed_rs::parser::Builder::new()
+        .base_uri(Some(base_url))
+        .build()
+        .parse(body)
+        .map_err(|e| e.to_string())?;
+
+    let mut items = vec![];
+    for entry in feed.entries {
+        let link = entry
+            .links
+            .first()
+            .map(|link| link.href.clone())
+            .unwrap_or_default();
+        let title = entry.title.map(|title| title.content);
+        let categories = entry
+            .categories
+            .into_iter()
+            .map(|category| category.term)
+            .collect::<Vec<_>>();
+
+        let contents = entry
+            .media
+            .into_iter()
+            .flat_map(|media| media.content)
+            .filter_map(|content| {
+                let url = content.url?.to_string();
+                let content_type = content.content_type.map(|mime| mime.to_string());
+                let extension = extension(&url);
+                let kind = media_kind(content_type.as_deref(), &extension)?;
+                let extension = match (extension.is_empty(), content_type) {
+                    // `image/jpeg` names the extension when the URL doesn't
+                    (true, Some(content_type)) => content_type
+                        .split_once('/')
+                        .map(|(_, subtype)| subtype.to_string())
+                        .unwrap_or_default(),
+                    _ => extension,
+                };
+
+                Some((url, kind, extension, content.width, content.height))
+            });
+
+        for (index, (url, kind, extension, width, height)) in contents.enumerate() {
+            let item = RemoteItem {
+                name: format!("{}#{index}", entry.id),
+                kind: kind.to_string(),
+                item_type: "remote".to_string(),
+                extension,
+                src: vec![RemoteItemSource {
+                    dimensions: match (width, height) {
+                        (Some(width), Some(height)) => dimensions(width as i64, height as i64),
+                        _ => None,
+                    },
+                    url,
+                }],
+                additional: serde_json::json!({
+                    "sourceUrl": link,
+                    "title": title,
+                }),
+            };
+            items.push((item, categories.clone()));
+        }
+    }
+
+    Ok(items)
+}
+
+/// One page of the parsed items, the iterator is the offset of the next one
+pub fn page_items(
+    items: Vec<(RemoteItem, Vec<String>)>,
+    iterator: Option<String>,
+    filter: &RemoteFilter,
+) -> Result<RemotePage, String> {
+    let offset = match iterator.as_deref() {
+        Some(iterator) if !iterator.is_empty() => iterator
+            .parse::<usize>()
+            .map_err(|_| "invalid iterator".to_string())?,
+        _ => 0,
+    };
+    let limit = filter
+        .limit
+        .map(|limit| limit as usize)
+        .unwrap_or(DEFAULT_PAGE_SIZE);
+
+    let items = items
+        .into_iter()
+        .filter(|(item, _)| match &filter.kind {
+            Some(kind) => &item.kind == kind,
+            None => true,
+        })
+        .filter(|(_, categories)| filter.tags.iter().all(|tag| categories.contains(tag)))
+        .map(|(item, _)| item)
+        .collect::<Vec<_>>();
+
+    let total = items.len();
+    let items = items
+        .into_iter()
+        .skip(offset)
+        .take(limit.max(1))
+        .collect::<Vec<_>>();
+    let next_offset = offset + items.len();
+
+    Ok(RemotePage {
+        iterator: if next_offset < total {
+            Some(next_offset.to_string())
+        } else {
+            None
+        },
+        items,
+    })
+}
+
+#[async_trait::async_trait]
+impl RemoteSource for FeedSource {
+    fn info(&self) -> RemoteSourceInfo {
+        RemoteSourceInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            provider: "feed",
+            removable: true,
+        }
+    }
+
+    async fn fetch_page(
+        &self,
+        iterator: Option<String>,
+        filter: &RemoteFilter,
+    ) -> Result<RemotePage, String> {
+        let response = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "{} responded with {}",
+                self.name,
+                response.status()
+            ));
+        }
+        let body = response.bytes().await.map_err(|e| e.to_string())?;
+
+        page_items(parse_feed(&body, &self.url)?, iterator, filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE_URL: &str = "https://example.com/feed";
+
+    fn urls(items: &[(RemoteItem, Vec<String>)]) -> Vec<&str> {
+        items
+            .iter()
+            .map(|(item, _)| item.src[0].url.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn parses_rss_enclosures() {
+        let items = parse_feed(include_bytes!("fixtures/rss.xml"), BASE_URL).unwrap();
+
+        assert_eq!(
+            urls(&items),
+            [
+                "https://example.com/media/clip.mp4",
+                "https://example.com/media/photo.jpg"
+            ]
+        );
+        let (clip, categories) = &items[0];
+        assert_eq!(clip.kind, "video");
+        assert_eq!(clip.extension, "mp4");
+        assert_eq!(clip.additional["title"], "A clip");
+        assert_eq!(clip.additional["sourceUrl"], "https://example.com/posts/1");
+        assert_eq!(categories, &["cats".to_string()]);
+        assert_eq!(items[1].0.kind, "image");
+    }
+
+    #[test]
+    fn parses_atom_media_content() {
+        let items = parse_feed(include_bytes!("fixtures/atom.xml"), BASE_URL).unwrap();
+
+        // the audio enclosure is neither image nor video and is skipped
+        assert_eq!(
+            urls(&items),
+            [
+                "https://example.com/media/first.webm",
+                "https://example.com/media/second"
+            ]
+        );
+        let first = &items[0].0;
+        assert_eq!(first.kind, "video");
+        let dimensions = first.src[0].dimensions.as_ref().unwrap();
+        assert_eq!((dimensions.width, dimensions.height), (1280, 720));
+        // no extension in the URL, the MIME subtype stands in
+        assert_eq!(items[1].0.extension, "png");
+        assert_eq!(items[1].0.kind, "image");
+    }
+
+    #[test]
+    fn parses_json_feed_attachments() {
+        let items = parse_feed(include_bytes!("fixtures/feed.json"), BASE_URL).unwrap();
+
+        assert_eq!(
+            urls(&items),
+            [
+                "https://example.com/media/a.gif",
+                "https://example.com/media/b.mov"
+            ]
+        );
+        // items of one entry share its id and are numbered
+        assert_eq!(items[0].0.name, "https://example.com/posts/json-1#0");
+        assert_eq!(items[1].0.name, "https://example.com/posts/json-1#1");
+        assert_eq!(items[1].0.kind, "video");
+    }
+
+    #[test]
+    fn pages_by_offset() {
+        let items = parse_feed(include_bytes!("fixtures/rss.xml"), BASE_URL).unwrap();
+        let filter = RemoteFilter {
+            limit: Some(1),
+            ..Default::default()
+        };
+
+        let first = page_items(items.clone(), None, &filter).unwrap();
+        assert_eq!(first.items.len(), 1);
+        assert_eq!(
+            first.items[0].src[0].url,
+            "https://example.com/media/clip.mp4"
+        );
+        assert_eq!(first.iterator.as_deref(), Some("1"));
+
+        let second = page_items(items.clone(), first.iterator, &filter).unwrap();
+        assert_eq!(
+            second.items[0].src[0].url,
+            "https://example.com/media/photo.jpg"
+        );
+        assert_eq!(second.iterator, None);
+
+        let past_end = page_items(items.clone(), Some("5".to_string()), &filter).unwrap();
+        assert!(past_end.items.is_empty());
+        assert_eq!(past_end.iterator, None);
+
+        assert!(page_items(items, Some("nope".to_string()), &filter).is_err());
+    }
+
+    #[test]
+    fn filters_before_paging() {
+        let items = parse_feed(include_bytes!("fixtures/rss.xml"), BASE_URL).unwrap();
+
+        let images = RemoteFilter {
+            kind: Some("image".to_string()),
+            ..Default::default()
+        };
+        let page = page_items(items.clone(), None, &images).unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].kind, "image");
+        assert_eq!(page.iterator, None);
+
+        let tagged = RemoteFilter {
+            tags: vec!["cats".to_string()],
+            ..Default::default()
+        };
+        let page = page_items(items, None, &tagged).unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].kind, "video");
+    }
+}
@@ -0,0 +1,96 @@
+use tauri_plugin_http::reqwest;
+
+use super::{RemoteFilter, RemotePage, RemoteSource, RemoteSourceInfo};
+
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// A URL serving `{ iterator, items }` pages of `FinalFile`s, which is what
+/// another instance's `/feed` returns.
+pub struct JsonSource {
+    id: String,
+    name: String,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl JsonSource {
+    pub fn new(id: String, name: String, url: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        JsonSource {
+            id,
+            name,
+            url,
+            client,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RemoteSource for JsonSource {
+    fn info(&self) -> RemoteSourceInfo {
+        RemoteSourceInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            provider: "json",
+            removable: true,
+        }
+    }
+
+    async fn fetch_page(
+        &self,
+        iterator: Option<String>,
+        filter: &RemoteFilter,
+    ) -> Result<RemotePage, String> {
+        let mut url = reqwest::Url::parse(&self.url).map_err(|e| e.to_string())?;
+        {
+            let mut query = url.query_pairs_mut();
+            if let Some(iterator) = iterator.filter(|iterator| !iterator.is_empty()) {
+                query.append_pair("iterator", &iterator);
+            }
+            if !filter.tags.is_empty() {
+                query.append_pair("tags", &filter.tags.join(","));
+            }
+            if let Some(kind) = &filter.kind {
+                query.append_pair("kind", kind);
+            }
+            if let Some(limit) = filter.limit {
+                query.append_pair("limit", &limit.to_string());
+            }
+        }
+
+        let response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "{} responded with {}",
+                self.name,
+                response.status()
+            ));
+        }
+
+        let text = response.text().await.map_err(|e| e.to_string())?;
+        let mut page = serde_json::from_str::<RemotePage>(&text).map_err(|e| e.to_string())?;
+
+        // item URLs may be relative to the feed
+        for item in page.items.iter_mut() {
+            if item.item_type.is_empty() {
+                item.item_type = "remote".to_string();
+            }
+            for src in item.src.iter_mut() {
+                if let Ok(resolved) = url.join(&src.url) {
+                    src.url = resolved.to_string();
+                }
+            }
+        }
+
+        Ok(page)
+    }
+}
@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use crate::types::Dimensions;
+
+pub mod feed;
+pub mod json;
+pub mod scrolller;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteItemSource {
+    pub url: String,
+    pub dimensions: Option<Dimensions>,
+}
+
+/// Mirrors the frontend `FinalFile` type, so every provider renders the same
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteItem {
+    pub name: String,
+    pub src: Vec<RemoteItemSource>,
+    /// `video` or `image`
+    pub kind: String,
+    #[serde(rename = "type", default)]
+    pub item_type: String,
+    pub extension: String,
+    #[serde(default)]
+    pub additional: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemotePage {
+    /// Pass back to get the next page, `null` once the source is exhausted
+    pub iterator: Option<String>,
+    pub items: Vec<RemoteItem>,
+}
+
+/// What the frontend asks for, providers apply what they support
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteFilter {
+    /// `video` or `image`
+    pub kind: Option<String>,
+    pub tags: Vec<String>,
+    pub is_nsfw: Option<bool>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteSourceInfo {
+    pub id: String,
+    pub name: String,
+    /// The provider behind the source, like `scrolller` or `json`
+    pub provider: &'static str,
+    /// Built-in sources can't be removed
+    pub removable: bool,
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+pub fn dimensions(width: i64, height: i64) -> Option<Dimensions> {
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+
+    let divisor = gcd(width, height);
+    Some(Dimensions {
+        width,
+        height,
+        aspect_ratio: format!("{}:{}", width / divisor, height / divisor),
+    })
+}
+
+/// Lowercased extension of the URL path, ignoring query and fragment
+pub fn extension(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    std::path::Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default()
+}
+
+#[async_trait::async_trait]
+pub trait RemoteSource: Send + Sync {
+    fn info(&self) -> RemoteSourceInfo;
+
+    async fn fetch_page(
+        &self,
+        iterator: Option<String>,
+        filter: &RemoteFilter,
+    ) -> Result<RemotePage, String>;
+}
+
+/// Sources the user added, the built-in ones are registered on startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum RemoteSourceConfig {
+    Json { name: String, url: String },
+    Feed { name: String, url: String },
+}
+
+impl RemoteSourceConfig {
+    fn into_source(self, id: String) -> Arc<dyn RemoteSource> {
+        match self {
+            RemoteSourceConfig::Json { name, url } => {
+                Arc::new(json::JsonSource::new(id, name, url))
+            }
+            RemoteSourceConfig::Feed { name, url } => {
+                Arc::new(feed::FeedSource::new(id, name, url))
+            }
+        }
+    }
+}
+
+pub fn get_remote_source_store(data_dir: &std::path::Path) -> Option<jfs::Store> {
+    match jfs::Store::new_with_cfg(
+        data_dir.join("remote_sources"),
+        jfs::Config {
+            single: true,
+            indent: 2,
+            pretty: true,
+        },
+    ) {
+        Ok(store) => Some(store),
+        Err(_) => None,
+    }
+}
+
+#[derive(Default)]
+pub struct RemoteSourceRegistry {
+    sources: RwLock<BTreeMap<String, Arc<dyn RemoteSource>>>,
+}
+
+impl RemoteSourceRegistry {
+    pub fn register(&self, source: Arc<dyn RemoteSource>) {
+        if let Ok(mut sources) = self.sources.write() {
+            sources.insert(source.info().id, source);
+        }
+    }
+
+    pub fn unregister(&self, id: &str) {
+        if let Ok(mut sources) = self.sources.write() {
+            sources.remove(id);
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<dyn RemoteSource>> {
+        self.sources.read().ok()?.get(id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<RemoteSourceInfo> {
+        self.sources
+            .read()
+            .map(|sources| sources.values().map(|source| source.info()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Registers every source saved with `add_source`
+    pub fn load(&self, data_dir: &std::path::Path) {
+        let configs = get_remote_source_store(data_dir)
+            .and_then(|store| store.all::<RemoteSourceConfig>().ok())
+            .unwrap_or_default();
+
+        for (id, config) in configs {
+            self.register(config.into_source(id));
+        }
+    }
+
+    pub fn add_source(
+        &self,
+        data_dir: &std::path::Path,
+        config: RemoteSourceConfig,
+    ) -> Result<RemoteSourceInfo, String> {
+        let store =
+            get_remote_source_store(data_dir).ok_or("remote source store not found".to_string())?;
+        let id = store.save(&config).map_err(|e| e.to_string())?;
+
+        let source = config.into_source(id);
+        let info = source.info();
+        self.register(source);
+
+        Ok(info)
+    }
+
+    pub fn remove_source(&self, data_dir: &std::path::Path, id: &str) -> Result<(), String> {
+        match self.get(id) {
+            Some(source) if !source.info().removable => {
+                return Err(format!("{id} is built in"));
+            }
+            Some(_) => {}
+            None => return Err(format!("no remote source {id}")),
+        }
+
+        let store =
+            get_remote_source_store(data_dir).ok_or("remote source store not found".to_string())?;
+        store.delete(id).map_err(|e| e.to_string())?;
+        self.unregister(id);
+
+        Ok(())
+    }
+}
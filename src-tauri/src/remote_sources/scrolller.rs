@@ -0,0 +1,106 @@
+use super::{
+    dimensions, extension, RemoteFilter, RemoteItem, RemoteItemSource, RemotePage, RemoteSource,
+    RemoteSourceInfo,
+};
+use crate::scrolller::types::{DiscoverParams, MediaFilter, MediaSource, SubredditPost};
+use crate::scrolller::ScrolllerClient;
+use crate::types::Dimensions;
+
+pub const SOURCE_ID: &str = "scrolller";
+
+const VIDEO_EXTENSIONS: [&str; 3] = ["mp4", "webm", "m4v"];
+
+pub struct ScrolllerSource {
+    client: ScrolllerClient,
+}
+
+impl ScrolllerSource {
+    pub fn new(client: ScrolllerClient) -> Self {
+        ScrolllerSource { client }
+    }
+}
+
+fn source_dimensions(source: &MediaSource) -> Option<Dimensions> {
+    dimensions(source.width?, source.height?)
+}
+
+/// Videos keep only their video sources, the rest are preview stills
+fn post_to_item(post: SubredditPost) -> Option<RemoteItem> {
+    let is_video =
+        |source: &&MediaSource| VIDEO_EXTENSIONS.contains(&extension(&source.url).as_str());
+
+    let mut sources = post
+        .media_sources
+        .iter()
+        .filter(is_video)
+        .collect::<Vec<_>>();
+    let kind = if sources.is_empty() {
+        sources = post.media_sources.iter().collect();
+        "image"
+    } else {
+        "video"
+    };
+    // smallest first, the feed only needs to fill a column
+    sources.sort_by_key(|source| source.width.unwrap_or(0) * source.height.unwrap_or(0));
+
+    let first = sources.first()?;
+    Some(RemoteItem {
+        name: post.id.to_string(),
+        kind: kind.to_string(),
+        item_type: "scrolller".to_string(),
+        extension: extension(&first.url),
+        src: sources
+            .iter()
+            .map(|source| RemoteItemSource {
+                url: source.url.clone(),
+                dimensions: source_dimensions(source),
+            })
+            .collect(),
+        additional: serde_json::to_value(&post).unwrap_or_default(),
+    })
+}
+
+#[async_trait::async_trait]
+impl RemoteSource for ScrolllerSource {
+    fn info(&self) -> RemoteSourceInfo {
+        RemoteSourceInfo {
+            id: SOURCE_ID.to_string(),
+            name: "Scrolller".to_string(),
+            provider: "scrolller",
+            removable: false,
+        }
+    }
+
+    async fn fetch_page(
+        &self,
+        iterator: Option<String>,
+        filter: &RemoteFilter,
+    ) -> Result<RemotePage, String> {
+        let params = DiscoverParams {
+            filter: match filter.kind.as_deref() {
+                Some("image") => Some(MediaFilter::Picture),
+                _ => Some(MediaFilter::Video),
+            },
+            is_nsfw: filter.is_nsfw,
+            limit: filter.limit,
+            ..Default::default()
+        };
+
+        let page = self
+            .client
+            .discover_filtered_subreddits(iterator, &params)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(RemotePage {
+            iterator: page.iterator,
+            items: page
+                .items
+                .into_iter()
+                .flat_map(|subreddit| subreddit.children.map(|children| children.items))
+                .flatten()
+                .filter_map(post_to_item)
+                .collect(),
+        })
+    }
+}
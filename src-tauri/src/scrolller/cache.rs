@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::library::now;
+
+/// How long a cached page is served before we ask Scrolller again
+pub const DEFAULT_TTL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Expired pages are still served offline until they reach this age, then
+/// they are deleted
+const MAX_STALE_SECS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// The query and variables the body answers, checked on read so a
+    /// file name collision can't return the wrong page
+    key: String,
+    stored_at: u64,
+    body: String,
+}
+
+/// GraphQL responses on disk, one file per query and variables
+#[derive(Clone)]
+pub struct ResponseCache {
+    dir: std::path::PathBuf,
+    ttl: std::time::Duration,
+}
+
+impl ResponseCache {
+    pub fn new(dir: std::path::PathBuf, ttl: std::time::Duration) -> Self {
+        let cache = ResponseCache { dir, ttl };
+        cache.prune();
+        cache
+    }
+
+    fn key(query: &str, variables: &serde_json::Value) -> String {
+        format!("{query}\n{variables}")
+    }
+
+    /// Named by a SHA-256 of the key, which stays the same across builds
+    fn entry_path(&self, key: &str) -> std::path::PathBuf {
+        let digest = Sha256::digest(key.as_bytes());
+        let name = digest
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+
+        self.dir.join(format!("{name}.json"))
+    }
+
+    fn read(&self, query: &str, variables: &serde_json::Value) -> Option<CacheEntry> {
+        let key = Self::key(query, variables);
+        let path = self.entry_path(&key);
+        let text = std::fs::read_to_string(&path).ok()?;
+        let entry = serde_json::from_str::<CacheEntry>(&text).ok()?;
+
+        if now().saturating_sub(entry.stored_at) >= MAX_STALE_SECS {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        (entry.key == key).then_some(entry)
+    }
+
+    /// A cached body that is still within the TTL
+    pub fn get_fresh(&self, query: &str, variables: &serde_json::Value) -> Option<String> {
+        self.read(query, variables)
+            .filter(|entry| now().saturating_sub(entry.stored_at) < self.ttl.as_secs())
+            .map(|entry| entry.body)
+    }
+
+    /// A cached body of any age, for when the network is down
+    pub fn get_stale(&self, query: &str, variables: &serde_json::Value) -> Option<String> {
+        self.read(query, variables).map(|entry| entry.body)
+    }
+
+    pub fn put(&self, query: &str, variables: &serde_json::Value, body: &str) {
+        let key = Self::key(query, variables);
+        let path = self.entry_path(&key);
+        let entry = CacheEntry {
+            key,
+            stored_at: now(),
+            body: body.to_string(),
+        };
+
+        // written aside and renamed over the entry, so a reader never sees
+        // half a file and a crash never leaves one behind
+        let temp_path = path.with_extension(format!("{:016x}.tmp", rand::random::<u64>()));
+        let res = std::fs::create_dir_all(&self.dir).and_then(|_| {
+            let text = serde_json::to_string(&entry)?;
+            std::fs::write(&temp_path, text)?;
+            std::fs::rename(&temp_path, &path)
+        });
+
+        if let Err(e) = res {
+            let _ = std::fs::remove_file(&temp_path);
+            println!("could not cache scrolller response: {e}");
+        }
+    }
+
+    /// Deletes entries too old to be served even offline, and files left
+    /// from older cache formats
+    fn prune(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            let is_live = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|text| serde_json::from_str::<CacheEntry>(&text).ok())
+                .is_some_and(|entry| now().saturating_sub(entry.stored_at) < MAX_STALE_SECS);
+            if !is_live {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        if self.dir.is_dir() {
+            std::fs::remove_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUERY: &str = "query Test { test }";
+
+    fn cache(dir: &std::path::Path) -> ResponseCache {
+        ResponseCache::new(dir.to_path_buf(), std::time::Duration::from_secs(60))
+    }
+
+    /// Writes the entry file for the variables, answering `key`, as if it
+    /// was stored `age` seconds ago
+    fn write_entry(cache: &ResponseCache, key: &str, variables: &serde_json::Value, age: u64) {
+        let entry = CacheEntry {
+            key: key.to_string(),
+            stored_at: now() - age,
+            body: "old body".to_string(),
+        };
+        std::fs::write(
+            cache.entry_path(&ResponseCache::key(QUERY, variables)),
+            serde_json::to_string(&entry).unwrap(),
+        )
+        .unwrap();
+    }
+
+    fn files(dir: &std::path::Path) -> Vec<String> {
+        let mut names = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn entries_are_fresh_until_the_ttl_and_stale_until_the_max_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path());
+        let variables = serde_json::json!({"limit": 10});
+        let key = ResponseCache::key(QUERY, &variables);
+
+        cache.put(QUERY, &variables, "body");
+        assert_eq!(cache.get_fresh(QUERY, &variables).as_deref(), Some("body"));
+        assert_eq!(
+            cache.get_fresh(QUERY, &serde_json::json!({"limit": 20})),
+            None
+        );
+
+        write_entry(&cache, &key, &variables, 60);
+        assert_eq!(cache.get_fresh(QUERY, &variables), None);
+        assert_eq!(
+            cache.get_stale(QUERY, &variables).as_deref(),
+            Some("old body")
+        );
+
+        write_entry(&cache, &key, &variables, MAX_STALE_SECS);
+        assert_eq!(cache.get_stale(QUERY, &variables), None);
+        assert!(files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn entries_for_another_key_are_not_served() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path());
+        let variables = serde_json::json!({"limit": 10});
+
+        write_entry(&cache, "some other query", &variables, 0);
+        assert_eq!(cache.get_fresh(QUERY, &variables), None);
+        assert_eq!(cache.get_stale(QUERY, &variables), None);
+
+        // a fresh write replaces it
+        cache.put(QUERY, &variables, "body");
+        assert_eq!(cache.get_fresh(QUERY, &variables).as_deref(), Some("body"));
+    }
+
+    #[test]
+    fn writes_leave_only_whole_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path());
+        let variables = serde_json::json!({});
+
+        cache.put(QUERY, &variables, "first");
+        cache.put(QUERY, &variables, "second");
+
+        let names = files(dir.path());
+        assert_eq!(names.len(), 1);
+        assert!(names[0].ends_with(".json"));
+        assert_eq!(
+            cache.get_fresh(QUERY, &variables).as_deref(),
+            Some("second")
+        );
+    }
+
+    #[test]
+    fn opening_prunes_old_and_unreadable_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let variables = serde_json::json!({});
+        let key = ResponseCache::key(QUERY, &variables);
+        write_entry(&cache(dir.path()), &key, &variables, MAX_STALE_SECS);
+        std::fs::write(dir.path().join("leftover.tmp"), "{\"key\"").unwrap();
+
+        cache(dir.path());
+        assert!(files(dir.path()).is_empty());
+    }
+}
@@ -1,16 +1,32 @@
 use reqwest::header::{
     HeaderMap, HeaderValue, ACCEPT, ACCEPT_LANGUAGE, CONTENT_TYPE, ORIGIN, USER_AGENT,
 };
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json::json;
 use tauri_plugin_http::reqwest;
 
-pub async fn fech_data(iterator: Option<String>, filter: &str, is_nsfw: bool) -> String {
-    // Create a client
-    let client = reqwest::Client::new();
+pub mod cache;
+pub mod types;
 
-    // Prepare the request body
-    let body = json!({
-        "query": "query DiscoverFilteredSubredditsQuery($filter: MediaFilter $limit: Int $iterator: String $hostsDown: [HostDisk] $includeFilters: [Int] $excludeFilters: [Int] $isNsfw: Boolean) {
+use types::{
+    Album, DiscoverFilteredSubredditsData, DiscoverParams, GetAlbumData, GetSubredditData,
+    GraphQlResponse, MediaFilter, Page, SearchSubredditsData, Subreddit, SubredditPost,
+};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.scrolller.com/api/v2/graphql";
+
+/// Overrides the GraphQL endpoint, for running the app against a mock server
+pub const BASE_URL_ENV: &str = "SCROLLLER_API_URL";
+
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+const MAX_ATTEMPTS: u32 = 4;
+const BASE_BACKOFF: std::time::Duration = std::time::Duration::from_millis(500);
+const MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(20);
+/// Minimum spacing between two requests leaving this client
+const MIN_REQUEST_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+const DISCOVER_FILTERED_SUBREDDITS_QUERY: &str = "query DiscoverFilteredSubredditsQuery($filter: MediaFilter $limit: Int $iterator: String $hostsDown: [HostDisk] $includeFilters: [Int] $excludeFilters: [Int] $isNsfw: Boolean) {
             discoverFilteredSubreddits(isNsfw: $isNsfw filter: $filter limit: $limit iterator: $iterator includeFilters: $includeFilters excludeFilters: $excludeFilters) {
                 iterator items {
                     __typename
@@ -75,45 +91,640 @@ pub async fn fech_data(iterator: Option<String>, filter: &str, is_nsfw: bool) ->
                     }
                 }
             }
-        }",
-        "variables": {
-            "limit": 30,
+        }";
+
+const SUBREDDIT_POST_FIELDS: &str = "fragment SubredditPostFields on SubredditPost {
+    __typename
+    id
+    url
+    title
+    subredditId
+    subredditTitle
+    subredditUrl
+    redditPath
+    isNsfw
+    albumUrl
+    hasAudio
+    fullLengthSource
+    gfycatSource
+    redgifsSource
+    ownerAvatar
+    username
+    displayName
+    isPaid
+    tags
+    isFavorite
+    mediaSources {
+        url
+        width
+        height
+        isOptimized
+    }
+    blurredMediaSources {
+        url
+        width
+        height
+        isOptimized
+    }
+}";
+
+const SUBREDDIT_CHILDREN_QUERY: &str = "query SubredditChildrenQuery($url: String! $iterator: String $filter: SubredditPostFilter $limit: Int $hostsDown: [HostDisk]) {
+    getSubreddit(url: $url) {
+        children(limit: $limit iterator: $iterator filter: $filter disabledHosts: $hostsDown) {
+            iterator items {
+                ...SubredditPostFields
+            }
+        }
+    }
+}";
+
+const SEARCH_SUBREDDITS_QUERY: &str =
+    "query SearchSubredditsQuery($query: String! $isNsfw: Boolean $limit: Int) {
+    searchSubreddits(query: $query isNsfw: $isNsfw limit: $limit) {
+        __typename
+        id
+        url
+        title
+        secondaryTitle
+        description
+        createdAt
+        isNsfw
+        subscribers
+        isComplete
+        itemCount
+        videoCount
+        pictureCount
+        albumCount
+        isPaid
+        username
+        tags
+        banner {
+            url
+            width
+            height
+            isOptimized
+        }
+        isFollowing
+    }
+}";
+
+const ALBUM_QUERY: &str = "query AlbumQuery($url: String! $iterator: String $limit: Int) {
+    getAlbum(url: $url) {
+        __typename
+        id
+        url
+        title
+        subredditTitle
+        subredditUrl
+        isNsfw
+        mediaSources {
+            url
+            width
+            height
+            isOptimized
+        }
+        children(limit: $limit iterator: $iterator) {
+            iterator items {
+                ...SubredditPostFields
+            }
+        }
+    }
+}";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum ScrolllerError {
+    /// The request never got a response
+    Network(String),
+    /// No response within `REQUEST_TIMEOUT`
+    Timeout,
+    /// Still rate limited after retrying, with the last `Retry-After` in seconds
+    RateLimited(Option<u64>),
+    /// Scrolller kept answering with a server error
+    Unavailable(u16),
+    /// Scrolller answered with a non-success status
+    Http(u16),
+    /// The GraphQL layer reported errors
+    Api(String),
+    /// The body wasn't what we expected
+    Decode(String),
+    /// Offline mode is on and the page isn't cached
+    Offline,
+}
+
+impl std::fmt::Display for ScrolllerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScrolllerError::Network(message) => write!(f, "network error: {message}"),
+            ScrolllerError::Timeout => write!(f, "scrolller timed out"),
+            ScrolllerError::RateLimited(_) => write!(f, "scrolller is rate limiting us"),
+            ScrolllerError::Unavailable(status) => {
+                write!(f, "scrolller is temporarily unavailable ({status})")
+            }
+            ScrolllerError::Http(status) => write!(f, "scrolller responded with {status}"),
+            ScrolllerError::Api(message) => write!(f, "scrolller error: {message}"),
+            ScrolllerError::Decode(message) => write!(f, "unexpected response: {message}"),
+            ScrolllerError::Offline => write!(f, "offline and the page isn't cached"),
+        }
+    }
+}
+
+impl std::error::Error for ScrolllerError {}
+
+impl ScrolllerError {
+    /// Errors worth retrying, and that the feed reports as temporary
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ScrolllerError::Network(_)
+                | ScrolllerError::Timeout
+                | ScrolllerError::RateLimited(_)
+                | ScrolllerError::Unavailable(_)
+        )
+    }
+}
+
+impl From<reqwest::Error> for ScrolllerError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            ScrolllerError::Timeout
+        } else if e.is_decode() {
+            ScrolllerError::Decode(e.to_string())
+        } else {
+            ScrolllerError::Network(e.to_string())
+        }
+    }
+}
+
+/// Exponential backoff with jitter, unless the server told us how long to wait
+fn backoff_delay(attempt: u32, retry_after: Option<u64>) -> std::time::Duration {
+    if let Some(retry_after) = retry_after {
+        return std::time::Duration::from_secs(retry_after).min(MAX_BACKOFF);
+    }
+
+    let backoff = BASE_BACKOFF
+        .saturating_mul(2_u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF);
+    let jitter = rand::random::<f64>() * backoff.as_secs_f64() / 2.0;
+
+    backoff + std::time::Duration::from_secs_f64(jitter)
+}
+
+/// Shares one connection pool and rate limit across every Scrolller request
+#[derive(Clone)]
+pub struct ScrolllerClient {
+    client: reqwest::Client,
+    base_url: String,
+    next_request_at: std::sync::Arc<tokio::sync::Mutex<tokio::time::Instant>>,
+    cache: Option<cache::ResponseCache>,
+    offline: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl Default for ScrolllerClient {
+    fn default() -> Self {
+        ScrolllerClient::new(std::env::var(BASE_URL_ENV).unwrap_or(DEFAULT_BASE_URL.to_string()))
+    }
+}
+
+impl ScrolllerClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        // Prepare headers
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+        headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en-US,en;q=0.9"));
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/plain;charset=UTF-8"),
+        );
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_static("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Safari/605.1.15"),
+        );
+        headers.insert(
+            ORIGIN,
+            HeaderValue::from_static("https://www.scrolller.com"),
+        );
+
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        ScrolllerClient {
+            client,
+            base_url: base_url.into(),
+            next_request_at: std::sync::Arc::new(tokio::sync::Mutex::new(
+                tokio::time::Instant::now(),
+            )),
+            cache: None,
+            offline: Default::default(),
+        }
+    }
+
+    pub fn with_cache(mut self, dir: std::path::PathBuf, ttl: std::time::Duration) -> Self {
+        self.cache = Some(cache::ResponseCache::new(dir, ttl));
+        self
+    }
+
+    pub fn clear_cache(&self) -> Result<(), String> {
+        match &self.cache {
+            Some(cache) => cache.clear(),
+            None => Ok(()),
+        }
+    }
+
+    /// In offline mode only cached pages are served, however old
+    pub fn set_offline(&self, offline: bool) {
+        self.offline
+            .store(offline, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Waits for this client's next free request slot
+    async fn wait_for_slot(&self) {
+        let mut next_request_at = self.next_request_at.lock().await;
+        tokio::time::sleep_until(*next_request_at).await;
+        *next_request_at = tokio::time::Instant::now() + MIN_REQUEST_INTERVAL;
+    }
+
+    /// One POST, with transient failures mapped to their error variants
+    async fn send(&self, body: &serde_json::Value) -> Result<String, ScrolllerError> {
+        self.wait_for_slot().await;
+
+        let response = self.client.post(&self.base_url).json(body).send().await?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok());
+            return Err(ScrolllerError::RateLimited(retry_after));
+        }
+        if status.is_server_error() {
+            return Err(ScrolllerError::Unavailable(status.as_u16()));
+        }
+        if !status.is_success() {
+            return Err(ScrolllerError::Http(status.as_u16()));
+        }
+
+        Ok(response.text().await?)
+    }
+
+    pub async fn discover_filtered_subreddits(
+        &self,
+        iterator: Option<String>,
+        params: &DiscoverParams,
+    ) -> Result<Page<Subreddit>, ScrolllerError> {
+        let variables = json!({
+            "limit": params.limit.unwrap_or(30).clamp(1, 100),
+            "iterator": iterator,
+            "filter": params.filter,
+            "isNsfw": params.is_nsfw,
+            "hostsDown": if params.hosts_down.is_empty() {
+                serde_json::Value::Null
+            } else {
+                json!(params.hosts_down)
+            },
+            "includeFilters": params.include_filters,
+            "excludeFilters": params.exclude_filters
+        });
+
+        let data: DiscoverFilteredSubredditsData = self
+            .query(DISCOVER_FILTERED_SUBREDDITS_QUERY, variables)
+            .await?;
+
+        Ok(data.discover_filtered_subreddits)
+    }
+
+    /// Posts of one subreddit, `url` is its scrolller path like `/r/aww`
+    pub async fn subreddit_posts(
+        &self,
+        url: &str,
+        iterator: Option<String>,
+        filter: Option<MediaFilter>,
+        limit: Option<u32>,
+    ) -> Result<Page<SubredditPost>, ScrolllerError> {
+        let variables = json!({
+            "url": url,
             "iterator": iterator,
             "filter": filter,
-            "hostsDown": null,
-            "includeFilters": [],
-            "excludeFilters": []
-        },
-        "authorization": null
-    });
-
-    println!("body: {:?}", body);
-
-    // Prepare headers
-    let mut headers = HeaderMap::new();
-    headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
-    headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en-US,en;q=0.9"));
-    headers.insert(
-        CONTENT_TYPE,
-        HeaderValue::from_static("text/plain;charset=UTF-8"),
-    );
-    headers.insert(
-        USER_AGENT,
-        HeaderValue::from_static("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Safari/605.1.15"),
-    );
-    headers.insert(
-        ORIGIN,
-        HeaderValue::from_static("https://www.scrolller.com"),
-    );
-
-    // Make the POST request
-    let response = client
-        .post("https://api.scrolller.com/api/v2/graphql")
-        .headers(headers)
-        .json(&body)
-        .send()
-        .await;
-    let text = response.unwrap().text().await.unwrap();
-
-    text
+            "limit": limit.unwrap_or(50),
+            "hostsDown": null
+        });
+
+        let data: GetSubredditData = self
+            .query(
+                &format!("{SUBREDDIT_CHILDREN_QUERY}\n{SUBREDDIT_POST_FIELDS}"),
+                variables,
+            )
+            .await?;
+
+        data.get_subreddit
+            .map(|subreddit| subreddit.children)
+            .ok_or(ScrolllerError::Api(format!("subreddit {url} not found")))
+    }
+
+    pub async fn search_subreddits(
+        &self,
+        query: &str,
+        is_nsfw: Option<bool>,
+        limit: Option<u32>,
+    ) -> Result<Vec<Subreddit>, ScrolllerError> {
+        let variables = json!({
+            "query": query,
+            "isNsfw": is_nsfw,
+            "limit": limit.unwrap_or(20)
+        });
+
+        let data: SearchSubredditsData = self.query(SEARCH_SUBREDDITS_QUERY, variables).await?;
+
+        Ok(data.search_subreddits)
+    }
+
+    pub async fn album(
+        &self,
+        url: &str,
+        iterator: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<Album, ScrolllerError> {
+        let variables = json!({
+            "url": url,
+            "iterator": iterator,
+            "limit": limit.unwrap_or(50)
+        });
+
+        let data: GetAlbumData = self
+            .query(
+                &format!("{ALBUM_QUERY}\n{SUBREDDIT_POST_FIELDS}"),
+                variables,
+            )
+            .await?;
+
+        data.get_album
+            .ok_or(ScrolllerError::Api(format!("album {url} not found")))
+    }
+
+    /// POSTs the body, retrying transient failures with backoff
+    async fn send_with_retries(&self, body: &serde_json::Value) -> Result<String, ScrolllerError> {
+        let mut attempt = 0;
+        loop {
+            match self.send(body).await {
+                Ok(text) => return Ok(text),
+                Err(e) if e.is_transient() && attempt + 1 < MAX_ATTEMPTS => {
+                    let retry_after = match e {
+                        ScrolllerError::RateLimited(retry_after) => retry_after,
+                        _ => None,
+                    };
+                    let delay = backoff_delay(attempt, retry_after);
+                    println!("scrolller request failed ({e}), retrying in {:?}", delay);
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn query<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<T, ScrolllerError> {
+        let offline = self.is_offline();
+        let cached = self.cache.as_ref().and_then(|cache| {
+            if offline {
+                cache.get_stale(query, &variables)
+            } else {
+                cache.get_fresh(query, &variables)
+            }
+        });
+
+        let (text, is_fresh) = match cached {
+            Some(text) => (text, false),
+            None if offline => return Err(ScrolllerError::Offline),
+            None => {
+                // Prepare the request body
+                let body = json!({
+                    "query": query,
+                    "variables": variables,
+                    "authorization": null
+                });
+
+                println!("body: {:?}", body);
+
+                match self.send_with_retries(&body).await {
+                    Ok(text) => (text, true),
+                    // the network is down, an old page beats no page
+                    Err(e) if e.is_transient() => match self
+                        .cache
+                        .as_ref()
+                        .and_then(|cache| cache.get_stale(query, &variables))
+                    {
+                        Some(text) => {
+                            println!("serving stale scrolller page: {e}");
+                            (text, false)
+                        }
+                        None => return Err(e),
+                    },
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+
+        let response = serde_json::from_str::<GraphQlResponse<T>>(&text)
+            .map_err(|e| ScrolllerError::Decode(e.to_string()))?;
+
+        match (response.data, response.errors) {
+            (Some(data), _) => {
+                if is_fresh {
+                    if let Some(cache) = &self.cache {
+                        cache.put(query, &variables, &text);
+                    }
+                }
+                Ok(data)
+            }
+            (None, Some(errors)) => Err(ScrolllerError::Api(
+                errors
+                    .into_iter()
+                    .map(|error| error.message)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )),
+            (None, None) => Err(ScrolllerError::Decode("response has no data".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn ok(body: &str) -> String {
+        response("200 OK", "Content-Type: application/json\r\n", body)
+    }
+
+    /// Reads one request off the stream, up to the end of its body
+    fn read_request(stream: &mut std::net::TcpStream) {
+        let mut reader = std::io::BufReader::new(stream);
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                break;
+            }
+            if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                content_length = length.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+    }
+
+    /// Answers one request per canned response, in order. Returns the client
+    /// pointed at it and the number of requests answered so far.
+    fn mock_server(responses: Vec<String>) -> (ScrolllerClient, Arc<AtomicUsize>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+        let answered = Arc::new(AtomicUsize::new(0));
+
+        let counter = answered.clone();
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                read_request(&mut stream);
+                stream.write_all(response.as_bytes()).unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        (ScrolllerClient::new(url), answered)
+    }
+
+    fn search(client: &ScrolllerClient) -> Result<Vec<Subreddit>, ScrolllerError> {
+        tauri::async_runtime::block_on(client.search_subreddits("cats", None, None))
+    }
+
+    fn send(client: &ScrolllerClient) -> Result<String, ScrolllerError> {
+        tauri::async_runtime::block_on(client.send(&json!({})))
+    }
+
+    #[test]
+    fn statuses_map_to_error_kinds() {
+        let (client, _) = mock_server(vec![
+            response("429 Too Many Requests", "Retry-After: 7\r\n", ""),
+            response("429 Too Many Requests", "", ""),
+            response("503 Service Unavailable", "", ""),
+            response("404 Not Found", "", ""),
+        ]);
+
+        assert!(matches!(
+            send(&client),
+            Err(ScrolllerError::RateLimited(Some(7)))
+        ));
+        assert!(matches!(
+            send(&client),
+            Err(ScrolllerError::RateLimited(None))
+        ));
+        assert!(matches!(
+            send(&client),
+            Err(ScrolllerError::Unavailable(503))
+        ));
+        assert!(matches!(send(&client), Err(ScrolllerError::Http(404))));
+    }
+
+    #[test]
+    fn rate_limits_are_retried() {
+        let (client, answered) = mock_server(vec![
+            response("429 Too Many Requests", "Retry-After: 0\r\n", ""),
+            ok(r#"{"data": {"searchSubreddits": []}}"#),
+        ]);
+
+        assert!(search(&client).unwrap().is_empty());
+        assert_eq!(answered.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let (client, answered) = mock_server(vec![
+            response("400 Bad Request", "", ""),
+            ok(r#"{"data": {"searchSubreddits": []}}"#),
+        ]);
+
+        assert!(matches!(search(&client), Err(ScrolllerError::Http(400))));
+        assert_eq!(answered.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn graphql_errors_and_bad_bodies_are_reported() {
+        let (client, _) = mock_server(vec![
+            ok(
+                r#"{"data": null, "errors": [{"message": "bad filter"}, {"message": "bad limit"}]}"#,
+            ),
+            ok("<html>not json</html>"),
+            ok(r#"{"data": null}"#),
+            ok(r#"{"data": {"getSubreddit": null}}"#),
+        ]);
+
+        match search(&client) {
+            Err(ScrolllerError::Api(message)) => assert_eq!(message, "bad filter, bad limit"),
+            other => panic!("expected an api error, got {other:?}"),
+        }
+        assert!(matches!(search(&client), Err(ScrolllerError::Decode(_))));
+        assert!(matches!(search(&client), Err(ScrolllerError::Decode(_))));
+        let posts =
+            tauri::async_runtime::block_on(client.subreddit_posts("/r/gone", None, None, None));
+        assert!(matches!(posts, Err(ScrolllerError::Api(_))));
+    }
+
+    #[test]
+    fn unreachable_servers_are_network_errors() {
+        // bind and drop, so nothing listens on the port
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = ScrolllerClient::new(format!("http://{address}/graphql"));
+
+        let error = send(&client).unwrap_err();
+        assert!(matches!(error, ScrolllerError::Network(_)));
+        assert!(error.is_transient());
+    }
+
+    #[test]
+    fn offline_without_a_cached_page_fails_without_a_request() {
+        let (client, answered) = mock_server(vec![ok(r#"{"data": {"searchSubreddits": []}}"#)]);
+        client.set_offline(true);
+
+        assert!(matches!(search(&client), Err(ScrolllerError::Offline)));
+        assert_eq!(answered.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn errors_serialize_with_their_kind() {
+        assert_eq!(
+            serde_json::to_value(ScrolllerError::RateLimited(Some(3))).unwrap(),
+            json!({"kind": "rate_limited", "message": 3})
+        );
+        assert_eq!(
+            serde_json::to_value(ScrolllerError::Offline).unwrap(),
+            json!({"kind": "offline"})
+        );
+    }
 }
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaSource {
+    pub url: String,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub is_optimized: Option<bool>,
+}
+
+/// The iterator envelope every paginated Scrolller field is wrapped in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub iterator: Option<String>,
+    #[serde(default = "Vec::new")]
+    pub items: Vec<T>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubredditPost {
+    #[serde(rename = "__typename")]
+    pub typename: Option<String>,
+    pub id: i64,
+    pub url: String,
+    pub title: Option<String>,
+    pub subreddit_id: Option<i64>,
+    pub subreddit_title: Option<String>,
+    pub subreddit_url: Option<String>,
+    pub reddit_path: Option<String>,
+    pub is_nsfw: Option<bool>,
+    pub album_url: Option<String>,
+    pub has_audio: Option<bool>,
+    pub full_length_source: Option<String>,
+    pub gfycat_source: Option<String>,
+    pub redgifs_source: Option<String>,
+    pub owner_avatar: Option<String>,
+    pub username: Option<String>,
+    pub display_name: Option<String>,
+    pub is_paid: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    pub is_favorite: Option<bool>,
+    #[serde(default = "Vec::new")]
+    pub media_sources: Vec<MediaSource>,
+    pub blurred_media_sources: Option<Vec<MediaSource>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subreddit {
+    #[serde(rename = "__typename")]
+    pub typename: Option<String>,
+    pub id: i64,
+    pub url: String,
+    pub title: Option<String>,
+    pub secondary_title: Option<String>,
+    pub description: Option<String>,
+    pub created_at: Option<String>,
+    pub is_nsfw: Option<bool>,
+    pub subscribers: Option<i64>,
+    pub is_complete: Option<bool>,
+    pub item_count: Option<i64>,
+    pub video_count: Option<i64>,
+    pub picture_count: Option<i64>,
+    pub album_count: Option<i64>,
+    pub is_paid: Option<bool>,
+    pub username: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub banner: Option<MediaSource>,
+    pub is_following: Option<bool>,
+    pub children: Option<Page<SubredditPost>>,
+}
+
+/// Kinds of posts a subreddit listing can be narrowed to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MediaFilter {
+    Video,
+    Picture,
+    Album,
+}
+
+/// Server-side constraints for the discover listing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiscoverParams {
+    pub filter: Option<MediaFilter>,
+    /// `None` mixes safe and nsfw subreddits
+    pub is_nsfw: Option<bool>,
+    /// Scrolller tag ids a subreddit must have
+    pub include_filters: Vec<i64>,
+    /// Scrolller tag ids a subreddit must not have
+    pub exclude_filters: Vec<i64>,
+    /// Media hosts to leave out of the previews, like `REDGIFS`
+    pub hosts_down: Vec<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Album {
+    #[serde(rename = "__typename")]
+    pub typename: Option<String>,
+    pub id: i64,
+    pub url: String,
+    pub title: Option<String>,
+    pub subreddit_title: Option<String>,
+    pub subreddit_url: Option<String>,
+    pub is_nsfw: Option<bool>,
+    pub media_sources: Option<Vec<MediaSource>>,
+    pub children: Option<Page<SubredditPost>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoverFilteredSubredditsData {
+    pub discover_filtered_subreddits: Page<Subreddit>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubredditChildren {
+    pub children: Page<SubredditPost>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetSubredditData {
+    pub get_subreddit: Option<SubredditChildren>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSubredditsData {
+    #[serde(default = "Vec::new")]
+    pub search_subreddits: Vec<Subreddit>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAlbumData {
+    pub get_album: Option<Album>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphQlError {
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphQlResponse<T> {
+    pub data: Option<T>,
+    pub errors: Option<Vec<GraphQlError>>,
+}
@@ -0,0 +1,480 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tantivy::collector::{Count, DocSetCollector, TopDocs};
+use tantivy::query::{AllQuery, BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+use crate::types::LocalFile;
+
+const WRITER_HEAP_SIZE: usize = 20_000_000;
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 500;
+/// Shorter terms match too much when a typo is allowed
+const MIN_FUZZY_TERM_LENGTH: usize = 4;
+
+#[derive(Clone, Copy)]
+struct Fields {
+    id: Field,
+    name: Field,
+    folder: Field,
+    title: Field,
+    subreddit: Field,
+    note: Field,
+}
+
+impl Fields {
+    /// Searchable fields with their boost, a hit in the name outranks one in
+    /// the folder path
+    fn weighted(&self) -> [(Field, f32); 5] {
+        [
+            (self.name, 3.0),
+            (self.title, 2.0),
+            (self.note, 2.0),
+            (self.subreddit, 1.5),
+            (self.folder, 1.0),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+pub struct SearchHit {
+    pub score: f32,
+    pub file: LocalFile,
+}
+
+#[derive(Serialize)]
+pub struct SearchResults {
+    /// Number of matching items, not just the ones on this page
+    pub total: usize,
+    pub offset: usize,
+    pub hits: Vec<SearchHit>,
+}
+
+/// Full-text index over the media dir, kept next to the other stores in the
+/// app data dir. The media dir stays the source of truth, the index can be
+/// deleted and rebuilt at any time.
+pub struct SearchIndex {
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+    /// Media ids currently in the index
+    indexed: Mutex<HashSet<String>>,
+}
+
+fn schema() -> (Schema, Fields) {
+    let mut builder = Schema::builder();
+    let fields = Fields {
+        id: builder.add_text_field("id", STRING | STORED),
+        name: builder.add_text_field("name", TEXT),
+        folder: builder.add_text_field("folder", TEXT),
+        title: builder.add_text_field("title", TEXT),
+        subreddit: builder.add_text_field("subreddit", TEXT),
+        note: builder.add_text_field("note", TEXT),
+    };
+
+    (builder.build(), fields)
+}
+
+fn open_index(index_dir: &std::path::Path, schema: Schema) -> Result<Index, String> {
+    std::fs::create_dir_all(index_dir).map_err(|e| e.to_string())?;
+    let directory =
+        tantivy::directory::MmapDirectory::open(index_dir).map_err(|e| e.to_string())?;
+
+    Index::open_or_create(directory, schema).map_err(|e| e.to_string())
+}
+
+/// Splits the way the default tokenizer does, so query terms line up with
+/// indexed terms
+fn tokenize(input: &str) -> Vec<String> {
+    input
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+impl SearchIndex {
+    /// Opens the index in `index_dir`. An index written with an older schema
+    /// is thrown away and will be refilled by the next sync.
+    pub fn open(index_dir: &std::path::Path) -> Result<Self, String> {
+        let (schema, fields) = schema();
+        let index = match open_index(index_dir, schema.clone()) {
+            Ok(index) => index,
+            Err(e) => {
+                println!("recreating search index: {e}");
+                std::fs::remove_dir_all(index_dir).map_err(|e| e.to_string())?;
+                open_index(index_dir, schema)?
+            }
+        };
+
+        Self::from_index(index, fields)
+    }
+
+    fn from_index(index: Index, fields: Fields) -> Result<Self, String> {
+        let writer: IndexWriter = index.writer(WRITER_HEAP_SIZE).map_err(|e| e.to_string())?;
+        let reader: IndexReader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(|e| e.to_string())?;
+
+        let search_index = SearchIndex {
+            reader,
+            writer: Mutex::new(writer),
+            fields,
+            indexed: Mutex::new(HashSet::new()),
+        };
+        let indexed = search_index.indexed_ids()?;
+        *search_index.indexed.lock().map_err(|e| e.to_string())? = indexed;
+
+        Ok(search_index)
+    }
+
+    fn indexed_ids(&self) -> Result<HashSet<String>, String> {
+        let searcher = self.reader.searcher();
+        let addresses = searcher
+            .search(&AllQuery, &DocSetCollector)
+            .map_err(|e| e.to_string())?;
+
+        Ok(addresses
+            .into_iter()
+            .filter_map(|address| searcher.doc::<TantivyDocument>(address).ok())
+            .filter_map(|doc| {
+                doc.get_first(self.fields.id)
+                    .and_then(|id| id.as_str())
+                    .map(|id| id.to_string())
+            })
+            .collect())
+    }
+
+    fn document(
+        &self,
+        media_id: &str,
+        record: &crate::library::MediaRecord,
+        source: Option<&crate::downloads::SourceMetadata>,
+    ) -> TantivyDocument {
+        // the import root and the folders below it down to the file
+        let folders = record
+            .import_path
+            .as_deref()
+            .and_then(|import_path| import_path.rsplit_once('/'))
+            .map(|(folders, _)| folders);
+        let folder = [record.origin.as_deref(), folders]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join("/");
+
+        doc!(
+            self.fields.id => media_id,
+            self.fields.name => media_id,
+            self.fields.folder => folder,
+            self.fields.title => source.and_then(|s| s.title.clone()).unwrap_or_default(),
+            self.fields.subreddit => source.and_then(|s| s.subreddit.clone()).unwrap_or_default(),
+            self.fields.note => record.note.clone().unwrap_or_default(),
+        )
+    }
+
+    fn commit(&self, writer: &mut IndexWriter) -> Result<(), String> {
+        writer.commit().map_err(|e| e.to_string())?;
+        self.reader.reload().map_err(|e| e.to_string())
+    }
+
+    /// Reindexes one item after its metadata changed, or drops it when the
+    /// file is gone
+    pub fn index_media(
+        &self,
+        data_dir: &std::path::Path,
+        media_dir: &std::path::Path,
+        media_id: &str,
+    ) -> Result<(), String> {
+        let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
+        let mut indexed = self.indexed.lock().map_err(|e| e.to_string())?;
+
+        writer.delete_term(Term::from_field_text(self.fields.id, media_id));
+        if crate::local_files::resolve_media_id(media_dir, media_id).is_some() {
+            let record = crate::library::get_record(data_dir, media_id);
+            let source = crate::downloads::source_for_media(data_dir, media_id);
+            writer
+                .add_document(self.document(media_id, &record, source.as_ref()))
+                .map_err(|e| e.to_string())?;
+            indexed.insert(media_id.to_string());
+        } else {
+            indexed.remove(media_id);
+        }
+
+        self.commit(&mut writer)
+    }
+
+    /// Adds files that appeared in the media dir and drops the ones that
+    /// disappeared. Cheap when nothing changed.
+    pub fn sync(
+        &self,
+        data_dir: &std::path::Path,
+        media_dir: &std::path::Path,
+    ) -> Result<(), String> {
+        let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
+        let mut indexed = self.indexed.lock().map_err(|e| e.to_string())?;
+
+        let current = crate::local_files::media_ids_in_dir(media_dir)
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        let added = current.difference(&indexed).cloned().collect::<Vec<_>>();
+        let removed = indexed.difference(&current).cloned().collect::<Vec<_>>();
+        if added.is_empty() && removed.is_empty() {
+            return Ok(());
+        }
+
+        for media_id in &removed {
+            writer.delete_term(Term::from_field_text(self.fields.id, media_id));
+            indexed.remove(media_id);
+        }
+        // read once, reopening the stores per item is quadratic
+        let records = crate::library::all_records(data_dir);
+        let sources = crate::downloads::all_sources(data_dir);
+        let default_record = crate::library::MediaRecord::default();
+        for media_id in added {
+            let record = records.get(&media_id).unwrap_or(&default_record);
+            writer
+                .add_document(self.document(&media_id, record, sources.get(&media_id)))
+                .map_err(|e| e.to_string())?;
+            indexed.insert(media_id);
+        }
+
+        self.commit(&mut writer)
+    }
+
+    /// Drops everything and indexes the media dir from scratch
+    pub fn rebuild(
+        &self,
+        data_dir: &std::path::Path,
+        media_dir: &std::path::Path,
+    ) -> Result<usize, String> {
+        {
+            let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
+            writer.delete_all_documents().map_err(|e| e.to_string())?;
+            self.commit(&mut writer)?;
+            self.indexed.lock().map_err(|e| e.to_string())?.clear();
+        }
+
+        self.sync(data_dir, media_dir)?;
+        Ok(self.indexed.lock().map_err(|e| e.to_string())?.len())
+    }
+
+    /// Every term must match in some field. Terms match exactly, as a prefix
+    /// of a longer word, or with one typo; exact matches rank highest.
+    fn build_query(&self, input: &str) -> Option<Box<dyn Query>> {
+        let terms = tokenize(input);
+        if terms.is_empty() {
+            return None;
+        }
+
+        let clauses = terms
+            .iter()
+            .map(|text| {
+                let mut alternatives: Vec<(Occur, Box<dyn Query>)> = vec![];
+                for (field, boost) in self.fields.weighted() {
+                    let term = Term::from_field_text(field, text);
+                    alternatives.push((
+                        Occur::Should,
+                        Box::new(BoostQuery::new(
+                            Box::new(TermQuery::new(term.clone(), IndexRecordOption::WithFreqs)),
+                            boost * 2.0,
+                        )),
+                    ));
+                    alternatives.push((
+                        Occur::Should,
+                        Box::new(BoostQuery::new(
+                            Box::new(FuzzyTermQuery::new_prefix(term.clone(), 0, true)),
+                            boost,
+                        )),
+                    ));
+                    if text.chars().count() >= MIN_FUZZY_TERM_LENGTH {
+                        alternatives.push((
+                            Occur::Should,
+                            Box::new(BoostQuery::new(
+                                Box::new(FuzzyTermQuery::new(term, 1, true)),
+                                boost * 0.5,
+                            )),
+                        ));
+                    }
+                }
+
+                (
+                    Occur::Must,
+                    Box::new(BooleanQuery::new(alternatives)) as Box<dyn Query>,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        Some(Box::new(BooleanQuery::new(clauses)))
+    }
+
+    pub fn search(
+        &self,
+        input: &str,
+        data_dir: &std::path::Path,
+        media_dir: &std::path::Path,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<SearchResults, String> {
+        let offset = offset.unwrap_or(0);
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+        let Some(query) = self.build_query(input) else {
+            return Ok(SearchResults {
+                total: 0,
+                offset,
+                hits: vec![],
+            });
+        };
+
+        let searcher = self.reader.searcher();
+        let (top_docs, total) = searcher
+            .search(
+                &query,
+                &(TopDocs::with_limit(limit).and_offset(offset), Count),
+            )
+            .map_err(|e| e.to_string())?;
+
+        let media_dir = media_dir.to_path_buf();
+        let mut hits = top_docs
+            .into_iter()
+            .filter_map(|(score, address)| {
+                let doc = searcher.doc::<TantivyDocument>(address).ok()?;
+                let media_id = doc.get_first(self.fields.id)?.as_str()?;
+                let path = crate::local_files::resolve_media_id(&media_dir, media_id)?;
+                let file = crate::local_files::load_local_file(path.to_str()?, &media_dir)?;
+
+                Some(SearchHit { score, file })
+            })
+            .collect::<Vec<_>>();
+
+        crate::library::annotate(data_dir, hits.iter_mut().map(|hit| &mut hit.file));
+
+        Ok(SearchResults {
+            total,
+            offset,
+            hits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Library {
+        data: tempfile::TempDir,
+        media: tempfile::TempDir,
+        index: SearchIndex,
+    }
+
+    impl Library {
+        fn new(media_ids: &[&str]) -> Self {
+            let (schema, fields) = schema();
+            let library = Library {
+                data: tempfile::tempdir().unwrap(),
+                media: tempfile::tempdir().unwrap(),
+                index: SearchIndex::from_index(Index::create_in_ram(schema), fields).unwrap(),
+            };
+            for media_id in media_ids {
+                library.add(media_id);
+            }
+            library.sync();
+            library
+        }
+
+        fn add(&self, media_id: &str) {
+            std::fs::write(self.media.path().join(media_id), b"not really media").unwrap();
+        }
+
+        fn sync(&self) {
+            self.index
+                .sync(self.data.path(), self.media.path())
+                .unwrap();
+        }
+
+        /// Matching ids, best first
+        fn search(&self, input: &str) -> Vec<String> {
+            self.index
+                .search(input, self.data.path(), self.media.path(), None, None)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|hit| hit.file.id)
+                .collect()
+        }
+    }
+
+    fn sorted(mut media_ids: Vec<String>) -> Vec<String> {
+        media_ids.sort();
+        media_ids
+    }
+
+    #[test]
+    fn exact_matches_rank_above_prefixes_and_typos() {
+        let library = Library::new(&["sunsat.jpg", "sunsets.jpg", "sunset.jpg", "beach.jpg"]);
+
+        assert_eq!(
+            library.search("sunset"),
+            ["sunset.jpg", "sunsets.jpg", "sunsat.jpg"]
+        );
+    }
+
+    #[test]
+    fn terms_match_the_start_of_longer_words() {
+        let library = Library::new(&["beach.jpg", "beaches.png", "abeam.jpg"]);
+
+        assert_eq!(sorted(library.search("bea")), ["beach.jpg", "beaches.png"]);
+        assert_eq!(
+            sorted(library.search("BEACH")),
+            sorted(library.search("beach"))
+        );
+    }
+
+    #[test]
+    fn only_longer_terms_forgive_a_typo() {
+        let library = Library::new(&["cat.jpg", "cot.jpg", "kitten.jpg"]);
+
+        assert_eq!(library.search("cat"), ["cat.jpg"]);
+        assert_eq!(library.search("kiten"), ["kitten.jpg"]);
+        assert_eq!(library.search("kiteen"), ["kitten.jpg"]);
+        assert!(library.search("kxtxen").is_empty());
+    }
+
+    #[test]
+    fn every_term_has_to_match() {
+        let library = Library::new(&["cat-beach.jpg", "cat.jpg", "beach.jpg"]);
+
+        assert_eq!(library.search("beach cat"), ["cat-beach.jpg"]);
+        assert!(library.search("").is_empty());
+        assert!(library.search(" - ").is_empty());
+    }
+
+    #[test]
+    fn sync_picks_up_added_and_removed_files() {
+        let library = Library::new(&["first-cat.jpg"]);
+        library.add("second-cat.jpg");
+        assert_eq!(library.search("cat"), ["first-cat.jpg"]);
+
+        library.sync();
+        assert_eq!(
+            sorted(library.search("cat")),
+            ["first-cat.jpg", "second-cat.jpg"]
+        );
+
+        std::fs::remove_file(library.media.path().join("first-cat.jpg")).unwrap();
+        library.sync();
+        let results = library
+            .index
+            .search("cat", library.data.path(), library.media.path(), None, None)
+            .unwrap();
+        assert_eq!(results.total, 1);
+        assert_eq!(results.hits[0].file.id, "second-cat.jpg");
+        assert_eq!(library.index.indexed.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,833 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Serializes read-modify-write cycles on the tag stores, commands and the
+/// LAN server can edit tags at the same time
+static TAG_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Tag {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkey: Option<String>,
+    /// Media with this tag also match queries for the parent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Other names that resolve to this tag
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+impl Tag {
+    fn named(name: &str) -> Self {
+        Tag {
+            name: name.to_string(),
+            color: None,
+            hotkey: None,
+            parent: None,
+            aliases: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagImportSummary {
+    pub tags: usize,
+    pub assignments: usize,
+}
+
+fn open_store(data_dir: &std::path::Path, name: &str) -> Option<jfs::Store> {
+    match jfs::Store::new_with_cfg(
+        data_dir.join(name),
+        jfs::Config {
+            single: true,
+            indent: 2,
+            pretty: true,
+        },
+    ) {
+        Ok(store) => Some(store),
+        Err(_) => None,
+    }
+}
+
+/// Assignments, keyed by media id
+pub fn get_tag_store(data_dir: &std::path::Path) -> Option<jfs::Store> {
+    open_store(data_dir, "tags")
+}
+
+/// Tag definitions, keyed by tag name
+pub fn get_tag_definition_store(data_dir: &std::path::Path) -> Option<jfs::Store> {
+    open_store(data_dir, "tag_definitions")
+}
+
+/// Present while both stores' temp files are complete and being renamed
+/// into place
+const REPLACE_MARKER: &str = "tags.replacing";
+
+fn store_path(data_dir: &std::path::Path, name: &str) -> std::path::PathBuf {
+    data_dir.join(name).with_extension("json")
+}
+
+fn temp_path(data_dir: &std::path::Path, name: &str) -> std::path::PathBuf {
+    store_path(data_dir, name).with_extension("json.tmp")
+}
+
+/// Replaces the assignment and definition stores together, so a rename or
+/// merge never leaves one rewritten without the other.
+///
+/// Both temp files are written before either store is touched, then the
+/// marker records that they are complete and the renames follow. If the app
+/// dies before the marker exists, the stores are untouched and
+/// [`recover_stores`] throws the temp files away. If it dies after, the temp
+/// files are whole and [`recover_stores`] finishes the renames.
+fn replace_stores(
+    data_dir: &std::path::Path,
+    assignments: &BTreeMap<String, Vec<String>>,
+    definitions: &BTreeMap<String, Tag>,
+) -> Result<(), String> {
+    let assignments = serde_json::to_string_pretty(assignments).map_err(|e| e.to_string())?;
+    let definitions = serde_json::to_string_pretty(definitions).map_err(|e| e.to_string())?;
+
+    for (name, text) in [("tags", assignments), ("tag_definitions", definitions)] {
+        let mut file =
+            std::fs::File::create(temp_path(data_dir, name)).map_err(|e| e.to_string())?;
+        file.write_all(text.as_bytes())
+            .and_then(|_| file.sync_all())
+            .map_err(|e| e.to_string())?;
+    }
+    std::fs::File::create(data_dir.join(REPLACE_MARKER))
+        .and_then(|marker| marker.sync_all())
+        .map_err(|e| e.to_string())?;
+
+    finish_replace(data_dir)
+}
+
+fn finish_replace(data_dir: &std::path::Path) -> Result<(), String> {
+    for name in ["tags", "tag_definitions"] {
+        let temp_path = temp_path(data_dir, name);
+        if temp_path.is_file() {
+            std::fs::rename(&temp_path, store_path(data_dir, name)).map_err(|e| e.to_string())?;
+        }
+    }
+    std::fs::remove_file(data_dir.join(REPLACE_MARKER)).map_err(|e| e.to_string())
+}
+
+/// Settles a replacement that was cut short, see [`replace_stores`]. Runs
+/// at startup before anything reads the stores.
+pub fn recover_stores(data_dir: &std::path::Path) -> Result<(), String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+
+    if data_dir.join(REPLACE_MARKER).is_file() {
+        println!("finishing an interrupted tag store update");
+        finish_replace(data_dir)
+    } else {
+        for name in ["tags", "tag_definitions"] {
+            let _ = std::fs::remove_file(temp_path(data_dir, name));
+        }
+        Ok(())
+    }
+}
+
+fn save_definition(data_dir: &std::path::Path, tag: &Tag) -> Result<(), String> {
+    let store = get_tag_definition_store(data_dir).ok_or("tag store not found".to_string())?;
+    store
+        .save_with_id(tag, &tag.name)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Tag definitions keyed by name, for resolving many names without
+/// rereading the store for each
+pub fn tag_definitions(data_dir: &std::path::Path) -> BTreeMap<String, Tag> {
+    get_tag_definition_store(data_dir)
+        .and_then(|store| store.all::<Tag>().ok())
+        .unwrap_or_default()
+}
+
+fn normalize_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("tag name is empty".to_string());
+    }
+    Ok(name.to_string())
+}
+
+/// The canonical name for a tag or one of its aliases
+pub fn resolve_in(definitions: &BTreeMap<String, Tag>, name: &str) -> String {
+    let name = name.trim();
+    if definitions.contains_key(name) {
+        return name.to_string();
+    }
+
+    definitions
+        .values()
+        .find(|tag| tag.aliases.iter().any(|alias| alias == name))
+        .map(|tag| tag.name.clone())
+        .unwrap_or(name.to_string())
+}
+
+/// The tag followed by its parents, stopping at a cycle
+fn ancestors_in(definitions: &BTreeMap<String, Tag>, name: &str) -> Vec<String> {
+    let mut names = vec![name.to_string()];
+    let mut current = definitions.get(name).and_then(|tag| tag.parent.clone());
+
+    while let Some(parent) = current {
+        if names.contains(&parent) {
+            break;
+        }
+        current = definitions.get(&parent).and_then(|tag| tag.parent.clone());
+        names.push(parent);
+    }
+
+    names
+}
+
+/// The tags plus every ancestor they imply, for matching queries
+pub fn implied_tags_in(definitions: &BTreeMap<String, Tag>, tags: &[String]) -> Vec<String> {
+    let mut implied = tags
+        .iter()
+        .flat_map(|tag| ancestors_in(definitions, tag))
+        .collect::<Vec<_>>();
+    implied.sort();
+    implied.dedup();
+    implied
+}
+
+fn normalize_tags(definitions: &BTreeMap<String, Tag>, tags: &[String]) -> Vec<String> {
+    let mut tags = tags
+        .iter()
+        .map(|tag| resolve_in(definitions, tag))
+        .filter(|tag| !tag.is_empty())
+        .collect::<Vec<_>>();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Makes sure every assigned name has a definition, so the tag list shows it
+fn ensure_definitions(definitions: &jfs::Store, names: &[String]) -> Result<(), String> {
+    for name in names {
+        if definitions.get::<Tag>(name).is_err() {
+            definitions
+                .save_with_id(&Tag::named(name), name)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn write_assignments(store: &jfs::Store, media_id: &str, tags: &[String]) -> Result<(), String> {
+    if tags.is_empty() {
+        let _ = store.delete(media_id);
+        Ok(())
+    } else {
+        store
+            .save_with_id(&tags.to_vec(), media_id)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+pub fn list_tags(data_dir: &std::path::Path) -> Vec<Tag> {
+    tag_definitions(data_dir).into_values().collect()
+}
+
+/// Creates the tag, or updates color and hotkey when it exists. Parents and
+/// aliases have their own operations.
+pub fn create_tag(data_dir: &std::path::Path, tag: Tag) -> Result<Tag, String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+    let definitions = tag_definitions(data_dir);
+
+    let name = normalize_name(&tag.name)?;
+    let canonical = resolve_in(&definitions, &name);
+    if canonical != name {
+        return Err(format!("{name} is an alias of {canonical}"));
+    }
+
+    let tag = match definitions.get(&name) {
+        Some(existing) => Tag {
+            color: tag.color,
+            hotkey: tag.hotkey,
+            ..existing.clone()
+        },
+        None => Tag {
+            color: tag.color,
+            hotkey: tag.hotkey,
+            ..Tag::named(&name)
+        },
+    };
+    save_definition(data_dir, &tag)?;
+
+    Ok(tag)
+}
+
+/// Removes the tag and every assignment of it, its children move up to its
+/// parent
+pub fn delete_tag(data_dir: &std::path::Path, name: &str) -> Result<(), String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+    let mut definitions = tag_definitions(data_dir);
+    let mut assignments = all_assignments(data_dir);
+
+    let removed = definitions.remove(name).ok_or(format!("no tag {name}"))?;
+    for tag in definitions.values_mut() {
+        if tag.parent.as_deref() == Some(name) {
+            tag.parent = removed.parent.clone();
+        }
+    }
+
+    for tags in assignments.values_mut() {
+        tags.retain(|tag| tag != name);
+    }
+    assignments.retain(|_, tags| !tags.is_empty());
+
+    replace_stores(data_dir, &assignments, &definitions)
+}
+
+pub fn tags_for_media(data_dir: &std::path::Path, media_id: &str) -> Vec<String> {
+    get_tag_store(data_dir)
+        .and_then(|store| store.get::<Vec<String>>(media_id).ok())
+        .unwrap_or_default()
+}
+
+/// The full definitions of the tags assigned to the media
+pub fn tag_definitions_for_media(data_dir: &std::path::Path, media_id: &str) -> Vec<Tag> {
+    let definitions = tag_definitions(data_dir);
+
+    tags_for_media(data_dir, media_id)
+        .iter()
+        .map(|name| definitions.get(name).cloned().unwrap_or(Tag::named(name)))
+        .collect()
+}
+
+pub fn all_assignments(data_dir: &std::path::Path) -> BTreeMap<String, Vec<String>> {
+    get_tag_store(data_dir)
+        .and_then(|store| store.all::<Vec<String>>().ok())
+        .unwrap_or_default()
+}
+
+/// Rewrites the media's tags under the lock, so concurrent edits don't drop
+/// each other's changes
+fn update_tags_for_media(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    update: impl FnOnce(Vec<String>) -> Vec<String>,
+) -> Result<Vec<String>, String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+    let store = get_tag_store(data_dir).ok_or("tag store not found".to_string())?;
+    let definitions =
+        get_tag_definition_store(data_dir).ok_or("tag store not found".to_string())?;
+
+    let current = store.get::<Vec<String>>(media_id).unwrap_or_default();
+    let tags = normalize_tags(&tag_definitions(data_dir), &update(current));
+    ensure_definitions(&definitions, &tags)?;
+    write_assignments(&store, media_id, &tags)?;
+
+    Ok(tags)
+}
+
+pub fn set_tags_for_media(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    tags: &[String],
+) -> Result<Vec<String>, String> {
+    update_tags_for_media(data_dir, media_id, |_| tags.to_vec())
+}
+
+/// Adds tags to many items with a single write of the stores, keeping the
+/// ones already assigned. Returns the tags each item gains, resolved the way
+/// they are stored. With `dry_run` nothing is written.
+pub fn add_tags_to_media(
+    data_dir: &std::path::Path,
+    additions: &BTreeMap<String, Vec<String>>,
+    dry_run: bool,
+) -> Result<BTreeMap<String, Vec<String>>, String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+    let mut definitions = tag_definitions(data_dir);
+    let mut assignments = all_assignments(data_dir);
+
+    let mut added = BTreeMap::new();
+    for (media_id, names) in additions {
+        let current = assignments.get(media_id).cloned().unwrap_or_default();
+        let tags = normalize_tags(&definitions, &[current.clone(), names.clone()].concat());
+        let new_tags = tags
+            .iter()
+            .filter(|tag| !current.contains(tag))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !new_tags.is_empty() {
+            assignments.insert(media_id.clone(), tags);
+            added.insert(media_id.clone(), new_tags);
+        }
+    }
+
+    if !dry_run && !added.is_empty() {
+        for name in added.values().flatten() {
+            if !definitions.contains_key(name) {
+                definitions.insert(name.clone(), Tag::named(name));
+            }
+        }
+        replace_stores(data_dir, &assignments, &definitions)?;
+    }
+
+    Ok(added)
+}
+
+pub fn assign_tag(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    name: &str,
+) -> Result<Vec<String>, String> {
+    let name = normalize_name(name)?;
+    update_tags_for_media(data_dir, media_id, |mut tags| {
+        tags.push(name);
+        tags
+    })
+}
+
+pub fn unassign_tag(
+    data_dir: &std::path::Path,
+    media_id: &str,
+    name: &str,
+) -> Result<Vec<String>, String> {
+    let name = resolve_in(&tag_definitions(data_dir), name);
+    update_tags_for_media(data_dir, media_id, |tags| {
+        tags.into_iter().filter(|tag| *tag != name).collect()
+    })
+}
+
+pub fn set_tag_parent(
+    data_dir: &std::path::Path,
+    name: &str,
+    parent: Option<&str>,
+) -> Result<Tag, String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+    let definitions = tag_definitions(data_dir);
+
+    let name = resolve_in(&definitions, name);
+    let mut tag = definitions
+        .get(&name)
+        .cloned()
+        .ok_or(format!("no tag {name}"))?;
+
+    tag.parent = match parent {
+        Some(parent) => {
+            let parent = resolve_in(&definitions, parent);
+            if !definitions.contains_key(&parent) {
+                return Err(format!("no tag {parent}"));
+            }
+            if ancestors_in(&definitions, &parent).contains(&name) {
+                return Err(format!("{parent} is already below {name}"));
+            }
+            Some(parent)
+        }
+        None => None,
+    };
+    save_definition(data_dir, &tag)?;
+
+    Ok(tag)
+}
+
+pub fn add_tag_alias(data_dir: &std::path::Path, name: &str, alias: &str) -> Result<Tag, String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+    let definitions = tag_definitions(data_dir);
+
+    let name = resolve_in(&definitions, name);
+    let alias = normalize_name(alias)?;
+    let mut tag = definitions
+        .get(&name)
+        .cloned()
+        .ok_or(format!("no tag {name}"))?;
+
+    if definitions.contains_key(&alias) {
+        return Err(format!("{alias} is a tag, merge it instead"));
+    }
+    let owner = resolve_in(&definitions, &alias);
+    if owner != alias && owner != name {
+        return Err(format!("{alias} is already an alias of {owner}"));
+    }
+
+    if !tag.aliases.contains(&alias) {
+        tag.aliases.push(alias);
+        tag.aliases.sort();
+    }
+    save_definition(data_dir, &tag)?;
+
+    Ok(tag)
+}
+
+pub fn remove_tag_alias(
+    data_dir: &std::path::Path,
+    name: &str,
+    alias: &str,
+) -> Result<Tag, String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+    let definitions = tag_definitions(data_dir);
+
+    let name = resolve_in(&definitions, name);
+    let mut tag = definitions
+        .get(&name)
+        .cloned()
+        .ok_or(format!("no tag {name}"))?;
+
+    tag.aliases.retain(|existing| existing != alias.trim());
+    save_definition(data_dir, &tag)?;
+
+    Ok(tag)
+}
+
+/// Folds `sources` into `target`: assignments and aliases move over,
+/// children are reparented, and with `keep_aliases` the old names keep
+/// resolving.
+/// Callers hold the tag lock.
+fn merge_into(
+    data_dir: &std::path::Path,
+    sources: &[String],
+    target: &str,
+    keep_aliases: bool,
+) -> Result<Tag, String> {
+    let mut definitions = tag_definitions(data_dir);
+    let mut assignments = all_assignments(data_dir);
+
+    let target = resolve_in(&definitions, &normalize_name(target)?);
+    let sources = sources
+        .iter()
+        .map(|source| resolve_in(&definitions, source))
+        .filter(|source| *source != target)
+        .collect::<Vec<_>>();
+    if sources.is_empty() {
+        return Err("nothing to merge".to_string());
+    }
+    if let Some(missing) = sources
+        .iter()
+        .find(|source| !definitions.contains_key(*source))
+    {
+        return Err(format!("no tag {missing}"));
+    }
+
+    let mut merged = definitions
+        .get(&target)
+        .cloned()
+        .unwrap_or(Tag::named(&target));
+    for source in &sources {
+        let Some(removed) = definitions.remove(source) else {
+            continue;
+        };
+
+        merged.color = merged.color.or(removed.color);
+        merged.hotkey = merged.hotkey.or(removed.hotkey);
+        merged.parent = merged.parent.or(removed.parent);
+        // the source's aliases always follow it, `keep_aliases` only decides
+        // whether its own name becomes one
+        merged.aliases.extend(removed.aliases);
+        if keep_aliases {
+            merged.aliases.push(source.clone());
+        }
+    }
+    merged.aliases.sort();
+    merged.aliases.dedup();
+    // the merged tag can't end up as its own parent
+    if merged
+        .parent
+        .as_ref()
+        .is_some_and(|parent| *parent == target || sources.contains(parent))
+    {
+        merged.parent = None;
+    }
+
+    for tag in definitions.values_mut() {
+        if tag
+            .parent
+            .as_ref()
+            .is_some_and(|parent| sources.contains(parent))
+        {
+            tag.parent = Some(target.clone());
+        }
+    }
+    definitions.insert(target.clone(), merged.clone());
+
+    for tags in assignments.values_mut() {
+        if tags.iter().any(|tag| sources.contains(tag)) {
+            for tag in tags.iter_mut() {
+                if sources.contains(tag) {
+                    *tag = target.clone();
+                }
+            }
+            tags.sort();
+            tags.dedup();
+        }
+    }
+
+    replace_stores(data_dir, &assignments, &definitions)?;
+
+    Ok(merged)
+}
+
+/// Folds `sources` into `target`, the old names become aliases of it
+pub fn merge_tags(
+    data_dir: &std::path::Path,
+    sources: &[String],
+    target: &str,
+) -> Result<Tag, String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+    merge_into(data_dir, sources, target, true)
+}
+
+pub fn rename_tag(data_dir: &std::path::Path, name: &str, new_name: &str) -> Result<Tag, String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+    let definitions = tag_definitions(data_dir);
+
+    let new_name = normalize_name(new_name)?;
+    if resolve_in(&definitions, &new_name) != new_name || definitions.contains_key(&new_name) {
+        return Err(format!("{new_name} already exists, merge the tags instead"));
+    }
+
+    merge_into(data_dir, &[name.to_string()], &new_name, false)
+}
+
+/// Merges the `tags` and `tagAssignments` the webview used to keep in
+/// localStorage. Existing definitions win, assignments are unioned.
+pub fn import_local_storage_tags(
+    data_dir: &std::path::Path,
+    tags: Vec<Tag>,
+    assignments: HashMap<String, Vec<String>>,
+) -> Result<TagImportSummary, String> {
+    let _lock = TAG_LOCK.lock().map_err(|e| e.to_string())?;
+    let mut definitions = tag_definitions(data_dir);
+    let mut stored = all_assignments(data_dir);
+
+    let mut imported_tags = 0;
+    for tag in tags {
+        let Ok(name) = normalize_name(&tag.name) else {
+            continue;
+        };
+        if !definitions.contains_key(&name) {
+            definitions.insert(
+                name.clone(),
+                Tag {
+                    name: name.clone(),
+                    ..tag
+                },
+            );
+            imported_tags += 1;
+        }
+    }
+
+    let mut imported_assignments = 0;
+    for (id, names) in assignments {
+        // the webview keyed assignments by path in places, media ids are file names
+        let media_id = crate::local_files::media_id_from_path(&id);
+        if media_id.is_empty() {
+            continue;
+        }
+
+        let mut tags = stored.remove(&media_id).unwrap_or_default();
+        tags.extend(names);
+        let tags = normalize_tags(&definitions, &tags);
+
+        for name in &tags {
+            if !definitions.contains_key(name) {
+                definitions.insert(name.clone(), Tag::named(name));
+            }
+        }
+        if !tags.is_empty() {
+            stored.insert(media_id, tags);
+        }
+        imported_assignments += 1;
+    }
+
+    replace_stores(data_dir, &stored, &definitions)?;
+
+    Ok(TagImportSummary {
+        tags: imported_tags,
+        assignments: imported_assignments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(data_dir: &std::path::Path, name: &str) -> Tag {
+        create_tag(data_dir, Tag::named(name)).unwrap()
+    }
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn aliases_resolve_to_their_tag() {
+        let data = tempfile::tempdir().unwrap();
+        let data_dir = data.path();
+        tag(data_dir, "cat");
+        tag(data_dir, "dog");
+
+        add_tag_alias(data_dir, "cat", " kitty ").unwrap();
+        assert_eq!(resolve_in(&tag_definitions(data_dir), "kitty"), "cat");
+        assert_eq!(assign_tag(data_dir, "a.jpg", "kitty").unwrap(), ["cat"]);
+        assert_eq!(
+            unassign_tag(data_dir, "a.jpg", "kitty").unwrap(),
+            strings(&[])
+        );
+
+        assert!(add_tag_alias(data_dir, "dog", "kitty").is_err());
+        assert!(add_tag_alias(data_dir, "cat", "dog").is_err());
+        assert!(create_tag(data_dir, Tag::named("kitty")).is_err());
+        // adding it again to the same tag is fine
+        assert_eq!(
+            add_tag_alias(data_dir, "kitty", "kitty").unwrap().aliases,
+            ["kitty"]
+        );
+    }
+
+    #[test]
+    fn parents_cannot_form_a_cycle() {
+        let data = tempfile::tempdir().unwrap();
+        let data_dir = data.path();
+        for name in ["animals", "cat", "kitten"] {
+            tag(data_dir, name);
+        }
+        set_tag_parent(data_dir, "cat", Some("animals")).unwrap();
+        set_tag_parent(data_dir, "kitten", Some("cat")).unwrap();
+
+        assert!(set_tag_parent(data_dir, "animals", Some("kitten")).is_err());
+        assert!(set_tag_parent(data_dir, "cat", Some("cat")).is_err());
+        assert!(set_tag_parent(data_dir, "cat", Some("missing")).is_err());
+
+        let definitions = tag_definitions(data_dir);
+        assert_eq!(definitions["animals"].parent, None);
+        assert_eq!(
+            implied_tags_in(&definitions, &strings(&["kitten"])),
+            ["animals", "cat", "kitten"]
+        );
+    }
+
+    #[test]
+    fn rename_moves_assignments_and_children() {
+        let data = tempfile::tempdir().unwrap();
+        let data_dir = data.path();
+        create_tag(
+            data_dir,
+            Tag {
+                color: Some("#f00".to_string()),
+                ..Tag::named("cat")
+            },
+        )
+        .unwrap();
+        tag(data_dir, "kitten");
+        set_tag_parent(data_dir, "kitten", Some("cat")).unwrap();
+        set_tags_for_media(data_dir, "a.jpg", &strings(&["cat", "zebra"])).unwrap();
+
+        let renamed = rename_tag(data_dir, "cat", "feline").unwrap();
+        assert_eq!(renamed.color.as_deref(), Some("#f00"));
+        assert!(renamed.aliases.is_empty());
+
+        let definitions = tag_definitions(data_dir);
+        assert!(!definitions.contains_key("cat"));
+        assert_eq!(definitions["kitten"].parent.as_deref(), Some("feline"));
+        assert_eq!(tags_for_media(data_dir, "a.jpg"), ["feline", "zebra"]);
+
+        assert!(rename_tag(data_dir, "kitten", "feline").is_err());
+        assert!(rename_tag(data_dir, "kitten", " ").is_err());
+    }
+
+    #[test]
+    fn merge_keeps_the_old_names_as_aliases() {
+        let data = tempfile::tempdir().unwrap();
+        let data_dir = data.path();
+        tag(data_dir, "cat");
+        tag(data_dir, "kitty");
+        add_tag_alias(data_dir, "kitty", "kitten").unwrap();
+        set_tags_for_media(data_dir, "a.jpg", &strings(&["cat"])).unwrap();
+        set_tags_for_media(data_dir, "b.jpg", &strings(&["kitty"])).unwrap();
+        set_tags_for_media(data_dir, "c.jpg", &strings(&["cat", "kitty"])).unwrap();
+
+        let merged = merge_tags(data_dir, &strings(&["kitty"]), "cat").unwrap();
+        assert_eq!(merged.aliases, ["kitten", "kitty"]);
+        assert_eq!(list_tags(data_dir), [merged]);
+        for media_id in ["a.jpg", "b.jpg", "c.jpg"] {
+            assert_eq!(tags_for_media(data_dir, media_id), ["cat"]);
+        }
+        assert_eq!(assign_tag(data_dir, "d.jpg", "kitty").unwrap(), ["cat"]);
+
+        assert!(merge_tags(data_dir, &strings(&["cat"]), "cat").is_err());
+        assert!(merge_tags(data_dir, &strings(&["missing"]), "cat").is_err());
+    }
+
+    #[test]
+    fn recovery_finishes_complete_replacements_and_drops_partial_ones() {
+        let data = tempfile::tempdir().unwrap();
+        let data_dir = data.path();
+        set_tags_for_media(data_dir, "a.jpg", &strings(&["cat"])).unwrap();
+
+        // cut short before the marker: the stores stay as they were
+        std::fs::write(temp_path(data_dir, "tags"), r#"{"a.jpg": ["dog"]}"#).unwrap();
+        recover_stores(data_dir).unwrap();
+        assert!(!temp_path(data_dir, "tags").exists());
+        assert_eq!(tags_for_media(data_dir, "a.jpg"), ["cat"]);
+
+        // cut short after the marker: the temp files are moved into place
+        std::fs::write(temp_path(data_dir, "tags"), r#"{"a.jpg": ["dog"]}"#).unwrap();
+        std::fs::write(
+            temp_path(data_dir, "tag_definitions"),
+            r#"{"dog": {"name": "dog"}}"#,
+        )
+        .unwrap();
+        std::fs::write(data_dir.join(REPLACE_MARKER), "").unwrap();
+        recover_stores(data_dir).unwrap();
+        assert!(!data_dir.join(REPLACE_MARKER).exists());
+        assert!(!temp_path(data_dir, "tags").exists());
+        assert!(!temp_path(data_dir, "tag_definitions").exists());
+        assert_eq!(tags_for_media(data_dir, "a.jpg"), ["dog"]);
+        assert_eq!(list_tags(data_dir), [Tag::named("dog")]);
+    }
+
+    #[test]
+    fn local_storage_import_keeps_existing_definitions_and_unions_assignments() {
+        let data = tempfile::tempdir().unwrap();
+        let data_dir = data.path();
+        create_tag(
+            data_dir,
+            Tag {
+                color: Some("red".to_string()),
+                ..Tag::named("cat")
+            },
+        )
+        .unwrap();
+        set_tags_for_media(data_dir, "a.jpg", &strings(&["cat"])).unwrap();
+
+        let summary = import_local_storage_tags(
+            data_dir,
+            vec![
+                Tag {
+                    color: Some("blue".to_string()),
+                    ..Tag::named("cat")
+                },
+                Tag::named(" dog "),
+                Tag::named(""),
+            ],
+            HashMap::from([
+                ("/old/library/a.jpg".to_string(), strings(&["dog"])),
+                ("b.jpg".to_string(), strings(&["bird"])),
+                ("".to_string(), strings(&["ignored"])),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(summary.tags, 1);
+        assert_eq!(summary.assignments, 2);
+        let definitions = tag_definitions(data_dir);
+        assert_eq!(definitions["cat"].color.as_deref(), Some("red"));
+        assert_eq!(
+            definitions.keys().collect::<Vec<_>>(),
+            ["bird", "cat", "dog"]
+        );
+        assert_eq!(tags_for_media(data_dir, "a.jpg"), ["cat", "dog"]);
+        assert_eq!(tags_for_media(data_dir, "b.jpg"), ["bird"]);
+        assert!(!data_dir.join(REPLACE_MARKER).exists());
+    }
+}
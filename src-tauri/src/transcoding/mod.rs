@@ -0,0 +1,602 @@
+extern crate ffmpeg_next as ffmpeg;
+
+use ffmpeg::{
+    codec, encoder, filter, format, frame, media, software, Dictionary, Packet, Rational, Rescale,
+};
+use http::{header::*, response::Builder as ResponseBuilder, status::StatusCode};
+use serde::Serialize;
+
+/// Length of one transcoded segment in seconds
+pub const SEGMENT_SECONDS: f64 = 10.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Mp4,
+    WebM,
+}
+
+impl OutputFormat {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mp4" => Some(OutputFormat::Mp4),
+            "webm" => Some(OutputFormat::WebM),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Mp4 => "mp4",
+            OutputFormat::WebM => "webm",
+        }
+    }
+
+    fn mime(&self) -> &'static str {
+        match self {
+            OutputFormat::Mp4 => "video/mp4; codecs=\"avc1.640028, mp4a.40.2\"",
+            OutputFormat::WebM => "video/webm; codecs=\"vp9, opus\"",
+        }
+    }
+
+    fn video_codec(&self) -> Option<ffmpeg::Codec> {
+        match self {
+            OutputFormat::Mp4 => {
+                encoder::find_by_name("libx264").or_else(|| encoder::find(codec::Id::H264))
+            }
+            OutputFormat::WebM => {
+                encoder::find_by_name("libvpx-vp9").or_else(|| encoder::find(codec::Id::VP9))
+            }
+        }
+    }
+
+    fn audio_codec(&self) -> Option<ffmpeg::Codec> {
+        match self {
+            OutputFormat::Mp4 => encoder::find(codec::Id::AAC),
+            OutputFormat::WebM => {
+                encoder::find_by_name("libopus").or_else(|| encoder::find(codec::Id::OPUS))
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TranscodeManifest {
+    pub id: String,
+    pub duration: f64,
+    pub segment_seconds: f64,
+    pub segment_count: u64,
+    pub mime: String,
+}
+
+/// Handles `stream://transcode/<id>` (manifest) and
+/// `stream://transcode/<id>/<segment>.<mp4|webm>` (segment) requests.
+///
+/// Segments are independent fragmented files carrying absolute timestamps, so
+/// the player can seek by restarting at the segment containing the requested
+/// time and append them to a `MediaSource` as-is.
+pub fn get_transcode_response(
+    request: http::Request<Vec<u8>>,
+    media_dir: &std::path::Path,
+    cache_dir: &std::path::Path,
+) -> Result<http::Response<Vec<u8>>, Box<dyn std::error::Error>> {
+    let path = percent_encoding::percent_decode(request.uri().path().as_bytes())
+        .decode_utf8_lossy()
+        .to_string();
+    let path = path.trim_start_matches('/');
+    let path = path.strip_prefix("transcode/").unwrap_or(path);
+    println!("transcode path: {path}");
+
+    let not_found = || {
+        ResponseBuilder::new()
+            .status(StatusCode::NOT_FOUND)
+            .header(CONTENT_TYPE, "text/plain")
+            .body("media not found".as_bytes().to_vec())
+    };
+
+    let (id, segment) = match path.split_once('/') {
+        Some((id, segment)) => (id, Some(segment)),
+        None => (path, None),
+    };
+
+    let source_path = match crate::local_files::resolve_media_id(media_dir, id) {
+        Some(source_path) => source_path,
+        None => return Ok(not_found()?),
+    };
+
+    let query_format = request
+        .uri()
+        .query()
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("format=")))
+        .and_then(OutputFormat::from_name)
+        .unwrap_or(OutputFormat::Mp4);
+
+    let response = match segment {
+        None => {
+            let duration = get_duration(&source_path)?;
+            let manifest = TranscodeManifest {
+                id: id.to_string(),
+                duration,
+                segment_seconds: SEGMENT_SECONDS,
+                segment_count: (duration / SEGMENT_SECONDS).ceil().max(1.0) as u64,
+                mime: query_format.mime().to_string(),
+            };
+
+            ResponseBuilder::new()
+                .header(CONTENT_TYPE, "application/json")
+                .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                .body(serde_json::to_vec(&manifest)?)
+        }
+        Some(segment) => {
+            let (index, output_format) = match segment.split_once('.') {
+                Some((index, extension)) => (
+                    index.parse::<u64>().ok(),
+                    OutputFormat::from_name(extension),
+                ),
+                None => (segment.parse::<u64>().ok(), Some(query_format)),
+            };
+
+            let (index, output_format) = match (index, output_format) {
+                (Some(index), Some(output_format)) => (index, output_format),
+                _ => {
+                    return Ok(ResponseBuilder::new()
+                        .status(StatusCode::BAD_REQUEST)
+                        .header(CONTENT_TYPE, "text/plain")
+                        .body("invalid segment".as_bytes().to_vec())?)
+                }
+            };
+
+            let segment_path = get_segment(&source_path, id, index, output_format, cache_dir)?;
+            let buf = std::fs::read(segment_path)?;
+
+            ResponseBuilder::new()
+                .header(CONTENT_TYPE, output_format.mime())
+                .header(CONTENT_LENGTH, buf.len())
+                .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                .body(buf)
+        }
+    };
+
+    response.map_err(Into::into)
+}
+
+pub fn get_duration(source_path: &std::path::Path) -> Result<f64, ffmpeg::Error> {
+    ffmpeg::init()?;
+    let ictx = format::input(&source_path)?;
+
+    Ok(ictx.duration().max(0) as f64 / f64::from(ffmpeg::ffi::AV_TIME_BASE))
+}
+
+/// Returns the cached segment, transcoding it first if it isn't there yet.
+/// Segments are written to a temporary file and renamed once complete, so an
+/// interrupted transcode never ends up in the cache.
+pub fn get_segment(
+    source_path: &std::path::Path,
+    id: &str,
+    index: u64,
+    output_format: OutputFormat,
+    cache_dir: &std::path::Path,
+) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    let segment_dir = cache_dir.join(id);
+    let segment_path = segment_dir.join(format!("{index}.{}", output_format.extension()));
+
+    if segment_path.is_file() {
+        println!("transcode cache hit: {:?}", segment_path);
+        return Ok(segment_path);
+    }
+
+    std::fs::create_dir_all(&segment_dir)?;
+    let temp_path = segment_dir.join(format!(
+        "{index}.{}.{}.part",
+        rand::random::<u32>(),
+        output_format.extension()
+    ));
+
+    let start = std::time::Instant::now();
+    let from = index as f64 * SEGMENT_SECONDS;
+    let res = transcode_segment(
+        source_path,
+        &temp_path,
+        from,
+        from + SEGMENT_SECONDS,
+        output_format,
+    );
+
+    match res {
+        Ok(_) => {
+            std::fs::rename(&temp_path, &segment_path)?;
+            println!("transcoded segment {index} of {id} in {:?}", start.elapsed());
+            Ok(segment_path)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp_path);
+            Err(e.into())
+        }
+    }
+}
+
+struct VideoTranscoder {
+    input_index: usize,
+    output_index: usize,
+    input_time_base: Rational,
+    decoder: codec::decoder::Video,
+    encoder: codec::encoder::Video,
+    scaler: software::scaling::Context,
+}
+
+struct AudioTranscoder {
+    input_index: usize,
+    output_index: usize,
+    input_time_base: Rational,
+    output_time_base: Rational,
+    decoder: codec::decoder::Audio,
+    encoder: codec::encoder::Audio,
+    filter: filter::Graph,
+}
+
+fn transcode_segment(
+    source_path: &std::path::Path,
+    dest_path: &std::path::Path,
+    from: f64,
+    to: f64,
+    output_format: OutputFormat,
+) -> Result<(), ffmpeg::Error> {
+    ffmpeg::init()?;
+
+    let mut ictx = format::input(&source_path)?;
+    let mut octx = format::output_as(&dest_path, output_format.extension())?;
+    let global_header = octx
+        .format()
+        .flags()
+        .contains(format::Flags::GLOBAL_HEADER);
+
+    let mut video = {
+        let input = ictx
+            .streams()
+            .best(media::Type::Video)
+            .ok_or(ffmpeg::Error::StreamNotFound)?;
+        let decoder = codec::context::Context::from_parameters(input.parameters())?
+            .decoder()
+            .video()?;
+
+        let codec = output_format
+            .video_codec()
+            .ok_or(ffmpeg::Error::EncoderNotFound)?;
+        let mut output = octx.add_stream(codec)?;
+        let mut encoder = codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()?;
+
+        // the webview only decodes even dimensions in 4:2:0
+        let width = decoder.width() & !1;
+        let height = decoder.height() & !1;
+        encoder.set_width(width);
+        encoder.set_height(height);
+        encoder.set_aspect_ratio(decoder.aspect_ratio());
+        encoder.set_format(format::Pixel::YUV420P);
+        encoder.set_frame_rate(decoder.frame_rate());
+        encoder.set_time_base(input.time_base());
+        if global_header {
+            encoder.set_flags(codec::Flags::GLOBAL_HEADER);
+        }
+
+        let mut options = Dictionary::new();
+        match output_format {
+            OutputFormat::Mp4 => {
+                options.set("preset", "veryfast");
+                options.set("crf", "23");
+            }
+            OutputFormat::WebM => {
+                options.set("deadline", "realtime");
+                options.set("cpu-used", "8");
+                options.set("crf", "32");
+                options.set("b", "0");
+            }
+        }
+        let encoder = encoder.open_with(options)?;
+        output.set_parameters(&encoder);
+
+        let scaler = software::scaling::Context::get(
+            decoder.format(),
+            decoder.width(),
+            decoder.height(),
+            format::Pixel::YUV420P,
+            width,
+            height,
+            software::scaling::Flags::BILINEAR,
+        )?;
+
+        VideoTranscoder {
+            input_index: input.index(),
+            output_index: output.index(),
+            input_time_base: input.time_base(),
+            decoder,
+            encoder,
+            scaler,
+        }
+    };
+
+    let mut audio = match ictx.streams().best(media::Type::Audio) {
+        Some(input) => match output_format.audio_codec() {
+            Some(codec) => Some(create_audio_transcoder(
+                &input,
+                codec,
+                &mut octx,
+                global_header,
+            )?),
+            None => None,
+        },
+        None => None,
+    };
+
+    let mut header_options = Dictionary::new();
+    if output_format == OutputFormat::Mp4 {
+        header_options.set("movflags", "frag_keyframe+empty_moov+default_base_moof");
+    }
+    octx.write_header_with(header_options)?;
+
+    // restart at the requested timestamp, landing on the keyframe before it
+    let seek_target = (from * f64::from(ffmpeg::ffi::AV_TIME_BASE)) as i64;
+    if seek_target > 0 {
+        ictx.seek(seek_target, ..seek_target)?;
+    }
+
+    let seconds = |ts: i64, time_base: Rational| ts as f64 * f64::from(time_base);
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() == video.input_index {
+            video.decoder.send_packet(&packet)?;
+            if receive_video_frames(&mut video, &mut octx, from, to)? {
+                break;
+            }
+        } else if let Some(audio) = audio.as_mut() {
+            if stream.index() == audio.input_index {
+                let past_end = packet
+                    .pts()
+                    .map(|pts| seconds(pts, audio.input_time_base) >= to)
+                    .unwrap_or(false);
+                if !past_end {
+                    audio.decoder.send_packet(&packet)?;
+                    receive_audio_frames(audio, &mut octx, from, to)?;
+                }
+            }
+        }
+    }
+
+    video.decoder.send_eof()?;
+    receive_video_frames(&mut video, &mut octx, from, to)?;
+    video.encoder.send_eof()?;
+    write_encoded_packets(
+        &mut video.encoder,
+        video.input_time_base,
+        video.output_index,
+        &mut octx,
+    )?;
+
+    if let Some(audio) = audio.as_mut() {
+        audio.decoder.send_eof()?;
+        receive_audio_frames(audio, &mut octx, from, to)?;
+        audio.filter.get("in").unwrap().source().flush()?;
+        filter_audio_frames(audio, &mut octx)?;
+        audio.encoder.send_eof()?;
+        write_encoded_packets(
+            &mut audio.encoder,
+            audio.output_time_base,
+            audio.output_index,
+            &mut octx,
+        )?;
+    }
+
+    octx.write_trailer()?;
+
+    Ok(())
+}
+
+fn create_audio_transcoder(
+    input: &format::stream::Stream,
+    codec: ffmpeg::Codec,
+    octx: &mut format::context::Output,
+    global_header: bool,
+) -> Result<AudioTranscoder, ffmpeg::Error> {
+    let decoder = codec::context::Context::from_parameters(input.parameters())?
+        .decoder()
+        .audio()?;
+    let codec = codec.audio()?;
+
+    let mut output = octx.add_stream(codec)?;
+    let mut encoder = codec::context::Context::new_with_codec(*codec)
+        .encoder()
+        .audio()?;
+
+    let channel_layout = codec
+        .channel_layouts()
+        .map(|layouts| layouts.best(decoder.channel_layout().channels()))
+        .unwrap_or(ffmpeg::channel_layout::ChannelLayout::STEREO);
+    let sample_format = codec
+        .formats()
+        .and_then(|mut formats| formats.next())
+        .unwrap_or(format::Sample::F32(format::sample::Type::Planar));
+    // opus only accepts a handful of rates
+    let rate = match codec.id() {
+        codec::Id::OPUS => 48000,
+        _ => decoder.rate() as i32,
+    };
+
+    encoder.set_rate(rate);
+    encoder.set_channel_layout(channel_layout);
+    encoder.set_format(sample_format);
+    encoder.set_bit_rate(128_000);
+    encoder.set_time_base((1, rate));
+    output.set_time_base((1, rate));
+    if global_header {
+        encoder.set_flags(codec::Flags::GLOBAL_HEADER);
+    }
+
+    let encoder = encoder.open_as(codec)?;
+    output.set_parameters(&encoder);
+
+    let filter = create_audio_filter(&decoder, &encoder)?;
+
+    Ok(AudioTranscoder {
+        input_index: input.index(),
+        output_index: output.index(),
+        input_time_base: input.time_base(),
+        output_time_base: Rational::new(1, rate),
+        decoder,
+        encoder,
+        filter,
+    })
+}
+
+fn create_audio_filter(
+    decoder: &codec::decoder::Audio,
+    encoder: &codec::encoder::Audio,
+) -> Result<filter::Graph, ffmpeg::Error> {
+    let mut graph = filter::Graph::new();
+
+    let args = format!(
+        "time_base={}:sample_rate={}:sample_fmt={}:channel_layout=0x{:x}",
+        decoder.time_base(),
+        decoder.rate(),
+        decoder.format().name(),
+        decoder.channel_layout().bits()
+    );
+
+    graph.add(&filter::find("abuffer").unwrap(), "in", &args)?;
+    graph.add(&filter::find("abuffersink").unwrap(), "out", "")?;
+
+    {
+        let mut out = graph.get("out").unwrap();
+        out.set_sample_format(encoder.format());
+        out.set_channel_layout(encoder.channel_layout());
+        out.set_sample_rate(encoder.rate());
+    }
+
+    graph.output("in", 0)?.input("out", 0)?.parse("anull")?;
+    graph.validate()?;
+
+    if let Some(codec) = encoder.codec() {
+        if !codec
+            .capabilities()
+            .contains(codec::capabilities::Capabilities::VARIABLE_FRAME_SIZE)
+        {
+            graph
+                .get("out")
+                .unwrap()
+                .sink()
+                .set_frame_size(encoder.frame_size());
+        }
+    }
+
+    Ok(graph)
+}
+
+/// Returns `true` once the decoder has produced a frame past the segment end.
+fn receive_video_frames(
+    video: &mut VideoTranscoder,
+    octx: &mut format::context::Output,
+    from: f64,
+    to: f64,
+) -> Result<bool, ffmpeg::Error> {
+    let mut decoded = frame::Video::empty();
+    while video.decoder.receive_frame(&mut decoded).is_ok() {
+        let timestamp = match decoded.timestamp() {
+            Some(timestamp) => timestamp,
+            None => continue,
+        };
+        let time = timestamp as f64 * f64::from(video.input_time_base);
+
+        if time < from {
+            continue;
+        }
+        if time >= to {
+            return Ok(true);
+        }
+
+        let mut scaled = frame::Video::empty();
+        video.scaler.run(&decoded, &mut scaled)?;
+        scaled.set_pts(Some(timestamp));
+        scaled.set_kind(ffmpeg::picture::Type::None);
+
+        video.encoder.send_frame(&scaled)?;
+        write_encoded_packets(
+            &mut video.encoder,
+            video.input_time_base,
+            video.output_index,
+            octx,
+        )?;
+    }
+
+    Ok(false)
+}
+
+fn receive_audio_frames(
+    audio: &mut AudioTranscoder,
+    octx: &mut format::context::Output,
+    from: f64,
+    to: f64,
+) -> Result<(), ffmpeg::Error> {
+    let mut decoded = frame::Audio::empty();
+    while audio.decoder.receive_frame(&mut decoded).is_ok() {
+        let timestamp = match decoded.timestamp() {
+            Some(timestamp) => timestamp,
+            None => continue,
+        };
+        let time = timestamp as f64 * f64::from(audio.input_time_base);
+        if time < from || time >= to {
+            continue;
+        }
+
+        // the filter graph works in the decoder time base
+        let decoder_time_base = audio.decoder.time_base();
+        decoded.set_pts(Some(timestamp.rescale(audio.input_time_base, decoder_time_base)));
+        audio.filter.get("in").unwrap().source().add(&decoded)?;
+        filter_audio_frames(audio, octx)?;
+    }
+
+    Ok(())
+}
+
+fn filter_audio_frames(
+    audio: &mut AudioTranscoder,
+    octx: &mut format::context::Output,
+) -> Result<(), ffmpeg::Error> {
+    let mut filtered = frame::Audio::empty();
+    while audio
+        .filter
+        .get("out")
+        .unwrap()
+        .sink()
+        .frame(&mut filtered)
+        .is_ok()
+    {
+        audio.encoder.send_frame(&filtered)?;
+        write_encoded_packets(
+            &mut audio.encoder,
+            audio.output_time_base,
+            audio.output_index,
+            octx,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_encoded_packets<E>(
+    encoder: &mut E,
+    encoder_time_base: Rational,
+    output_index: usize,
+    octx: &mut format::context::Output,
+) -> Result<(), ffmpeg::Error>
+where
+    E: std::ops::DerefMut<Target = codec::encoder::Encoder>,
+{
+    let output_time_base = octx.stream(output_index).unwrap().time_base();
+    let mut encoded = Packet::empty();
+    while encoder.receive_packet(&mut encoded).is_ok() {
+        encoded.set_stream(output_index);
+        encoded.rescale_ts(encoder_time_base, output_time_base);
+        encoded.write_interleaved(octx)?;
+    }
+
+    Ok(())
+}
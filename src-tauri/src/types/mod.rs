@@ -4,23 +4,28 @@ use serde::{Deserialize, Serialize, Serializer};
 
 pub struct KindWrapper(pub Kind);
 
-impl Serialize for KindWrapper {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let kind_str = match self.0 {
+impl KindWrapper {
+    pub fn as_str(&self) -> &'static str {
+        match self.0 {
             Kind::Image => "image",
             Kind::Video => "video",
             Kind::Audio => "audio",
 
             _ => "unknown",
-        };
-        serializer.serialize_str(kind_str)
+        }
+    }
+}
+
+impl Serialize for KindWrapper {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct Dimensions {
     pub width: i64,
     pub height: i64,
@@ -28,12 +33,17 @@ pub struct Dimensions {
 }
 
 pub struct LocalFile {
+    pub id: String,
     pub name: String,
     pub lazy: bool,
     pub data: Option<String>,
     pub kind: KindWrapper,
     pub dimensions: Option<Dimensions>,
     pub extension: String,
+    pub favorite: bool,
+    /// One to five stars
+    pub rating: Option<u8>,
+    pub note: Option<String>,
 }
 
 impl Serialize for LocalFile {
@@ -41,13 +51,17 @@ impl Serialize for LocalFile {
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("File", 3)?;
+        let mut state = serializer.serialize_struct("File", 10)?;
+        state.serialize_field("id", &self.id)?;
         state.serialize_field("name", &self.name)?;
         state.serialize_field("data", &self.data)?;
         state.serialize_field("kind", &self.kind)?;
         state.serialize_field("extension", &self.extension)?;
         state.serialize_field("lazy", &self.lazy)?;
         state.serialize_field("dimensions", &self.dimensions)?;
+        state.serialize_field("favorite", &self.favorite)?;
+        state.serialize_field("rating", &self.rating)?;
+        state.serialize_field("note", &self.note)?;
 
         state.end()
     }
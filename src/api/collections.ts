@@ -0,0 +1,62 @@
+import { invoke } from "@tauri-apps/api/core";
+import { save } from "@tauri-apps/plugin-dialog";
+
+export type CollectionContent =
+  /** a saved library query, re-evaluated every time */
+  | { kind: "smart"; query: string }
+  /** media ids in order */
+  | { kind: "playlist"; items: string[] };
+
+export type Collection = { name: string } & CollectionContent;
+export type CollectionInfo = { id: string } & Collection;
+
+export const listCollections = (): Promise<CollectionInfo[]> =>
+  invoke("list_collections");
+
+export const createCollection = (
+  collection: Collection,
+): Promise<CollectionInfo> => invoke("create_collection", { collection });
+
+export const renameCollection = (
+  id: string,
+  name: string,
+): Promise<CollectionInfo> => invoke("rename_collection", { id, name });
+
+export const setCollectionQuery = (
+  id: string,
+  query: string,
+): Promise<CollectionInfo> => invoke("set_collection_query", { id, query });
+
+export const deleteCollection = (id: string): Promise<void> =>
+  invoke("delete_collection", { id });
+
+/** Appends, or inserts at `position`. Items already in the playlist move. */
+export const addToPlaylist = (
+  id: string,
+  mediaIds: string[],
+  position?: number,
+): Promise<CollectionInfo> =>
+  invoke("add_to_playlist", { id, mediaIds, position: position ?? null });
+
+export const removeFromPlaylist = (
+  id: string,
+  mediaIds: string[],
+): Promise<CollectionInfo> =>
+  invoke("remove_from_playlist", { id, mediaIds });
+
+export const moveInPlaylist = (
+  id: string,
+  from: number,
+  to: number,
+): Promise<CollectionInfo> => invoke("move_in_playlist", { id, from, to });
+
+/** Asks where to save and writes the collection as an M3U playlist */
+export const exportCollectionM3u = async (collection: CollectionInfo) => {
+  const path = await save({
+    defaultPath: `${collection.name}.m3u`,
+    filters: [{ name: "M3U playlist", extensions: ["m3u", "m3u8"] }],
+  });
+  if (!path) return null;
+
+  return invoke<string>("export_collection_m3u", { id: collection.id, path });
+};
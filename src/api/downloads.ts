@@ -0,0 +1,40 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { SubredditPost } from "./scrolller";
+
+export type DownloadResult = {
+  media_id: string;
+  /** the item was already in the library */
+  duplicate: boolean;
+};
+
+export type DownloadProgress = {
+  media_id: string;
+  received: number;
+  total: number | null;
+};
+
+export type SourceMetadata = {
+  source_url: string;
+  media_url: string;
+  subreddit: string | null;
+  title: string | null;
+  tags: string[];
+  downloaded_at: number;
+};
+
+export const downloadRemoteMedia = (
+  post: SubredditPost,
+): Promise<DownloadResult> => invoke("download_remote_media", { post });
+
+export const getMediaSource = (
+  mediaId: string,
+): Promise<SourceMetadata | null> =>
+  invoke("get_media_source", { mediaId });
+
+export const onDownloadProgress = (
+  callback: (progress: DownloadProgress) => void,
+) =>
+  listen<DownloadProgress>("download-progress", (event) =>
+    callback(event.payload),
+  );
@@ -0,0 +1,64 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export type ViewOutcome = "completed" | "skipped" | "partial";
+export type HistoryView = "recently_viewed" | "most_viewed" | "never_viewed";
+
+export type ViewEvent = {
+  media_id: string;
+  viewed_at: number;
+  /** seconds played */
+  watched: number;
+  outcome: ViewOutcome;
+};
+
+export type ViewStats = {
+  media_id: string;
+  views: number;
+  last_viewed_at: number | null;
+  watch_time: number;
+  completions: number;
+  skips: number;
+};
+
+export type MediaHistory = {
+  stats: ViewStats;
+  /** newest first */
+  events: ViewEvent[];
+};
+
+/** Without an outcome the backend classifies the view from the duration */
+export const recordView = ({
+  mediaId,
+  watched,
+  duration,
+  outcome,
+}: {
+  mediaId: string;
+  watched: number;
+  duration?: number;
+  outcome?: ViewOutcome;
+}): Promise<ViewStats> =>
+  invoke("record_view", {
+    mediaId,
+    watched,
+    duration: duration ?? null,
+    outcome: outcome ?? null,
+  });
+
+/** Seconds of the video that were actually played, seeking doesn't count */
+export const playedSeconds = (videoElement: HTMLVideoElement) => {
+  let total = 0;
+  for (let i = 0; i < videoElement.played.length; i++) {
+    total += videoElement.played.end(i) - videoElement.played.start(i);
+  }
+  return total;
+};
+
+export const viewHistory = (
+  view: HistoryView,
+  limit?: number,
+): Promise<ViewStats[]> =>
+  invoke("view_history", { view, limit: limit ?? null });
+
+export const mediaHistory = (mediaId: string): Promise<MediaHistory> =>
+  invoke("media_history", { mediaId });
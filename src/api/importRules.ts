@@ -0,0 +1,41 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export type RuleKind =
+  /** every folder between the import root and the file becomes a tag */
+  | { kind: "folders"; ignore?: string[] }
+  /** tags may use capture groups as `$1` or `${name}` */
+  | {
+      kind: "pattern";
+      pattern: string;
+      target?: "file_name" | "path";
+      tags: string[];
+    };
+
+export type ImportRule = { name: string; enabled?: boolean } & RuleKind;
+export type ImportRuleInfo = { id: string } & ImportRule;
+
+export type RulePreview = {
+  media_id: string;
+  /** the path the rules matched against */
+  import_path: string;
+  /** only tags the item doesn't have yet */
+  tags: string[];
+};
+
+export const listImportRules = (): Promise<ImportRuleInfo[]> =>
+  invoke("list_import_rules");
+
+export const addImportRule = (rule: ImportRule): Promise<ImportRuleInfo> =>
+  invoke("add_import_rule", { rule });
+
+export const updateImportRule = (
+  id: string,
+  rule: ImportRule,
+): Promise<ImportRuleInfo> => invoke("update_import_rule", { id, rule });
+
+export const deleteImportRule = (id: string): Promise<void> =>
+  invoke("delete_import_rule", { id });
+
+/** Reruns the rules over the library, a dry run only reports the changes */
+export const applyImportRules = (dryRun: boolean): Promise<RulePreview[]> =>
+  invoke("apply_import_rules", { dryRun });
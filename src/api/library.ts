@@ -0,0 +1,52 @@
+import { invoke } from "@tauri-apps/api/core";
+
+/** Terms of the weighted ("randomize") order, all added to an item's log weight */
+export type OrderingWeights = {
+  views: number;
+  recency: number;
+  recency_half_life_days: number;
+  rating: number;
+  favorite: number;
+  /** tag name to extra weight */
+  tags: Record<string, number>;
+  unseen_first: boolean;
+};
+
+export const getOrderingWeights = (): Promise<OrderingWeights> =>
+  invoke("get_ordering_weights");
+
+export const setOrderingWeights = (
+  weights: OrderingWeights,
+): Promise<OrderingWeights> => invoke("set_ordering_weights", { weights });
+
+/** What the library knows about one item beyond the file */
+export type MediaRecord = {
+  origin: string | null;
+  added_at: number | null;
+  duration: number | null;
+  note: string | null;
+  views: number;
+  last_viewed_at: number | null;
+  watch_time: number;
+  completions: number;
+  skips: number;
+  rating: number | null;
+  favorite: boolean;
+};
+
+export const setFavorite = (
+  mediaId: string,
+  favorite: boolean,
+): Promise<MediaRecord> => invoke("set_favorite", { mediaId, favorite });
+
+/** null clears the rating */
+export const setRating = (
+  mediaId: string,
+  rating: number | null,
+): Promise<MediaRecord> => invoke("set_rating", { mediaId, rating });
+
+/** null or an empty note removes it */
+export const setNote = (
+  mediaId: string,
+  note: string | null,
+): Promise<MediaRecord> => invoke("set_note", { mediaId, note });
@@ -0,0 +1,48 @@
+import { invoke } from "@tauri-apps/api/core";
+import { FinalFile } from "../types";
+
+export type RemoteSourceInfo = {
+  id: string;
+  name: string;
+  /** the provider behind the source, like `scrolller` or `json` */
+  provider: string;
+  /** built-in sources can't be removed */
+  removable: boolean;
+};
+
+export type RemoteSourceConfig = {
+  /** `feed` takes RSS, Atom and JSON Feed documents */
+  provider: "json" | "feed";
+  name: string;
+  url: string;
+};
+
+export type RemoteFilter = {
+  kind?: "video" | "image";
+  tags?: string[];
+  is_nsfw?: boolean;
+  limit?: number;
+};
+
+export type RemotePage = {
+  /** `null` once the source is exhausted */
+  iterator: string | null;
+  items: FinalFile[];
+};
+
+export const listRemoteSources = (): Promise<RemoteSourceInfo[]> =>
+  invoke("list_remote_sources");
+
+export const fetchRemotePage = (
+  sourceId: string,
+  iterator: string | null,
+  filter: RemoteFilter = {},
+): Promise<RemotePage> =>
+  invoke("fetch_remote_page", { sourceId, iterator, filter });
+
+export const addRemoteSource = (
+  config: RemoteSourceConfig,
+): Promise<RemoteSourceInfo> => invoke("add_remote_source", { config });
+
+export const removeRemoteSource = (id: string): Promise<void> =>
+  invoke("remove_remote_source", { id });
@@ -1,5 +1,12 @@
 import { invoke } from "@tauri-apps/api/core";
 
+export type MediaSource = {
+  url: string;
+  width: number;
+  height: number;
+  isOptimized: boolean;
+};
+
 export type SubredditPost = {
   __typename: "SubredditPost";
   id: number;
@@ -54,33 +61,123 @@ export type Subreddit = {
   isPaid: boolean | null;
   username: string | null;
   tags: string[] | null;
-  banner: string | null;
+  banner: MediaSource | null;
   isFollowing: boolean;
   children: { iterator: string; items: SubredditPost[] };
 };
 
-export type ScrolllerResult = {
-  data: {
-    discoverFilteredSubreddits: {
-      iterator: string;
-      items: Subreddit[];
-    };
-  };
+export type ScrolllerPage<T> = {
+  iterator: string | null;
+  items: T[];
+};
+
+export type ScrolllerError = {
+  kind:
+    | "network"
+    | "timeout"
+    | "rate_limited"
+    | "unavailable"
+    | "http"
+    | "api"
+    | "decode"
+    | "offline";
+  message?: string | number | null;
+};
+
+export const isTransientScrolllerError = (e: unknown) =>
+  typeof e === "object" &&
+  e !== null &&
+  ["network", "timeout", "rate_limited", "unavailable"].includes(
+    (e as ScrolllerError).kind,
+  );
+
+export type ScrolllerResult = ScrolllerPage<Subreddit>;
+
+export type DiscoverParams = {
+  filter?: MediaFilter;
+  /** left out mixes safe and nsfw subreddits */
+  isNsfw?: boolean;
+  includeFilters?: number[];
+  excludeFilters?: number[];
+  /** media hosts to leave out of the previews, like `REDGIFS` */
+  hostsDown?: string[];
+  limit?: number;
 };
 
 export async function getScrolllerData(
   {
     iterator,
-    filter,
-    isNsfw = false,
-  }: { iterator?: string; filter?: "VIDEO" | "IMAGE"; isNsfw?: boolean } = {
-    iterator: "",
+    ...params
+  }: DiscoverParams & { iterator?: string } = {
     filter: "VIDEO",
+    isNsfw: false,
   },
 ) {
-  const payload = { iterator, filter, isNsfw };
-  if (!iterator) delete payload.iterator;
-  const data: string = await invoke("get_scrolller_data", payload);
+  const data: ScrolllerResult = await invoke("get_scrolller_data", {
+    iterator: iterator || null,
+    params,
+  });
 
-  return (JSON.parse(data) as ScrolllerResult).data.discoverFilteredSubreddits;
+  return data;
 }
+
+export type MediaFilter = "VIDEO" | "PICTURE" | "ALBUM";
+
+export type Album = {
+  __typename: "Album";
+  id: number;
+  url: string;
+  title: string | null;
+  subredditTitle: string | null;
+  subredditUrl: string | null;
+  isNsfw: boolean | null;
+  mediaSources: MediaSource[] | null;
+  children: ScrolllerPage<SubredditPost> | null;
+};
+
+export function getScrolllerSubreddit({
+  url,
+  iterator,
+  filter,
+  limit,
+}: {
+  url: string;
+  iterator?: string;
+  filter?: MediaFilter;
+  limit?: number;
+}): Promise<ScrolllerPage<SubredditPost>> {
+  return invoke("get_scrolller_subreddit", { url, iterator, filter, limit });
+}
+
+export function searchScrolllerSubreddits({
+  query,
+  isNsfw,
+  limit,
+}: {
+  query: string;
+  isNsfw?: boolean;
+  limit?: number;
+}): Promise<Subreddit[]> {
+  return invoke("search_scrolller_subreddits", { query, isNsfw, limit });
+}
+
+export function getScrolllerAlbum({
+  url,
+  iterator,
+  limit,
+}: {
+  url: string;
+  iterator?: string;
+  limit?: number;
+}): Promise<Album> {
+  return invoke("get_scrolller_album", { url, iterator, limit });
+}
+
+export const clearScrolllerCache = (): Promise<void> =>
+  invoke("clear_scrolller_cache");
+
+export const setScrolllerOffline = (offline: boolean): Promise<void> =>
+  invoke("set_scrolller_offline", { offline });
+
+export const getScrolllerOffline = (): Promise<boolean> =>
+  invoke("get_scrolller_offline");
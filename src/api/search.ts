@@ -0,0 +1,38 @@
+import { invoke } from "@tauri-apps/api/core";
+import { LocalFile } from "../contexts/LocalFeedContext/types";
+
+export type SearchHit = {
+  score: number;
+  file: LocalFile;
+};
+
+export type SearchResults = {
+  /** matches across all pages */
+  total: number;
+  offset: number;
+  hits: SearchHit[];
+};
+
+export const searchMedia = ({
+  query,
+  offset,
+  limit,
+}: {
+  query: string;
+  offset?: number;
+  limit?: number;
+}): Promise<SearchResults> =>
+  invoke<SearchResults>("search_media", {
+    query,
+    offset: offset ?? null,
+    limit: limit ?? null,
+  }).then((results) => ({
+    ...results,
+    hits: results.hits.map((hit) => ({
+      ...hit,
+      file: { ...hit.file, type: "local" as const },
+    })),
+  }));
+
+export const rebuildSearchIndex = (): Promise<number> =>
+  invoke("rebuild_search_index");
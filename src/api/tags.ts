@@ -0,0 +1,62 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export type Tag = {
+  name: string;
+  color?: string;
+  hotkey?: string;
+  /** media with this tag also match queries for the parent */
+  parent?: string;
+  /** other names that resolve to this tag */
+  aliases?: string[];
+};
+
+export type TagAssignments = Record<string, string[]>;
+
+export type TagImportSummary = {
+  tags: number;
+  assignments: number;
+};
+
+export const listTags = (): Promise<Tag[]> => invoke("list_tags");
+
+export const createTag = (tag: Tag): Promise<Tag> =>
+  invoke("create_tag", { tag });
+
+export const deleteTag = (name: string): Promise<void> =>
+  invoke("delete_tag", { name });
+
+export const assignTag = (mediaId: string, name: string): Promise<string[]> =>
+  invoke("assign_tag", { mediaId, name });
+
+export const unassignTag = (mediaId: string, name: string): Promise<string[]> =>
+  invoke("unassign_tag", { mediaId, name });
+
+export const tagsForMedia = (mediaId: string): Promise<Tag[]> =>
+  invoke("tags_for_media", { mediaId });
+
+export const listTagAssignments = (): Promise<TagAssignments> =>
+  invoke("list_tag_assignments");
+
+export const importLocalStorageTags = (
+  tags: Tag[],
+  assignments: TagAssignments,
+): Promise<TagImportSummary> =>
+  invoke("import_local_storage_tags", { tags, assignments });
+
+export const setTagParent = (
+  name: string,
+  parent: string | null,
+): Promise<Tag> => invoke("set_tag_parent", { name, parent });
+
+export const addTagAlias = (name: string, alias: string): Promise<Tag> =>
+  invoke("add_tag_alias", { name, alias });
+
+export const removeTagAlias = (name: string, alias: string): Promise<Tag> =>
+  invoke("remove_tag_alias", { name, alias });
+
+export const renameTag = (name: string, newName: string): Promise<Tag> =>
+  invoke("rename_tag", { name, newName });
+
+/** the source names become aliases of the target */
+export const mergeTags = (sources: string[], target: string): Promise<Tag> =>
+  invoke("merge_tags", { sources, target });
@@ -3,47 +3,67 @@ import { Tag } from "./TagContext";
 
 export type Filter = {
   tags: Tag[];
+  /** Free-form library query, e.g. `kind:video duration:>30s -tag:nsfw` */
+  query: string;
   asString: string;
 };
 
 type FilterContext = Filter & {
   setTags: (tags: Tag[]) => void;
+  setQuery: (query: string) => void;
 };
 const FilterContext = createContext<FilterContext>({
   tags: [],
   setTags: () => {},
+  query: "",
+  setQuery: () => {},
   asString: "",
 });
 
-const persistFilterContext = (filters: { tags: Tag[] }) => {
+type PersistedFilter = { tags: Tag[]; query: string };
+
+const persistFilterContext = (filters: PersistedFilter) => {
   localStorage.setItem("filter", JSON.stringify(filters));
 };
 
-const loadFilterContext = (): { tags: Tag[] } => {
+const loadFilterContext = (): PersistedFilter => {
   const filters = localStorage.getItem("filter");
   if (filters) {
-    return JSON.parse(filters);
+    return { query: "", ...JSON.parse(filters) };
   }
-  return { tags: [] };
+  return { tags: [], query: "" };
 };
 
+/** The selected tags and the free-form query as one backend query */
+export const toLibraryQuery = ({
+  tags,
+  query,
+}: Pick<Filter, "tags" | "query">) =>
+  [
+    ...tags.map((t) => `tag:"${t.name.replace(/"/g, "")}"`),
+    ...(query.trim() ? [`(${query.trim()})`] : []),
+  ].join(" ");
+
 export const FilterProvider = ({ children }: { children: React.ReactNode }) => {
   const loaded = useMemo(() => loadFilterContext(), []);
   const [tags, setTags] = useState<Tag[]>(loaded.tags);
+  const [query, setQuery] = useState<string>(loaded.query);
 
   const asString = useMemo(() => {
-    return tags.map((t) => t.name).join(",");
-  }, [tags]);
+    return [tags.map((t) => t.name).join(","), query].join("|");
+  }, [tags, query]);
 
   useEffect(() => {
-    persistFilterContext({ tags });
-  }, [tags]);
+    persistFilterContext({ tags, query });
+  }, [tags, query]);
 
   return (
     <FilterContext.Provider
       value={{
         tags,
         setTags,
+        query,
+        setQuery,
         asString,
       }}
     >
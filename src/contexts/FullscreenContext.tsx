@@ -13,6 +13,7 @@ import { useSwipeable } from "react-swipeable";
 import { Container } from "../components/Container";
 import { useBoundingClientRect } from "../useBoundingClientRef";
 import { useTags } from "./TagContext";
+import { playedSeconds, recordView } from "../api/history";
 
 const FullscreenContext = createContext<{
   isFullscreen: boolean;
@@ -221,6 +222,22 @@ export const FullscreenProvider = ({
     }
   }, [divRect, state]);
 
+  // a fullscreen session is a view, recorded when it ends
+  const viewedId = state.kind === "fullscreen" ? state.id : null;
+  const viewedElement = state.kind === "fullscreen" ? state.videoElement : null;
+  useEffect(() => {
+    if (!viewedId || !viewedElement) return;
+    return () => {
+      const duration = viewedElement.duration;
+      recordView({
+        mediaId: viewedId,
+        watched: playedSeconds(viewedElement),
+        duration: Number.isFinite(duration) ? duration : undefined,
+        outcome: viewedElement.ended ? "completed" : undefined,
+      }).catch((e) => console.log("could not record view", e));
+    };
+  }, [viewedId, viewedElement]);
+
   useEffect(() => {
     if (state.kind === "unloading") {
       if (state.oldAttributes) {
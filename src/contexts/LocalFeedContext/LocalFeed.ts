@@ -1,7 +1,7 @@
 import { FeedImpl, FeedResult, FeedState } from "../../feed/Feed";
 import { FinalFile } from "../../types";
 import { Filter } from "../FilterContext";
-import { localFileToFinalFile } from "./localFiles";
+import { localFileToFinalFile, MediaPage } from "./localFiles";
 import { LocalFile } from "./types";
 
 export class LocalFeed extends FeedImpl<FinalFile, Filter> {
@@ -11,26 +11,53 @@ export class LocalFeed extends FeedImpl<FinalFile, Filter> {
 
   private localFiles: LocalFile[] = [];
   private currentIndex = 0;
+  private cursor: string | null = null;
+  private pending: Promise<void> | null = null;
 
   constructor(
     public filter: Filter,
-    private registerFilesListener: (
-      callback: (files: LocalFile[]) => void,
-    ) => void,
+    private loadPage: (
+      filter: Filter,
+      cursor: string | null,
+    ) => Promise<MediaPage>,
   ) {
     super();
 
-    this.registerFilesListener((files) => {
-      this.setFiles(files);
-    });
+    this.fetchNextPage();
   }
 
+  /** Pages are requested one at a time, the cursor comes from the last one */
+  private fetchNextPage = () => {
+    if (!this.pending) {
+      this.pending = this.loadPage(this.filter, this.cursor)
+        .then((page) => {
+          this.localFiles = [...this.localFiles, ...page.items];
+          this.cursor = page.cursor;
+          if (this.state === "loading") {
+            this.setState("ok");
+          }
+        })
+        .catch((e) => {
+          console.error("could not load local files", e);
+          this.setState("error");
+        })
+        .finally(() => {
+          this.pending = null;
+        });
+    }
+    return this.pending;
+  };
+
   getNext = async (): Promise<FeedResult<FinalFile>> => {
     console.log("local feed get next", this.state);
     if (this.state !== "ok") {
       return { kind: "none" };
     }
 
+    if (this.currentIndex >= this.localFiles.length && this.cursor) {
+      await this.fetchNextPage();
+    }
+
     if (this.currentIndex >= this.localFiles.length) {
       this.setState("exhausted");
       return { kind: "none" };
@@ -43,12 +70,4 @@ export class LocalFeed extends FeedImpl<FinalFile, Filter> {
       value: localFileToFinalFile(file),
     };
   };
-
-  setFiles = (files: LocalFile[]) => {
-    this.localFiles = files;
-
-    if (this.state === "loading") {
-      this.setState("ok");
-    }
-  };
 }
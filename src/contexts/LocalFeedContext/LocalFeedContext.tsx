@@ -8,18 +8,20 @@ import {
 } from "react";
 import { invoke } from "@tauri-apps/api/core";
 
-import { LocalFile } from "./types";
-import { loadLocalFiles } from "./localFiles";
+import { listMedia, SortKey } from "./localFiles";
 import { useFeed } from "../FeedContext";
-import { useTags } from "../TagContext";
 import { FeedFactory } from "../../feed/Feed";
 import { FinalFile } from "../../types";
-import { Filter } from "../FilterContext";
+import { Filter, toLibraryQuery } from "../FilterContext";
 import { LocalFeed } from "./LocalFeed";
 
 type LocalFeedContextSettings = {
   glob: string | null;
   randomize: boolean;
+  sort: SortKey;
+  descending: boolean;
+  /** id of the collection the feed shows, the whole library when null */
+  collection: string | null;
 };
 
 const persistSettings = (settings: LocalFeedContextSettings) => {
@@ -27,16 +29,25 @@ const persistSettings = (settings: LocalFeedContextSettings) => {
 };
 
 const loadSettings = (): LocalFeedContextSettings => {
+  const defaults: LocalFeedContextSettings = {
+    glob: null,
+    randomize: false,
+    sort: "name",
+    descending: false,
+    collection: null,
+  };
   const settings = localStorage.getItem("localFeedSettings");
   if (settings) {
-    return JSON.parse(settings);
+    return { ...defaults, ...JSON.parse(settings) };
   }
-  return { glob: null, randomize: false };
+  return defaults;
 };
 
 interface LocalFeedContext extends LocalFeedContextSettings {
   setGlob: (glob: string | null) => void;
   setRandomize: (randomize: boolean) => void;
+  setSort: (sort: SortKey, descending: boolean) => void;
+  setCollection: (collection: string | null) => void;
   loadGlobFiles: () => Promise<void>;
   triggerReload: () => Promise<void>;
 }
@@ -48,144 +59,108 @@ const LocalFeedContext = createContext<LocalFeedContext>({
   setGlob: () => {},
   randomize: false,
   setRandomize: () => {},
+  sort: "name",
+  descending: false,
+  setSort: () => {},
+  collection: null,
+  setCollection: () => {},
   loadGlobFiles: async () => {},
   triggerReload: async () => {},
 });
 
-type FilesState =
-  | {
-      kind: "loading";
-    }
-  | {
-      kind: "loaded";
-      files: LocalFile[];
-    }
-  | { kind: "error" };
+/** A fresh random order for every feed, kept while that feed is paged */
+const newSeed = () => Math.floor(Math.random() * Number.MAX_SAFE_INTEGER);
 
 export const LocalFeedProvider = ({
   children,
 }: {
   children: React.ReactNode;
 }) => {
-  const { registerFeed } = useFeed();
-  const { getTagsForId } = useTags();
+  const { registerFeed, triggerReload: triggerFeedReload } = useFeed();
 
-  const [{ glob, randomize }, setSettings] =
+  const [settings, setSettings] =
     useState<LocalFeedContextSettings>(loadSettings());
-  const [bareFiles, setBareFiles] = useState<FilesState>({ kind: "loading" });
-  // const files = useMemo(() => {
-  //   if (bareFiles.kind === "loading") return bareFiles;
-  //   if (bareFiles.kind === "error") return bareFiles;
-  //   return {
-  //     kind: "loaded",
-  //     files:
-  //       tags.length > 0
-  //         ? bareFiles.files.filter((file) => {
-  //             const fileTags = getTagsForId(file.name);
-  //             const res = tags.every((tag) =>
-  //               fileTags.find((fT) => fT.name === tag.name),
-  //             );
-  //             if (fileTags.length)
-  //               console.log("file tags", fileTags, res, tags);
-  //             return res;
-  //           })
-  //         : bareFiles.files,
-  //   };
-  // }, [bareFiles, tags]);
-  // const feedState = useRef(0);
-
-  const setGlob = useCallback((glob: string | null) => {
-    setSettings((settings) => {
-      const newSettings = { ...settings, glob };
-      persistSettings(newSettings);
-      return newSettings;
-    });
-  }, []);
-
-  const setRandomize = useCallback((randomize: boolean) => {
-    setSettings((settings) => {
-      const newSettings = { ...settings, randomize };
-      persistSettings(newSettings);
-      return newSettings;
-    });
-  }, []);
-  const filesListener = useRef<((files: LocalFile[]) => void) | null>(null);
-  const feedFilesPass = useRef((callback: (files: LocalFile[]) => void) => {
-    filesListener.current = callback;
-  });
+  const { glob, randomize, sort, descending, collection } = settings;
+
+  const updateSettings = useCallback(
+    (update: Partial<LocalFeedContextSettings>) => {
+      setSettings((settings) => {
+        const newSettings = { ...settings, ...update };
+        persistSettings(newSettings);
+        return newSettings;
+      });
+    },
+    [],
+  );
+
+  const setGlob = useCallback(
+    (glob: string | null) => updateSettings({ glob }),
+    [],
+  );
+  const setRandomize = useCallback(
+    (randomize: boolean) => updateSettings({ randomize }),
+    [],
+  );
+  const setSort = useCallback(
+    (sort: SortKey, descending: boolean) =>
+      updateSettings({ sort, descending }),
+    [],
+  );
+  const setCollection = useCallback(
+    (collection: string | null) => updateSettings({ collection }),
+    [],
+  );
+
+  // the registered factory has to stay the same, feeds read the current
+  // settings when they are created
+  const settingsRef = useRef(settings);
+  settingsRef.current = settings;
+
   const feedFactory: FeedFactory<FinalFile, Filter> = useCallback(
-    (filter) => new LocalFeed(filter, feedFilesPass.current),
+    (filter) => {
+      const seed = newSeed();
+      return new LocalFeed(filter, (filter, cursor) => {
+        const { randomize, sort, descending, collection } =
+          settingsRef.current;
+        return listMedia({
+          sort: randomize ? "weighted" : sort,
+          descending,
+          seed,
+          query: toLibraryQuery(filter),
+          collection,
+          cursor,
+        });
+      });
+    },
     [],
   );
-  // const getNextFile = useCallback(async (): Promise<GetNextFileResult> => {
-  //   console.log("getting next local file", files);
-  //   if (files.kind === "loading") return { kind: "loading" };
-  //   if (files.kind === "loaded") {
-  //     const file = files.files[feedState.current];
-  //     feedState.current = feedState.current + 1;
-  //     if (!file) {
-  //       return {
-  //         kind: "none" as const,
-  //       };
-  //     }
-  //     return {
-  //       kind: "ok",
-  //       file: localFileToFinalFile(file),
-  //     };
-  //   }
-  //   return {
-  //     kind: "none" as const,
-  //   };
-  // }, [files]);
+
+  const triggerReload = useCallback(() => triggerFeedReload(), []);
 
   const loadGlobFiles = useCallback(() => {
     return invoke("clean_data_dir")
       .then(() => invoke("move_files_to_data_dir", { path: glob }))
-      .then(() => loadLocalFiles({ randomize }))
-      .then(() => {});
-  }, [randomize]);
-
-  const triggerReload = useCallback(() => {
-    return loadLocalFiles({ randomize }).then((files) => {
-      setBareFiles({
-        kind: "loaded",
-        files,
-      });
-    });
-  }, [loadGlobFiles]);
-
-  // useEffect(() => {
-  //   if (files.kind !== "loaded") return;
-  //   registerFeed({
-  //     id: LOCAL_FEED_NAME,
-  //     getNextFile,
-  //     isExhausted: () => {
-  //       console.log(feedState.current, files.files, bareFiles.files);
-  //       return files.files.length === feedState.current;
-  //     },
-  //   });
-  // }, [getNextFile, files]);
+      .then(triggerReload);
+  }, [glob]);
+
   useEffect(() => {
     triggerReload();
-  }, []);
+  }, [randomize, sort, descending, collection]);
   useEffect(() => {
     registerFeed({ name: LOCAL_FEED_NAME, factory: feedFactory });
   }, [feedFactory, registerFeed]);
-  // useEffect(() => {
-  //   feedState.current = 0;
-  // }, [files, asString]);
-  useEffect(() => {
-    if (filesListener.current && bareFiles.kind === "loaded") {
-      filesListener.current(bareFiles.files);
-    }
-  }, [bareFiles, feedFactory]);
   return (
     <LocalFeedContext.Provider
       value={{
         glob,
         randomize,
+        sort,
+        descending,
+        collection,
         setGlob,
         setRandomize,
+        setSort,
+        setCollection,
         loadGlobFiles,
         triggerReload,
       }}
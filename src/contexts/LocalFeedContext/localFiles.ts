@@ -57,13 +57,6 @@ export const convertFilePathToFileSrc = (
   return res;
 };
 
-export const convertFilePathToHttpSrc = (
-  filePath: string,
-  httpPort: number,
-) => {
-  return `http://192.68.1.32:${httpPort}/file?path=${filePath}`;
-};
-
 export const localFileToFinalFile = (file: LocalFile): FinalFile<LocalFile> => {
   const localFileURL = convertFilePathToFileSrc(file.name);
   // const dataFileURL = `data:${getFileKind(file)}/${getFileExtension(file)};base64,${file.data}`;
@@ -96,17 +89,45 @@ export const snipLocalFile = (
 export const moveFileToAssets = (filePath: string) =>
   invoke("move_file_to_data_dir", { dir: filePath });
 
-export const loadLocalFiles = ({
-  randomize,
-}: {
-  randomize: boolean;
-}): Promise<LocalFile[]> => {
-  return invoke(randomize ? "load_files_random" : "load_files", {})
-    .then((files: any) => {
-      return files.map((file: any) => {
-        file.type = "local";
-        return file;
-      });
-    })
-    .catch(console.error);
+export type SortKey =
+  | "name"
+  | "added"
+  | "modified"
+  | "size"
+  | "duration"
+  | "resolution"
+  | "views"
+  | "last_viewed"
+  | "rating"
+  | "favorite"
+  | "position"
+  | "random"
+  | "weighted";
+
+export type MediaPage = {
+  items: LocalFile[];
+  /** null on the last page */
+  cursor: string | null;
+  total: number;
 };
+
+export const listMedia = (params: {
+  sort: SortKey;
+  descending?: boolean;
+  seed?: number;
+  query?: string;
+  collection?: string | null;
+  cursor?: string | null;
+  limit?: number;
+}): Promise<MediaPage> =>
+  invoke<MediaPage>("list_media", {
+    params: {
+      ...params,
+      query: params.query || null,
+      collection: params.collection ?? null,
+      cursor: params.cursor ?? null,
+    },
+  }).then((page) => ({
+    ...page,
+    items: page.items.map((file) => ({ ...file, type: "local" as const })),
+  }));
@@ -8,4 +8,8 @@ export interface LocalFile {
   kind: string;
   extension: string;
   dimensions?: Dimensions;
+  favorite: boolean;
+  /** 1-5 stars */
+  rating: number | null;
+  note: string | null;
 }
@@ -7,6 +7,7 @@ import {
 import { Filter } from "../FilterContext";
 import { FinalFile } from "../../types";
 import { RemoteItem } from "./types";
+import { fetchRemotePage, RemotePage } from "../../api/remoteSources";
 
 export class RemoteFeedSingle extends FeedImpl<FinalFile, Filter> {
   id = "scrolller";
@@ -14,11 +15,13 @@ export class RemoteFeedSingle extends FeedImpl<FinalFile, Filter> {
   state = "ok" as FeedState;
   filter = {} as Filter;
 
-  private iterator = "";
+  private iterator: string | null = "";
   private items: RemoteItem[] = [];
   private currentIndex = 0;
-  private promise: Promise<{ iterator: string; items: RemoteItem[] }> | null =
-    null;
+  private promise: Promise<{
+    iterator: string | null;
+    items: RemoteItem[];
+  }> | null = null;
 
   constructor(
     filter: Filter,
@@ -35,12 +38,32 @@ export class RemoteFeedSingle extends FeedImpl<FinalFile, Filter> {
     }
 
     if (this.currentIndex >= this.items.length) {
+      // feeds without an iterator are a single page
+      if (this.iterator === null) {
+        this.setState("exhausted");
+        return { kind: "none" };
+      }
+
       try {
-        console.log(this.url);
-        const data = await (this.promise ??
-          (this.promise = fetch(this.url).then((res) => res.json())));
+        const url = new URL(this.url);
+        if (this.iterator) url.searchParams.set("iterator", this.iterator);
+        console.log(url.href);
+        const promise =
+          this.promise ??
+          (this.promise = fetch(url.href).then((res) => res.json()));
+        const data = await promise;
+        // a concurrent call already consumed this page
+        if (this.promise !== promise) return this.getNext();
+        this.promise = null;
         const { items } = data;
 
+        this.iterator = data.iterator ?? null;
+        this.currentIndex = 0;
+        if (items.length === 0) {
+          this.setState("exhausted");
+          return { kind: "none" };
+        }
+
         this.items = items.map((item: RemoteItem) => {
           return {
             ...item,
@@ -68,4 +91,70 @@ export class RemoteFeedSingle extends FeedImpl<FinalFile, Filter> {
   };
 }
 
+/** Pages through a source registered in the backend */
+export class RemoteSourceFeed extends FeedImpl<FinalFile, Filter> {
+  frequency = 1;
+  state = "ok" as FeedState;
+  filter = {} as Filter;
+
+  private iterator: string | null = "";
+  private items: FinalFile[] = [];
+  private currentIndex = 0;
+  private promise: Promise<RemotePage> | null = null;
+
+  constructor(
+    filter: Filter,
+    public id: string,
+  ) {
+    super();
+
+    this.filter = filter;
+  }
+
+  getNext = async (): Promise<FeedResult<FinalFile>> => {
+    if (this.state !== "ok") {
+      return { kind: "none" };
+    }
+
+    if (this.currentIndex >= this.items.length) {
+      if (this.iterator === null) {
+        this.setState("exhausted");
+        return { kind: "none" };
+      }
+
+      try {
+        const promise =
+          this.promise ??
+          (this.promise = fetchRemotePage(this.id, this.iterator || null));
+        const data = await promise;
+        // a concurrent call already consumed this page
+        if (this.promise !== promise) return this.getNext();
+        this.promise = null;
+
+        this.iterator = data.iterator;
+        this.currentIndex = 0;
+        this.items = data.items;
+        if (data.items.length === 0) {
+          this.setState("exhausted");
+          return { kind: "none" };
+        }
+
+        return this.getNext();
+      } catch (e) {
+        console.log(e);
+        this.promise = null;
+        this.setState("exhausted");
+        return { kind: "none" };
+      }
+    }
+
+    const item = this.items[this.currentIndex];
+    this.currentIndex++;
+    return {
+      kind: "ok",
+      value: item,
+    };
+  };
+}
+
 export class RemoteFeed extends ComposedFeedImpl<FinalFile, Filter> {}
@@ -11,8 +11,8 @@ import { Filter } from "./FilterContext";
 import { FinalFile } from "../types";
 import {
   getScrolllerData,
+  isTransientScrolllerError,
   ScrolllerResult,
-  Subreddit,
   SubredditPost,
 } from "../api/scrolller";
 
@@ -45,6 +45,7 @@ const scrolllerItemToFinalFile = (
         },
       },
     ],
+    additional: item,
   };
 };
 
@@ -57,8 +58,7 @@ class ScrolllerFeed extends FeedImpl<FinalFile, Filter> {
   private iterator = "";
   private items: SubredditPost[] = [];
   private currentIndex = 0;
-  private promise: Promise<{ iterator: string; items: Subreddit[] }> | null =
-    null;
+  private promise: Promise<ScrolllerResult> | null = null;
 
   constructor() {
     super();
@@ -76,10 +76,11 @@ class ScrolllerFeed extends FeedImpl<FinalFile, Filter> {
           (this.promise = getScrolllerData({
             iterator: this.iterator,
             filter: "VIDEO",
+            isNsfw: false,
           })));
         const { iterator, items: subreddits } = data;
 
-        this.iterator = iterator;
+        this.iterator = iterator ?? "";
 
         const items = subreddits.flatMap(
           (subreddit) => subreddit.children.items,
@@ -88,8 +89,18 @@ class ScrolllerFeed extends FeedImpl<FinalFile, Filter> {
         this.items.push(...items);
 
         return this.getNext();
-      } catch {
-        console.log("Scrolller exhausted");
+      } catch (e) {
+        this.promise = null;
+        if (isTransientScrolllerError(e)) {
+          // leave the feed usable so the next call retries
+          console.log("Scrolller temporarily unavailable", e);
+          return {
+            kind: "error",
+            error: new Error("Scrolller is temporarily unavailable"),
+          };
+        }
+
+        console.log("Scrolller exhausted", e);
         this.setState("exhausted");
         return { kind: "none" };
       }
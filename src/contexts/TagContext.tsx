@@ -1,12 +1,10 @@
 import { useHotkeysContext } from "react-hotkeys-hook";
 import { createContext, useContext, useState, useEffect } from "react";
 import { useSettings } from "./SettingsContext";
+import * as api from "../api/tags";
+import type { Tag, TagAssignments } from "../api/tags";
 
-export type Tag = {
-  name: string;
-  color?: string;
-  hotkey?: string;
-};
+export type { Tag };
 
 type TagContext = {
   tags: Tag[];
@@ -14,6 +12,7 @@ type TagContext = {
   addTag: (tag: Tag) => void;
   removeTag: (tag: Tag) => void;
   assignTag: (id: string, tag: Tag) => void;
+  unassignTag: (id: string, tag: Tag) => void;
 };
 
 const TagContext = createContext<TagContext>({
@@ -22,49 +21,55 @@ const TagContext = createContext<TagContext>({
   addTag: () => {},
   removeTag: () => {},
   assignTag: () => {},
+  unassignTag: () => {},
 });
 
-const persistTags = (tags: Tag[]) => {
-  localStorage.setItem("tags", JSON.stringify(tags));
-};
-
-const loadTags = (): Tag[] => {
+/** Moves the tags the webview used to keep in localStorage to the backend, once */
+const importLegacyTags = async () => {
   const tags = localStorage.getItem("tags");
-  if (tags) {
-    return JSON.parse(tags);
-  }
-  return [];
-};
+  const assignments = localStorage.getItem("tagAssignments");
+  if (!tags && !assignments) return;
 
-type TagAssignment = Record<string, string[]>;
+  const summary = await api.importLocalStorageTags(
+    tags ? JSON.parse(tags) : [],
+    assignments ? JSON.parse(assignments) : {},
+  );
+  console.log("imported localStorage tags", summary);
 
-const persistTagAssignments = (assignments: TagAssignment) => {
-  localStorage.setItem("tagAssignments", JSON.stringify(assignments));
+  localStorage.removeItem("tags");
+  localStorage.removeItem("tagAssignments");
 };
 
-const loadTagAssignments = (): TagAssignment => {
-  const assignments = localStorage.getItem("tagAssignments");
-  if (assignments) {
-    return JSON.parse(assignments);
-  }
-  return {};
-};
+/** Assignments are keyed by file name, the gallery passes whole paths */
+const toMediaId = (id: string) => id.split(/[\\/]/).pop() ?? id;
 
 export function TagProvider({ children }: { children: React.ReactNode }) {
   const { enableScope, disableScope } = useHotkeysContext();
   const { allowTags } = useSettings();
 
   const [tags, setTags] = useState<Tag[]>([]);
-  const [tagAssignments, setTagAssignments] = useState<TagAssignment>({});
+  const [tagAssignments, setTagAssignments] = useState<TagAssignments>({});
 
-  useEffect(() => {
-    const tags = loadTags();
-    setTags(tags);
+  const reload = () =>
+    Promise.all([api.listTags(), api.listTagAssignments()]).then(
+      ([tags, assignments]) => {
+        setTags(tags);
+        setTagAssignments(assignments);
+      },
+    );
 
-    const assignments = loadTagAssignments();
-    setTagAssignments(assignments);
+  useEffect(() => {
+    importLegacyTags()
+      .catch((e) => console.log("could not import localStorage tags", e))
+      .then(reload);
   }, []);
 
+  const setAssignment = (id: string) => (names: string[]) =>
+    setTagAssignments((assignments) => ({
+      ...assignments,
+      [toMediaId(id)]: names,
+    }));
+
   useEffect(() => {
     if (allowTags) enableScope("tags");
     else disableScope("tags");
@@ -75,23 +80,30 @@ export function TagProvider({ children }: { children: React.ReactNode }) {
       value={{
         tags,
         addTag: (tag: Tag) => {
-          setTags([...tags, tag]);
-          persistTags([...tags, tag]);
+          api.createTag(tag).then(reload).catch(console.log);
         },
         removeTag: (tag: Tag) => {
-          setTags(tags.filter((t) => t !== tag));
-          persistTags(tags.filter((t) => t !== tag));
+          api.deleteTag(tag.name).then(reload).catch(console.log);
         },
         assignTag: (id: string, tag: Tag) => {
-          const newAssignments = {
-            ...tagAssignments,
-            [id]: [...(tagAssignments[id] || []), tag.name],
-          };
-          setTagAssignments(newAssignments);
-          persistTagAssignments(newAssignments);
+          api
+            .assignTag(id, tag.name)
+            .then(setAssignment(id))
+            .then(() => {
+              // assigning creates unknown tags
+              if (!tags.some((t) => t.name === tag.name)) reload();
+            })
+            .catch(console.log);
+        },
+        unassignTag: (id: string, tag: Tag) => {
+          api
+            .unassignTag(id, tag.name)
+            .then(setAssignment(id))
+            .catch(console.log);
         },
         getTagsForId: (id: string) => {
-          return tags.filter((t) => tagAssignments[id]?.includes(t.name));
+          const names = tagAssignments[toMediaId(id)];
+          return tags.filter((t) => names?.includes(t.name));
         },
       }}
     >
@@ -14,6 +14,8 @@ import { Box } from "../components/Box";
 export function Gallery({}: {}) {
   const { feed } = useFeed();
   const [feedState, setFeedState] = useState<FeedState>(feed.state);
+  // the last getNext failed, the feed stays usable and retries on the next call
+  const [feedError, setFeedError] = useState<Error | null>(null);
   const galleryElementsRef = useRef<ScrollElement[]>([]);
   const [galleryElements, setGalleryElements] = useState<ScrollElement[]>([]);
   const { asString } = useFilter();
@@ -26,6 +28,7 @@ export function Gallery({}: {}) {
         return feed.getNext().then((result: FeedResult<FinalFile<unknown>>) => {
           switch (result.kind) {
             case "ok": {
+              setFeedError(null);
               const file = result.value;
               const res = {
                 data: file,
@@ -40,6 +43,7 @@ export function Gallery({}: {}) {
               return res;
             }
             case "error": {
+              setFeedError(result.error);
               return null;
             }
             case "none": {
@@ -157,6 +161,7 @@ export function Gallery({}: {}) {
 
   useEffect(() => {
     setGalleryElements([]);
+    setFeedError(null);
     const callback = (_: FeedState, newState: FeedState) => {
       setFeedState(newState);
     };
@@ -167,6 +172,19 @@ export function Gallery({}: {}) {
   }, [feed]);
   return (
     <Box>
+      {feedError && (
+        <div>
+          Error: {feedError.message}{" "}
+          <button
+            onClick={() => {
+              setFeedError(null);
+              getNextElement();
+            }}
+          >
+            Retry
+          </button>
+        </div>
+      )}
       {feedState === "ok" || feedState === "exhausted" ? (
         <Scroll
           exhausted={feed.state === "exhausted"}
@@ -183,6 +201,8 @@ export function Gallery({}: {}) {
         />
       ) : feedState === "loading" ? (
         <div>Loading...</div>
+      ) : feedState === "error" ? (
+        <div>Error: the feed failed to load</div>
       ) : (
         <div>No active feeds, go to settings</div>
       )}
@@ -1,4 +1,4 @@
-import { useMemo } from "react";
+import { useEffect, useMemo, useState } from "react";
 import Select from "react-select";
 import invert from "invert-color";
 
@@ -8,6 +8,17 @@ import {
   LOCAL_FEED_NAME,
   useLocalFeed,
 } from "../contexts/LocalFeedContext/LocalFeedContext";
+import { SortKey } from "../contexts/LocalFeedContext/localFiles";
+import {
+  CollectionInfo,
+  exportCollectionM3u,
+  listCollections,
+} from "../api/collections";
+import {
+  getOrderingWeights,
+  OrderingWeights,
+  setOrderingWeights,
+} from "../api/library";
 import { useFeed } from "../contexts/FeedContext";
 import { Section, Sections } from "../components/Section";
 import { LocalFileControl } from "./LocalFileControl";
@@ -80,6 +91,127 @@ function TagSelect() {
   );
 }
 
+const SORT_OPTIONS: { value: SortKey; label: string }[] = [
+  { value: "name", label: "Name" },
+  { value: "added", label: "Date added" },
+  { value: "modified", label: "Date modified" },
+  { value: "size", label: "Size" },
+  { value: "duration", label: "Duration" },
+  { value: "resolution", label: "Resolution" },
+  { value: "views", label: "View count" },
+  { value: "last_viewed", label: "Last viewed" },
+  { value: "rating", label: "Rating" },
+  { value: "favorite", label: "Favorites first" },
+  { value: "position", label: "Collection order" },
+  { value: "random", label: "Shuffle" },
+];
+
+function SortControl() {
+  const { sort, descending, setSort, randomize } = useLocalFeed();
+
+  return (
+    <div>
+      <label htmlFor="local-sort">Sort by</label>
+      <select
+        id="local-sort"
+        disabled={randomize}
+        value={sort}
+        onChange={(e) => setSort(e.target.value as SortKey, descending)}
+      >
+        {SORT_OPTIONS.map((option) => (
+          <option key={option.value} value={option.value}>
+            {option.label}
+          </option>
+        ))}
+      </select>
+      <BooleanControl
+        name="Descending"
+        value={descending}
+        setValue={(descending: boolean) => setSort(sort, descending)}
+      />
+    </div>
+  );
+}
+
+function UnseenFirstControl() {
+  const [weights, setWeights] = useState<OrderingWeights | null>(null);
+  const { triggerReload } = useLocalFeed();
+  useEffect(() => {
+    getOrderingWeights().then(setWeights).catch(console.log);
+  }, []);
+
+  if (!weights) return null;
+  return (
+    <BooleanControl
+      name="Unseen first"
+      value={weights.unseen_first}
+      setValue={(unseen_first: boolean) =>
+        setOrderingWeights({ ...weights, unseen_first })
+          .then(setWeights)
+          .then(triggerReload)
+          .catch(console.log)
+      }
+    />
+  );
+}
+
+function CollectionSelect() {
+  const { collection, setCollection } = useLocalFeed();
+  const [collections, setCollections] = useState<CollectionInfo[]>([]);
+  useEffect(() => {
+    listCollections().then(setCollections).catch(console.log);
+  }, []);
+  const selected = collections.find((c) => c.id === collection);
+
+  return (
+    <div>
+      <label htmlFor="local-collection">Collection</label>
+      <select
+        id="local-collection"
+        value={collection ?? ""}
+        onChange={(e) => setCollection(e.target.value || null)}
+      >
+        <option value="">Whole library</option>
+        {collections.map((c) => (
+          <option key={c.id} value={c.id}>
+            {c.name}
+          </option>
+        ))}
+      </select>
+      {selected && (
+        <button
+          onClick={() => exportCollectionM3u(selected).catch(console.log)}
+        >
+          Export M3U
+        </button>
+      )}
+    </div>
+  );
+}
+
+function QueryInput() {
+  const { query, setQuery } = useFilter();
+  const [draft, setDraft] = useState(query);
+  useEffect(() => setDraft(query), [query]);
+
+  return (
+    <div>
+      <label htmlFor="library-query">Query</label>
+      <input
+        id="library-query"
+        type="text"
+        placeholder='kind:video duration:>30s -tag:"seen"'
+        value={draft}
+        onChange={(e) => setDraft(e.target.value)}
+        onBlur={() => setQuery(draft)}
+        onKeyDown={(e) => {
+          if (e.key === "Enter") setQuery(draft);
+        }}
+      />
+    </div>
+  );
+}
+
 const h2 = (props: any) => <h3 {...props} />;
 
 export function FeedControl() {
@@ -120,6 +252,10 @@ export function FeedControl() {
               value={randomize}
               setValue={setRandomize}
             />
+            {randomize && <UnseenFirstControl />}
+            <CollectionSelect />
+            <SortControl />
+            <QueryInput />
             <LocalFileControl />
           </Section>
         )}
@@ -5,12 +5,17 @@ export interface Dimensions {
 }
 export interface LocalFile {
   type: "local";
+  id: string;
   name: string;
   lazy: boolean;
   data: string | null;
   kind: string;
   extension: string;
   dimensions?: Dimensions;
+  favorite: boolean;
+  /** 1-5 stars */
+  rating: number | null;
+  note: string | null;
 }
 
 export type File = LocalFile;